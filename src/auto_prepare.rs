@@ -0,0 +1,147 @@
+//! Automatically moves ships into the state required by an action.
+//!
+//! When enabled with [set_auto_prepare](SpaceTradersClient::set_auto_prepare), every ship action
+//! first prepares the ship instead of failing:
+//!
+//! - Trading, refuelling and delivering contract goods dock the ship.
+//! - Extracting, surveying and navigating put the ship in orbit, and wait for its cooldown.
+//! - Every action waits for the ship to arrive if it is in transit.
+//!
+//! The ship's state is taken from the client's cache, so no extra API calls are made when the ship
+//! is already in the right state.
+
+use crate::{
+    conditional_types::strings::Symbol, ship::ShipStatus, space_traders_client::SpaceTradersClient,
+    STResult,
+};
+use chrono::{DateTime, Utc};
+
+/// The state a ship must be in for an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Preparation {
+    /// The ship must be docked.
+    Dock,
+    /// The ship must be in orbit, and its cooldown must have expired.
+    Orbit,
+    /// The ship must not be in transit.
+    Arrive,
+}
+
+impl SpaceTradersClient {
+    /// Enables or disables automatically preparing ships for actions.
+    ///
+    /// This is disabled by default. The setting is copied to clones made after this call.
+    pub fn set_auto_prepare(&mut self, enabled: bool) {
+        self.auto_prepare = enabled;
+    }
+
+    pub fn auto_prepare(&self) -> bool {
+        self.auto_prepare
+    }
+
+    /// Get the time the ship's cooldown expires, if the client has seen one.
+    pub fn cooldown_expiration(&self, ship_symbol: &Symbol) -> Option<DateTime<Utc>> {
+        self.scheduler.cooldown_expiration(ship_symbol)
+    }
+
+    /// Moves the ship into the state required by an action, if auto-prepare is enabled.
+    pub(crate) async fn prepare_ship(
+        &self,
+        ship_symbol: &Symbol,
+        preparation: Preparation,
+    ) -> STResult<()> {
+        if !self.auto_prepare {
+            return Ok(());
+        }
+
+        self.await_arrival(ship_symbol).await?;
+
+        match preparation {
+            Preparation::Dock => {
+                self.dock_ship(ship_symbol).await?;
+            }
+            Preparation::Orbit => {
+                self.orbit_ship(ship_symbol).await?;
+                self.await_cooldown(ship_symbol).await;
+            }
+            Preparation::Arrive => {}
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the ship to arrive if it is in transit, if auto-prepare is enabled.
+    pub(crate) async fn await_arrival(&self, ship_symbol: &Symbol) -> STResult<()> {
+        if !self.auto_prepare {
+            return Ok(());
+        }
+
+        let nav = self.get_ship(ship_symbol)?.nav;
+        if nav.status != ShipStatus::InTransit {
+            return Ok(());
+        }
+
+        self.scheduler.clock().sleep_until(nav.route.arrival).await;
+
+        // Ships are in orbit around their destination once they arrive
+        self.update_ship(ship_symbol, |ship| {
+            if ship.nav.status == ShipStatus::InTransit {
+                ship.nav.status = ShipStatus::InOrbit;
+            }
+        })
+    }
+
+    async fn await_cooldown(&self, ship_symbol: &Symbol) {
+        if let Some(expiration) = self.cooldown_expiration(ship_symbol) {
+            self.scheduler.clock().sleep_until(expiration).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn waits_for_arrival_when_enabled() -> STResult<()> {
+        let mut client = fixtures::client(vec![fixtures::ship_in_transit()], vec![]);
+        let ship_symbol = Symbol::new("TST-RS-04-1").unwrap();
+
+        // Nothing is prepared while disabled
+        client
+            .prepare_ship(&ship_symbol, Preparation::Arrive)
+            .await?;
+        assert_eq!(
+            client.get_ship(&ship_symbol)?.nav.status,
+            ShipStatus::InTransit
+        );
+
+        // The arrival time has already passed, so this doesn't wait
+        client.set_auto_prepare(true);
+        client
+            .prepare_ship(&ship_symbol, Preparation::Arrive)
+            .await?;
+        assert_eq!(
+            client.get_ship(&ship_symbol)?.nav.status,
+            ShipStatus::InOrbit
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn waits_for_cooldown() {
+        let client = fixtures::client(vec![fixtures::ship_in_transit()], vec![]);
+        let ship_symbol = Symbol::new("TST-RS-04-1").unwrap();
+        assert_eq!(client.cooldown_expiration(&ship_symbol), None);
+
+        let expiration = Utc::now() + Duration::milliseconds(50);
+        client.scheduler.record_cooldown(&ship_symbol, expiration);
+        assert_eq!(client.cooldown_expiration(&ship_symbol), Some(expiration));
+
+        client.await_cooldown(&ship_symbol).await;
+        assert!(Utc::now() >= expiration);
+    }
+}
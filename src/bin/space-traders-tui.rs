@@ -0,0 +1,32 @@
+use space_traders::{
+    dashboard::Dashboard,
+    space_traders_client::{SpaceTradersClient, SAVEFILE},
+};
+use std::process::ExitCode;
+
+/// Opens the dashboard for the agent saved at the path given as the first argument, or at the
+/// default save file, and saves the agent back to it as the dashboard is used.
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let profile = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| SAVEFILE.to_string());
+
+    let dashboard = SpaceTradersClient::load_saved_from(&profile).and_then(Dashboard::new);
+    match dashboard {
+        Ok(mut dashboard) => {
+            dashboard.set_save_path(profile);
+            match dashboard.run().await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! A synchronous client, for scripts and tools that don't use `async`.
+//!
+//! The [BlockingSpaceTradersClient] wraps a [SpaceTradersClient] and runs each call to completion
+//! on its own single-threaded `tokio` runtime, so no runtime needs to be set up by the caller.
+//! Every API call of the async client has a blocking equivalent with the same name and arguments.
+//!
+//! The blocking client must not be used from within an async runtime, as blocking on a call there
+//! panics. Use the [SpaceTradersClient] directly instead.
+//!
+//! The blocking client is only available with the `blocking` feature.
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! let client = BlockingSpaceTradersClient::load_saved().unwrap();
+//! let ship = Symbol::new("TST-RS-04-1").unwrap();
+//!
+//! client.orbit_ship(&ship).unwrap();
+//! client
+//!     .navigate_ship(&ship, &Symbol::new("X1-ZA40-99095A").unwrap())
+//!     .unwrap();
+//! client.save_client().unwrap();
+//! ```
+
+use crate::{
+    agent::Agent,
+    conditional_types::{Id, Symbol},
+    contract::Contract,
+    faction::FactionSymbol,
+    market::{Market, MarketTransaction},
+    secrets::SecretStore,
+    ship::{Cooldown, Extraction, Nav, ShipType, Shipyard, ShipyardTransaction, Survey},
+    space_traders_client::SpaceTradersClient,
+    system::System,
+    waypoint::Waypoint,
+    STResult,
+};
+use std::{future::Future, path::Path, sync::Arc};
+use tokio::runtime::{Builder, Runtime};
+
+/// A [SpaceTradersClient] whose API calls block until they finish.
+///
+/// Clones share the same runtime, token and cache.
+#[derive(Debug, Clone)]
+pub struct BlockingSpaceTradersClient {
+    client: SpaceTradersClient,
+    runtime: Arc<Runtime>,
+}
+
+impl BlockingSpaceTradersClient {
+    /// Initalize a client to register with.
+    ///
+    /// See [SpaceTradersClient::new].
+    pub fn new() -> STResult<Self> {
+        Self::from_client(SpaceTradersClient::new())
+    }
+
+    /// Wraps an existing async client, e.g. one that was configured with a custom
+    /// [Transport](crate::transport::Transport).
+    pub fn from_client(client: SpaceTradersClient) -> STResult<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+
+        Ok(Self {
+            client,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Load a client from the save file.
+    ///
+    /// See [SpaceTradersClient::load_saved].
+    pub fn load_saved() -> STResult<Self> {
+        Self::from_client(SpaceTradersClient::load_saved()?)
+    }
+
+    /// Load a client from the save file at the given path.
+    ///
+    /// See [SpaceTradersClient::load_saved_from].
+    pub fn load_saved_from(path: impl AsRef<Path>) -> STResult<Self> {
+        Self::from_client(SpaceTradersClient::load_saved_from(path)?)
+    }
+
+    pub fn save_client(&self) -> STResult<()> {
+        self.client.save_client()
+    }
+
+    pub fn save_client_to(&self, path: impl AsRef<Path>) -> STResult<()> {
+        self.client.save_client_to(path)
+    }
+
+    /// See [SpaceTradersClient::save_cache_to].
+    pub fn save_cache_to(&self, path: impl AsRef<Path>) -> STResult<()> {
+        self.client.save_cache_to(path)
+    }
+
+    /// See [SpaceTradersClient::save_with_store].
+    pub fn save_with_store(&self, path: impl AsRef<Path>, store: &dyn SecretStore) -> STResult<()> {
+        self.client.save_with_store(path, store)
+    }
+
+    /// Load a client from the save file at the given path, with the token from the store.
+    ///
+    /// See [SpaceTradersClient::load_with_store].
+    pub fn load_with_store(path: impl AsRef<Path>, store: &dyn SecretStore) -> STResult<Self> {
+        Self::from_client(SpaceTradersClient::load_with_store(path, store)?)
+    }
+
+    /// See [SpaceTradersClient::save_encrypted_to].
+    #[cfg(feature = "encryption")]
+    pub fn save_encrypted_to(
+        &self,
+        path: impl AsRef<Path>,
+        key: &crate::secrets::SaveKey,
+    ) -> STResult<()> {
+        self.client.save_encrypted_to(path, key)
+    }
+
+    /// Load a client from a save file encrypted with the key.
+    ///
+    /// See [SpaceTradersClient::load_encrypted_from].
+    #[cfg(feature = "encryption")]
+    pub fn load_encrypted_from(
+        path: impl AsRef<Path>,
+        key: &crate::secrets::SaveKey,
+    ) -> STResult<Self> {
+        Self::from_client(SpaceTradersClient::load_encrypted_from(path, key)?)
+    }
+
+    /// The async client, which shares its token and cache with this one.
+    pub fn client(&self) -> &SpaceTradersClient {
+        &self.client
+    }
+
+    pub fn into_client(self) -> SpaceTradersClient {
+        self.client
+    }
+
+    pub fn set_base_url(&mut self, base_url: impl Into<String>) {
+        self.client.set_base_url(base_url);
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// See [SpaceTradersClient::register_callsign].
+    pub fn register_callsign(
+        &self,
+        callsign: &str,
+        faction: Option<FactionSymbol>,
+    ) -> STResult<()> {
+        self.block_on(self.client.register_callsign(callsign, faction))
+    }
+
+    pub fn agent(&self) -> STResult<Agent> {
+        self.client.agent()
+    }
+
+    pub fn contracts(&self) -> STResult<Vec<Contract>> {
+        self.client.contracts()
+    }
+
+    pub fn starting_system(&self) -> STResult<Symbol> {
+        self.client.starting_system()
+    }
+
+    /// See [SpaceTradersClient::accept_contract].
+    pub fn accept_contract(&self, contract_id: Id) -> STResult<()> {
+        self.block_on(self.client.accept_contract(contract_id))
+    }
+
+    /// See [SpaceTradersClient::deliver_contract].
+    pub fn deliver_contract(
+        &self,
+        contract_id: &Id,
+        ship_symbol: &Symbol,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<()> {
+        self.block_on(
+            self.client
+                .deliver_contract(contract_id, ship_symbol, trade_symbol, units),
+        )
+    }
+
+    /// See [SpaceTradersClient::fulfill_contract].
+    pub fn fulfill_contract(&self, contract_id: &Id) -> STResult<()> {
+        self.block_on(self.client.fulfill_contract(contract_id))
+    }
+
+    /// See [SpaceTradersClient::view_system].
+    pub fn view_system(&self, system_symbol: &Symbol) -> STResult<System> {
+        self.block_on(self.client.view_system(system_symbol))
+    }
+
+    /// See [SpaceTradersClient::view_waypoint].
+    pub fn view_waypoint(
+        &self,
+        system_symbol: Symbol,
+        waypoint_symbol: Symbol,
+    ) -> STResult<Waypoint> {
+        self.block_on(self.client.view_waypoint(system_symbol, waypoint_symbol))
+    }
+
+    /// See [SpaceTradersClient::view_market].
+    pub fn view_market(
+        &self,
+        system_symbol: &Symbol,
+        waypoint_symbol: &Symbol,
+    ) -> STResult<Market> {
+        self.block_on(self.client.view_market(system_symbol, waypoint_symbol))
+    }
+
+    /// See [SpaceTradersClient::sell_cargo].
+    pub fn sell_cargo(
+        &self,
+        ship_symbol: &Symbol,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<MarketTransaction> {
+        self.block_on(self.client.sell_cargo(ship_symbol, trade_symbol, units))
+    }
+
+    /// See [SpaceTradersClient::purchase_cargo].
+    pub fn purchase_cargo(
+        &self,
+        ship_symbol: &Symbol,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<MarketTransaction> {
+        self.block_on(self.client.purchase_cargo(ship_symbol, trade_symbol, units))
+    }
+
+    /// See [SpaceTradersClient::view_shipyard].
+    pub fn view_shipyard(
+        &self,
+        system_symbol: &Symbol,
+        waypoint_symbol: &Symbol,
+    ) -> STResult<Shipyard> {
+        self.block_on(self.client.view_shipyard(system_symbol, waypoint_symbol))
+    }
+
+    /// See [SpaceTradersClient::buy_ship].
+    pub fn buy_ship(
+        &self,
+        ship_type: ShipType,
+        waypoint_symbol: Symbol,
+    ) -> STResult<ShipyardTransaction> {
+        self.block_on(self.client.buy_ship(ship_type, waypoint_symbol))
+    }
+
+    /// See [SpaceTradersClient::dock_ship].
+    pub fn dock_ship(&self, ship_symbol: &Symbol) -> STResult<Nav> {
+        self.block_on(self.client.dock_ship(ship_symbol))
+    }
+
+    /// See [SpaceTradersClient::orbit_ship].
+    pub fn orbit_ship(&self, ship_symbol: &Symbol) -> STResult<Nav> {
+        self.block_on(self.client.orbit_ship(ship_symbol))
+    }
+
+    /// See [SpaceTradersClient::extract_resources].
+    pub fn extract_resources(
+        &self,
+        ship_symbol: &Symbol,
+        survey: Option<Survey>,
+    ) -> STResult<(Cooldown, Extraction)> {
+        self.block_on(self.client.extract_resources(ship_symbol, survey))
+    }
+
+    /// See [SpaceTradersClient::navigate_ship].
+    pub fn navigate_ship(&self, ship_symbol: &Symbol, waypoint_symbol: &Symbol) -> STResult<Nav> {
+        self.block_on(self.client.navigate_ship(ship_symbol, waypoint_symbol))
+    }
+
+    /// See [SpaceTradersClient::create_survey].
+    pub fn create_survey(&self, ship_symbol: &Symbol) -> STResult<(Cooldown, Vec<Survey>)> {
+        self.block_on(self.client.create_survey(ship_symbol))
+    }
+
+    /// See [SpaceTradersClient::refuel_ship].
+    pub fn refuel_ship(&self, ship_symbol: &Symbol) -> STResult<MarketTransaction> {
+        self.block_on(self.client.refuel_ship(ship_symbol))
+    }
+
+    /// See [SpaceTradersClient::jettison_cargo].
+    pub fn jettison_cargo(
+        &self,
+        ship_symbol: &Symbol,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<()> {
+        self.block_on(self.client.jettison_cargo(ship_symbol, trade_symbol, units))
+    }
+}
+
+impl From<SpaceTradersClient> for BlockingSpaceTradersClient {
+    /// Wraps the async client.
+    ///
+    /// # Panics
+    /// Panics if the runtime can't be created; use [from_client](Self::from_client) to handle the
+    /// error instead.
+    fn from(client: SpaceTradersClient) -> Self {
+        Self::from_client(client).expect("failed to create a runtime for the blocking client")
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{mock::MockServer, ship::ShipStatus};
+
+    #[test]
+    fn blocks_on_api_calls() -> STResult<()> {
+        // The mock server needs its own runtime, which the blocking client can't share
+        let runtime = tokio::runtime::Runtime::new()?;
+        let server = runtime.block_on(MockServer::start())?;
+
+        let mut client = BlockingSpaceTradersClient::new()?;
+        client.set_base_url(server.base_url());
+        client.register_callsign("BLOCKING", None)?;
+        let ship = Symbol::new("BLOCKING-1").unwrap();
+
+        assert_eq!(client.orbit_ship(&ship)?.status, ShipStatus::InOrbit);
+        let system = client.view_system(&client.starting_system()?)?;
+        assert_eq!(system.symbol, "X1-ZA40");
+
+        let id = client.contracts()?[0].id.clone();
+        client.accept_contract(id)?;
+        assert!(client.contracts()?[0].accepted);
+
+        // Clones of the async client share the same cache
+        assert!(client.client().contracts()?[0].accepted);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,461 @@
+//! Records real API traffic to cassette files, and replays it later without a network connection.
+//!
+//! A [CassetteServer] is a local proxy for the client. In [record](CassetteServer::record) mode it
+//! forwards every request to the real API and saves the request and response to a [Cassette] file.
+//! In [replay](CassetteServer::replay) mode it answers requests from that file instead, so a
+//! session captured once can be replayed in tests, and the recorded responses can be used to check
+//! that they still deserialize.
+//!
+//! Requests are matched by their method, path and JSON body. If the same request was recorded more
+//! than once, the responses are replayed in the order they were recorded.
+//!
+//! Bearer tokens are never written to cassettes: the `Authorization` header isn't recorded, and
+//! tokens in response bodies (e.g. from registering) are replaced with [REDACTED].
+//!
+//! Cassettes are only available with the `cassette` feature.
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! // Record a session against the real API...
+//! let recorder = CassetteServer::record("fixtures/register.json", DEFAULT_BASE_URL)
+//!     .await
+//!     .unwrap();
+//! recorder.client().register_callsign("CASSETTE", None).await.unwrap();
+//! drop(recorder);
+//!
+//! // ...and replay it offline
+//! let player = CassetteServer::replay("fixtures/register.json").await.unwrap();
+//! player.client().register_callsign("CASSETTE", None).await.unwrap();
+//! # })
+//! ```
+
+use crate::{rate_limiter::RateLimiter, space_traders_client::SpaceTradersClient, STResult};
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    net::{Ipv4Addr, SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+/// The value bearer tokens are replaced with in cassettes.
+pub const REDACTED: &str = "<REDACTED>";
+
+/// A recorded request, without its headers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// The path and query of the request, relative to the API's base URL.
+    pub path: String,
+    pub body: Option<Value>,
+}
+
+/// A recorded response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+/// A request and the response the API sent for it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// The interactions recorded in a cassette file, in the order they happened.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Loads the cassette at the given path.
+    pub fn load(path: impl AsRef<Path>) -> STResult<Self> {
+        let file = std::fs::File::open(path)?;
+
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+
+    /// Saves the cassette to the given path, creating its parent directories if needed.
+    pub fn save(&self, path: impl AsRef<Path>) -> STResult<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Finds the responses recorded for requests to the given path, e.g. to test deserializing
+    /// them.
+    pub fn responses<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a Value> + 'a {
+        self.interactions
+            .iter()
+            .filter(move |interaction| interaction.request.path == path)
+            .map(|interaction| &interaction.response.body)
+    }
+}
+
+#[derive(Debug)]
+enum Mode {
+    Record {
+        upstream: String,
+        client: reqwest::Client,
+        path: PathBuf,
+        /// Every token seen so far, so they can be redacted.
+        tokens: HashSet<String>,
+    },
+    Replay {
+        /// Whether each interaction has been replayed yet.
+        used: Vec<bool>,
+    },
+}
+
+#[derive(Debug)]
+struct CassetteState {
+    cassette: Cassette,
+    mode: Mode,
+}
+
+/// A local proxy that records API traffic to, or replays it from, a [Cassette] file.
+///
+/// The server is shut down when it is dropped.
+#[derive(Debug)]
+pub struct CassetteServer {
+    address: SocketAddr,
+    state: Arc<Mutex<CassetteState>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl CassetteServer {
+    /// Starts a server that forwards requests to `upstream` (e.g. [DEFAULT_BASE_URL](crate::space_traders_client::DEFAULT_BASE_URL)),
+    /// and records them to the cassette at `path`.
+    ///
+    /// The cassette is overwritten, and saved after every interaction.
+    pub async fn record(path: impl AsRef<Path>, upstream: impl Into<String>) -> STResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        Cassette::default().save(&path)?;
+
+        Self::start(CassetteState {
+            cassette: Cassette::default(),
+            mode: Mode::Record {
+                upstream: upstream.into().trim_end_matches('/').to_string(),
+                client: reqwest::Client::new(),
+                path,
+                tokens: HashSet::new(),
+            },
+        })
+    }
+
+    /// Starts a server that replays the cassette at `path`.
+    ///
+    /// Requests that weren't recorded are answered with a `404` error response.
+    pub async fn replay(path: impl AsRef<Path>) -> STResult<Self> {
+        let cassette = Cassette::load(path)?;
+        let used = vec![false; cassette.interactions.len()];
+
+        Self::start(CassetteState {
+            cassette,
+            mode: Mode::Replay { used },
+        })
+    }
+
+    fn start(state: CassetteState) -> STResult<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let state = Arc::new(Mutex::new(state));
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+        });
+
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let server = Server::from_tcp(listener)
+            .map_err(std::io::Error::other)?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+        tokio::spawn(server);
+
+        Ok(Self {
+            address,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// The URL to pass to [set_base_url](SpaceTradersClient::set_base_url).
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Creates a client that sends its requests through this server.
+    ///
+    /// When replaying, the client isn't rate limited.
+    pub fn client(&self) -> SpaceTradersClient {
+        let mut client = SpaceTradersClient::new();
+        client.set_base_url(self.base_url());
+        if let Mode::Replay { .. } = self.state.lock().unwrap().mode {
+            client.set_rate_limiter(RateLimiter::new(1_000., 1_000));
+        }
+
+        client
+    }
+
+    /// A copy of the interactions recorded or loaded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.state.lock().unwrap().cassette.clone()
+    }
+}
+
+impl Drop for CassetteServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<CassetteState>>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let (parts, body) = request.into_parts();
+    let token = parts
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(String::from);
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+    let request = RecordedRequest {
+        method: parts.method.to_string(),
+        path: parts
+            .uri
+            .path_and_query()
+            .map(|path| path.to_string())
+            .unwrap_or_default(),
+        body: serde_json::from_slice(&body).ok(),
+    };
+
+    let forward = match &mut state.lock().unwrap().mode {
+        Mode::Record {
+            upstream,
+            client,
+            tokens,
+            ..
+        } => {
+            tokens.extend(token.clone());
+            Some((client.clone(), format!("{}{}", upstream, request.path)))
+        }
+        Mode::Replay { .. } => None,
+    };
+
+    let response = match forward {
+        Some((client, url)) => record(&state, client, url, token, &body, request).await,
+        None => replay(&state, &request),
+    };
+
+    Ok(Response::builder()
+        .status(response.status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(response.body.to_string()))
+        .unwrap())
+}
+
+/// Forwards the request upstream, and records the redacted interaction.
+async fn record(
+    state: &Mutex<CassetteState>,
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+    body: &[u8],
+    request: RecordedRequest,
+) -> RecordedResponse {
+    let method = reqwest::Method::from_bytes(request.method.as_bytes()).unwrap();
+    let mut upstream = client
+        .request(method, url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_vec());
+    if let Some(token) = token {
+        upstream = upstream.bearer_auth(token);
+    }
+
+    let (status, body) = match upstream.send().await {
+        Ok(response) => {
+            let status = response.status();
+            match response.json::<Value>().await {
+                Ok(body) => (status.as_u16(), body),
+                Err(error) => (StatusCode::BAD_GATEWAY.as_u16(), proxy_error(error)),
+            }
+        }
+        Err(error) => (StatusCode::BAD_GATEWAY.as_u16(), proxy_error(error)),
+    };
+
+    let mut state = state.lock().unwrap();
+    let CassetteState { cassette, mode } = &mut *state;
+    let Mode::Record { path, tokens, .. } = mode else {
+        unreachable!("only recording servers forward requests");
+    };
+
+    // Registering returns a new token, which is used by every request after it
+    if let Some(token) = body.pointer("/data/token").and_then(Value::as_str) {
+        tokens.insert(token.to_string());
+    }
+    let response = RecordedResponse {
+        status,
+        body: body.clone(),
+    };
+    cassette.interactions.push(Interaction {
+        request,
+        response: RecordedResponse {
+            status,
+            body: redact(body, tokens),
+        },
+    });
+    // Recording continues even if the cassette can't be saved, so the session isn't interrupted
+    let _ = cassette.save(path);
+
+    response
+}
+
+/// Finds the first interaction matching the request that hasn't been replayed yet.
+fn replay(state: &Mutex<CassetteState>, request: &RecordedRequest) -> RecordedResponse {
+    let mut state = state.lock().unwrap();
+    let CassetteState { cassette, mode } = &mut *state;
+    let Mode::Replay { used } = mode else {
+        unreachable!("only replaying servers answer requests themselves");
+    };
+
+    let found = cassette
+        .interactions
+        .iter()
+        .enumerate()
+        .position(|(i, interaction)| !used[i] && interaction.request == *request);
+
+    match found {
+        Some(i) => {
+            used[i] = true;
+            cassette.interactions[i].response.clone()
+        }
+        None => RecordedResponse {
+            status: StatusCode::NOT_FOUND.as_u16(),
+            body: json!({
+                "error": {
+                    "code": 404,
+                    "message": format!(
+                        "No recorded response for {} {}.",
+                        request.method, request.path
+                    ),
+                },
+            }),
+        },
+    }
+}
+
+fn proxy_error(error: reqwest::Error) -> Value {
+    json!({
+        "error": {
+            "code": StatusCode::BAD_GATEWAY.as_u16(),
+            "message": format!("The request could not be forwarded: {}", error),
+        },
+    })
+}
+
+/// Replaces every token in the value with [REDACTED].
+fn redact(value: Value, tokens: &HashSet<String>) -> Value {
+    match value {
+        Value::String(string) if tokens.contains(&string) => Value::String(REDACTED.into()),
+        Value::Array(values) => values
+            .into_iter()
+            .map(|value| redact(value, tokens))
+            .collect(),
+        Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| (key, redact(value, tokens)))
+            .collect(),
+        value => value,
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        conditional_types::Symbol,
+        mock::MockServer,
+        ship::{Nav, ShipStatus},
+        ResponseData, SpaceTradersError,
+    };
+
+    #[tokio::test]
+    async fn replays_recorded_sessions() -> STResult<()> {
+        let path = std::env::temp_dir().join(format!("{}.cassette.json", uuid::Uuid::new_v4()));
+        let ship = Symbol::new("CASSETTE-1").unwrap();
+
+        let upstream = MockServer::start().await?;
+        let recorder = CassetteServer::record(&path, upstream.base_url()).await?;
+        let client = recorder.client();
+        client.register_callsign("CASSETTE", None).await?;
+        client.orbit_ship(&ship).await?;
+        client.dock_ship(&ship).await?;
+        client.orbit_ship(&ship).await?;
+        let token = client.token()?;
+        drop(recorder);
+        drop(upstream);
+
+        let contents = std::fs::read_to_string(&path)?;
+        assert!(!contents.contains(&token));
+        assert!(contents.contains(REDACTED));
+
+        // Repeated requests are answered in the order they were recorded
+        let player = CassetteServer::replay(&path).await?;
+        std::fs::remove_file(&path)?;
+        let client = player.client();
+        client.register_callsign("CASSETTE", None).await?;
+        assert_eq!(client.token()?, REDACTED);
+        assert_eq!(client.orbit_ship(&ship).await?.status, ShipStatus::InOrbit);
+        assert_eq!(client.dock_ship(&ship).await?.status, ShipStatus::Docked);
+        assert_eq!(client.orbit_ship(&ship).await?.status, ShipStatus::InOrbit);
+
+        match client.dock_ship(&ship).await {
+            Err(SpaceTradersError::ResponseError(error)) => assert_eq!(error.code, 404),
+            result => panic!("expected a missing recording: {:?}", result),
+        }
+
+        // Recorded responses can be checked against the response types directly
+        let cassette = player.cassette();
+        let path = format!("/my/ships/{}/orbit", ship);
+        assert_eq!(cassette.responses(&path).count(), 2);
+        #[derive(serde::Deserialize, Debug)]
+        struct OrbitResponse {
+            nav: Nav,
+        }
+        for response in cassette.responses(&path) {
+            let response: ResponseData<OrbitResponse> = serde_json::from_value(response.clone())?;
+            match response {
+                ResponseData::Data { data } => assert_eq!(data.nav.status, ShipStatus::InOrbit),
+                response => panic!("expected a nav response: {:?}", response),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,564 @@
+//! The `space-traders` command-line tool, for one-off actions with a saved agent.
+//!
+//! Every command except `register` loads the agent from a profile (a save file, see
+//! [load_saved_from](crate::space_traders_client::SpaceTradersClient::load_saved_from)), and saves
+//! it again afterwards, so the profile's cached ships and contracts stay up to date. Results are
+//! printed as a table, or as JSON with `--json`. `register` refuses to overwrite an existing
+//! profile unless `--force` is passed.
+//!
+//! The profile holds the agent's token in plaintext, unless it is kept in a
+//! [SecretStore](crate::secrets::SecretStore) with `--token-file` or `--token-env`. With the
+//! `encryption` feature, `--key-file` encrypts the whole profile instead (see
+//! [SaveKey](crate::secrets::SaveKey)).
+//!
+//! ```text
+//! space-traders register MYCALLSIGN --faction cosmic
+//! space-traders ships
+//! space-traders orbit MYCALLSIGN-1
+//! space-traders navigate MYCALLSIGN-1 X1-ZA40-99095A
+//! space-traders --json --profile other.save contracts
+//! space-traders --token-env SPACE_TRADERS_TOKEN ships
+//! ```
+//!
+//! The command-line tool is only available with the `cli` feature.
+
+use crate::{
+    blocking::BlockingSpaceTradersClient,
+    conditional_types::{Id, Symbol},
+    faction::FactionSymbol,
+    secrets::{EnvSecretStore, FileSecretStore, SecretStore},
+    ship::{Nav, ShipType},
+    space_traders_client::SAVEFILE,
+    STResult, SpaceTradersError,
+};
+use clap::{Parser, Subcommand};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt::Display, path::PathBuf};
+
+/// Everyday operations for a SpaceTraders agent.
+#[derive(Parser, Debug)]
+#[command(name = "space-traders", version)]
+pub struct Cli {
+    /// The save file of the agent.
+    #[arg(long, short, global = true, default_value = SAVEFILE)]
+    profile: PathBuf,
+
+    /// Keeps the token in this file instead of the profile.
+    #[arg(long, global = true, conflicts_with = "token_env")]
+    token_file: Option<PathBuf>,
+
+    /// Reads the token from this environment variable instead of the profile.
+    #[arg(long, global = true)]
+    token_env: Option<String>,
+
+    /// Encrypts the profile with the contents of this file.
+    #[cfg(feature = "encryption")]
+    #[arg(long, global = true, conflicts_with_all = ["token_file", "token_env"])]
+    key_file: Option<PathBuf>,
+
+    /// The URL of the API, e.g. to use a mock server.
+    #[arg(long, global = true)]
+    base_url: Option<String>,
+
+    /// Prints results as JSON instead of a table.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Registers a new agent, and saves it to the profile.
+    Register {
+        #[arg(value_parser = Symbol::new)]
+        callsign: Symbol,
+        #[arg(long, value_parser = parse_enum::<FactionSymbol>)]
+        faction: Option<FactionSymbol>,
+        /// Overwrites the profile if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Shows the agent.
+    Agent,
+    /// Shows the agent's ships.
+    Ships,
+    /// Shows the agent's contracts.
+    Contracts,
+    /// Accepts a contract.
+    Accept {
+        #[arg(value_parser = Id::new)]
+        contract_id: Id,
+    },
+    /// Shows a waypoint.
+    Waypoint {
+        #[arg(value_parser = Symbol::new)]
+        waypoint: Symbol,
+    },
+    /// Shows the ships for sale at a shipyard.
+    Shipyard {
+        #[arg(value_parser = Symbol::new)]
+        waypoint: Symbol,
+    },
+    /// Buys a ship at a shipyard, e.g. `buy-ship ship-mining-drone X1-ZA40-68707C`.
+    BuyShip {
+        #[arg(value_parser = parse_enum::<ShipType>)]
+        ship_type: ShipType,
+        #[arg(value_parser = Symbol::new)]
+        waypoint: Symbol,
+    },
+    /// Docks a ship at its waypoint.
+    Dock {
+        #[arg(value_parser = Symbol::new)]
+        ship: Symbol,
+    },
+    /// Moves a ship into orbit.
+    Orbit {
+        #[arg(value_parser = Symbol::new)]
+        ship: Symbol,
+    },
+    /// Navigates a ship to a waypoint in its system.
+    Navigate {
+        #[arg(value_parser = Symbol::new)]
+        ship: Symbol,
+        #[arg(value_parser = Symbol::new)]
+        waypoint: Symbol,
+    },
+    /// Extracts resources with a ship.
+    Extract {
+        #[arg(value_parser = Symbol::new)]
+        ship: Symbol,
+    },
+}
+
+impl Cli {
+    /// Runs the command, and returns what should be printed.
+    pub fn run(self) -> STResult<String> {
+        let mut client = match self.command {
+            Command::Register { force, .. } => {
+                // The profile holds the only copy of the existing agent's token
+                if !force && self.profile.exists() {
+                    return Err(SpaceTradersError::FileError(std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        format!(
+                            "the profile `{}` already exists; pass `--force` to overwrite it",
+                            self.profile.display()
+                        ),
+                    )));
+                }
+                BlockingSpaceTradersClient::new()?
+            }
+            _ => self.load()?,
+        };
+        if let Some(base_url) = &self.base_url {
+            client.set_base_url(base_url.as_str());
+        }
+
+        let output = match &self.command {
+            Command::Register {
+                callsign, faction, ..
+            } => {
+                client.register_callsign(callsign, *faction)?;
+                agent(&client)
+            }
+            Command::Agent => agent(&client),
+            Command::Ships => ships(&client),
+            Command::Contracts => contracts(&client),
+            Command::Accept { contract_id } => {
+                client.accept_contract(contract_id.clone())?;
+                contracts(&client)
+            }
+            Command::Waypoint { waypoint } => {
+                let waypoint = client.view_waypoint(system_of(waypoint)?, waypoint.clone())?;
+                let table = Table::new(["SYMBOL", "TYPE", "X", "Y", "TRAITS"]).row([
+                    waypoint.symbol.to_string(),
+                    name(&waypoint.waypoint_type),
+                    waypoint.x.to_string(),
+                    waypoint.y.to_string(),
+                    waypoint
+                        .traits
+                        .iter()
+                        .map(|t| name(&t.symbol))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ]);
+                Ok(Output::new(&waypoint, table))
+            }
+            Command::Shipyard { waypoint } => {
+                let shipyard = client.view_shipyard(&system_of(waypoint)?, waypoint)?;
+
+                // The prices are only listed while one of the agent's ships is docked there
+                let mut table = Table::new(["TYPE", "PRICE"]);
+                for ship_type in &shipyard.ship_types {
+                    let price = shipyard
+                        .ships
+                        .iter()
+                        .flatten()
+                        .find(|ship| ship.type_ == ship_type.type_)
+                        .map_or("-".into(), |ship| ship.purchase_price.to_string());
+                    table = table.row([name(&ship_type.type_), price]);
+                }
+                Ok(Output::new(&shipyard, table))
+            }
+            Command::BuyShip {
+                ship_type,
+                waypoint,
+            } => {
+                let transaction = client.buy_ship(ship_type.clone(), waypoint.clone())?;
+                let table = Table::new(["SHIP", "WAYPOINT", "PRICE"]).row([
+                    transaction.ship_symbol.to_string(),
+                    transaction.waypoint_symbol.to_string(),
+                    transaction.price.to_string(),
+                ]);
+                Ok(Output::new(&transaction, table))
+            }
+            Command::Dock { ship } => nav(ship, &client.dock_ship(ship)?),
+            Command::Orbit { ship } => nav(ship, &client.orbit_ship(ship)?),
+            Command::Navigate { ship, waypoint } => {
+                nav(ship, &client.navigate_ship(ship, waypoint)?)
+            }
+            Command::Extract { ship } => {
+                let (cooldown, extraction) = client.extract_resources(ship, None)?;
+                let table = Table::new(["SHIP", "YIELD", "UNITS", "COOLDOWN"]).row([
+                    extraction.ship_symbol.to_string(),
+                    extraction.yield_.symbol.to_string(),
+                    extraction.yield_.units.to_string(),
+                    format!("{}s", *cooldown.remaining_seconds),
+                ]);
+                Ok(Output::new(
+                    &serde_json::json!({ "cooldown": cooldown, "extraction": extraction }),
+                    table,
+                ))
+            }
+        }?;
+
+        self.save(&client)?;
+
+        if self.json {
+            Ok(serde_json::to_string_pretty(&output.json)?)
+        } else {
+            Ok(output.table.to_string())
+        }
+    }
+
+    /// Where the token is kept, if not in the profile.
+    fn store(&self) -> Option<Box<dyn SecretStore>> {
+        match (&self.token_file, &self.token_env) {
+            (Some(path), _) => Some(Box::new(FileSecretStore::new(path))),
+            (_, Some(var)) => Some(Box::new(EnvSecretStore::new(var))),
+            (None, None) => None,
+        }
+    }
+
+    fn load(&self) -> STResult<BlockingSpaceTradersClient> {
+        #[cfg(feature = "encryption")]
+        if let Some(key_file) = &self.key_file {
+            let key = crate::secrets::SaveKey::from_file(key_file)?;
+            return BlockingSpaceTradersClient::load_encrypted_from(&self.profile, &key);
+        }
+
+        match self.store() {
+            Some(store) => BlockingSpaceTradersClient::load_with_store(&self.profile, &*store),
+            None => BlockingSpaceTradersClient::load_saved_from(&self.profile),
+        }
+    }
+
+    fn save(&self, client: &BlockingSpaceTradersClient) -> STResult<()> {
+        #[cfg(feature = "encryption")]
+        if let Some(key_file) = &self.key_file {
+            let key = crate::secrets::SaveKey::from_file(key_file)?;
+            return client.save_encrypted_to(&self.profile, &key);
+        }
+
+        match self.store() {
+            Some(store) => client.save_with_store(&self.profile, &*store),
+            None => client.save_client_to(&self.profile),
+        }
+    }
+}
+
+/// The result of a command, in both output formats.
+struct Output {
+    json: serde_json::Value,
+    table: Table,
+}
+
+impl Output {
+    fn new(value: &impl Serialize, table: Table) -> Self {
+        Self {
+            json: serde_json::json!(value),
+            table,
+        }
+    }
+}
+
+/// Text in aligned columns.
+#[derive(Debug)]
+struct Table {
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new<const N: usize>(header: [&str; N]) -> Self {
+        Self {
+            rows: vec![header.map(String::from).to_vec()],
+        }
+    }
+
+    fn row<const N: usize>(mut self, row: [String; N]) -> Self {
+        self.rows.push(row.to_vec());
+        self
+    }
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut widths = vec![0; self.rows[0].len()];
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        for (i, row) in self.rows.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| format!("{:width$}", cell))
+                .collect::<Vec<_>>()
+                .join("  ");
+            f.write_str(line.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn agent(client: &BlockingSpaceTradersClient) -> STResult<Output> {
+    let agent = client.agent()?;
+    let table = Table::new(["SYMBOL", "HEADQUARTERS", "CREDITS"]).row([
+        agent.symbol.to_string(),
+        agent.headquarters.to_string(),
+        agent.credits.to_string(),
+    ]);
+
+    Ok(Output::new(&agent, table))
+}
+
+fn ships(client: &BlockingSpaceTradersClient) -> STResult<Output> {
+    let ships = client.client().ships()?;
+    let mut table = Table::new(["SYMBOL", "ROLE", "STATUS", "WAYPOINT", "FUEL", "CARGO"]);
+    for ship in &ships {
+        table = table.row([
+            ship.symbol.to_string(),
+            name(&ship.registration.role),
+            name(&ship.nav.status),
+            ship.nav.waypoint_symbol.to_string(),
+            format!("{}/{}", *ship.fuel.current, *ship.fuel.capacity),
+            format!("{}/{}", *ship.cargo.units, *ship.cargo.capacity),
+        ]);
+    }
+
+    Ok(Output::new(&ships, table))
+}
+
+fn contracts(client: &BlockingSpaceTradersClient) -> STResult<Output> {
+    let contracts = client.contracts()?;
+    let mut table = Table::new(["ID", "FACTION", "STATUS", "DEADLINE", "DELIVER", "PAYMENT"]);
+    for contract in &contracts {
+        let status = match (contract.accepted, contract.fulfilled) {
+            (_, true) => "FULFILLED",
+            (true, false) => "ACCEPTED",
+            (false, false) => "OFFERED",
+        };
+        let deliver = contract
+            .terms
+            .deliver
+            .iter()
+            .map(|d| {
+                format!(
+                    "{} {}/{} to {}",
+                    d.trade_symbol, d.units_fulfilled, d.units_required, d.destination_symbol
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let payment = contract.terms.payment.on_accepted + contract.terms.payment.on_fulfilled;
+
+        table = table.row([
+            contract.id.to_string(),
+            name(&contract.faction_symbol),
+            status.into(),
+            contract.terms.deadline.to_rfc3339(),
+            deliver,
+            payment.to_string(),
+        ]);
+    }
+
+    Ok(Output::new(&contracts, table))
+}
+
+fn nav(ship: &Symbol, nav: &Nav) -> STResult<Output> {
+    let table = Table::new(["SHIP", "STATUS", "WAYPOINT", "FLIGHT MODE", "ARRIVAL"]).row([
+        ship.to_string(),
+        name(&nav.status),
+        nav.waypoint_symbol.to_string(),
+        name(&nav.flight_mode),
+        nav.route.arrival.to_rfc3339(),
+    ]);
+
+    Ok(Output::new(nav, table))
+}
+
+/// The API's name of an enum variant, e.g. `IN_ORBIT`.
+fn name(value: &impl Serialize) -> String {
+    match serde_json::json!(value) {
+        serde_json::Value::String(name) => name,
+        value => value.to_string(),
+    }
+}
+
+/// Parses an enum variant from its API name, ignoring case and accepting `-` for `_`.
+fn parse_enum<T: DeserializeOwned>(name: &str) -> Result<T, serde_json::Error> {
+    serde_json::from_value(serde_json::Value::String(
+        name.to_uppercase().replace('-', "_"),
+    ))
+}
+
+/// The system a waypoint is in, e.g. `X1-ZA40` for `X1-ZA40-99095A`.
+fn system_of(waypoint: &Symbol) -> STResult<Symbol> {
+    waypoint
+        .rsplit_once('-')
+        .and_then(|(system, _)| Symbol::new(system).ok())
+        .ok_or_else(|| SpaceTradersError::InvalidWaypointSymbol(waypoint.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "mock")]
+    use crate::mock::MockServer;
+
+    #[cfg(feature = "mock")]
+    fn run(server: &MockServer, profile: &std::path::Path, args: &[&str]) -> STResult<String> {
+        let profile = profile.to_str().unwrap();
+        let base_url = server.base_url();
+        let args = [
+            "space-traders",
+            "--profile",
+            profile,
+            "--base-url",
+            &base_url,
+        ]
+        .into_iter()
+        .chain(args.iter().copied());
+
+        Cli::try_parse_from(args).unwrap().run()
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn runs_commands_against_a_profile() -> STResult<()> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        let server = runtime.block_on(MockServer::start())?;
+        let profile = std::env::temp_dir().join(format!("cli-{}.save", uuid::Uuid::new_v4()));
+
+        let agent = run(
+            &server,
+            &profile,
+            &["register", "CLI", "--faction", "cosmic"],
+        )?;
+        assert!(agent.starts_with("SYMBOL  HEADQUARTERS    CREDITS\nCLI"));
+
+        // The existing agent isn't overwritten by accident
+        let result = run(&server, &profile, &["register", "CLI-2"]);
+        assert!(matches!(result, Err(SpaceTradersError::FileError(_))));
+        let agent = run(&server, &profile, &["agent"])?;
+        assert!(agent.lines().nth(1).unwrap().starts_with("CLI "));
+
+        // Later commands load the saved profile
+        let nav = run(&server, &profile, &["orbit", "CLI-1"])?;
+        assert!(nav.lines().nth(1).unwrap().starts_with("CLI-1  IN_ORBIT"));
+        let ships = run(&server, &profile, &["ships"])?;
+        assert!(ships.contains("IN_ORBIT"));
+
+        let contracts = run(&server, &profile, &["--json", "contracts"])?;
+        let contracts: serde_json::Value = serde_json::from_str(&contracts)?;
+        let id = contracts[0]["id"].as_str().unwrap();
+        assert_eq!(contracts[0]["accepted"], false);
+
+        let contracts = run(&server, &profile, &["accept", id])?;
+        assert!(contracts.lines().nth(1).unwrap().contains("ACCEPTED"));
+
+        std::fs::remove_file(profile)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn keeps_the_token_out_of_the_profile() -> STResult<()> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        let server = runtime.block_on(MockServer::start())?;
+        let temp_path =
+            |ext: &str| std::env::temp_dir().join(format!("cli-{}.{}", uuid::Uuid::new_v4(), ext));
+
+        let profile = temp_path("save");
+        let token_file = temp_path("token");
+        let token_file_arg = token_file.to_str().unwrap();
+        run(
+            &server,
+            &profile,
+            &["--token-file", token_file_arg, "register", "TOKEN"],
+        )?;
+
+        let token = std::fs::read_to_string(&token_file)?;
+        assert!(!token.is_empty());
+        assert!(!std::fs::read_to_string(&profile)?.contains(&token));
+        let nav = run(
+            &server,
+            &profile,
+            &["--token-file", token_file_arg, "orbit", "TOKEN-1"],
+        )?;
+        assert!(nav.lines().nth(1).unwrap().starts_with("TOKEN-1  IN_ORBIT"));
+        std::fs::remove_file(&token_file)?;
+        std::fs::remove_file(&profile)?;
+
+        #[cfg(feature = "encryption")]
+        {
+            let key_file = temp_path("key");
+            std::fs::write(&key_file, "0123456789abcdef0123456789abcdef")?;
+            let key_file_arg = key_file.to_str().unwrap();
+            run(
+                &server,
+                &profile,
+                &["--key-file", key_file_arg, "register", "ENCRYPTED"],
+            )?;
+
+            assert!(!std::fs::read_to_string(&profile)?.contains("ENCRYPTED"));
+            assert!(run(&server, &profile, &["agent"]).is_err());
+            let agent = run(&server, &profile, &["--key-file", key_file_arg, "agent"])?;
+            assert!(agent.lines().nth(1).unwrap().starts_with("ENCRYPTED "));
+            std::fs::remove_file(&key_file)?;
+            std::fs::remove_file(&profile)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_enums_and_waypoints() {
+        assert_eq!(
+            parse_enum::<ShipType>("ship-mining-drone").unwrap(),
+            ShipType::ShipMiningDrone
+        );
+        assert!(parse_enum::<ShipType>("SPACESHIP").is_err());
+
+        let waypoint = Symbol::new("X1-ZA40-99095A").unwrap();
+        assert_eq!(system_of(&waypoint).unwrap(), "X1-ZA40");
+        assert!(system_of(&Symbol::new("X1").unwrap()).is_err());
+    }
+}
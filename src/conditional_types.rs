@@ -5,7 +5,7 @@ pub mod strings {
     use serde::{de::Visitor, Deserialize, Serialize};
     use std::{fmt::Display, ops::Deref};
 
-    #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+    #[derive(Debug, Clone, Serialize, PartialEq, Eq, Hash)]
     pub struct NonEmptyString(String);
 
     impl NonEmptyString {
@@ -1,12 +1,17 @@
 use crate::{
+    auto_prepare::Preparation,
     conditional_types::{Id, Symbol},
+    events::ClientEvent,
     faction::FactionSymbol,
+    ledger::{Activity, LedgerEntry},
     prelude::Agent,
+    ship::Cargo,
     space_traders_client::SpaceTradersClient,
     ResponseData, STResult, SpaceTradersError,
 };
+use std::sync::{Arc, RwLock};
 
-#[derive(serde::Deserialize, Debug, serde::Serialize)]
+#[derive(serde::Deserialize, Debug, serde::Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Contract {
     pub(crate) id: Id,
@@ -19,7 +24,7 @@ pub struct Contract {
     pub(crate) expiration: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(serde::Deserialize, Debug, serde::Serialize, PartialEq, Eq)]
+#[derive(serde::Deserialize, Debug, serde::Serialize, PartialEq, Eq, Clone, Copy)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub(crate) enum ContractType {
     Procurement,
@@ -27,7 +32,7 @@ pub(crate) enum ContractType {
     Shuttle,
 }
 
-#[derive(serde::Deserialize, Debug, serde::Serialize)]
+#[derive(serde::Deserialize, Debug, serde::Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ContractTerms {
     pub(crate) deadline: chrono::DateTime<chrono::Utc>,
@@ -35,14 +40,14 @@ pub(crate) struct ContractTerms {
     pub(crate) deliver: Vec<DeliverInfo>,
 }
 
-#[derive(serde::Deserialize, Debug, serde::Serialize)]
+#[derive(serde::Deserialize, Debug, serde::Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Payment {
     pub(crate) on_accepted: i32,
     pub(crate) on_fulfilled: i32,
 }
 
-#[derive(serde::Deserialize, Debug, serde::Serialize)]
+#[derive(serde::Deserialize, Debug, serde::Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct DeliverInfo {
     pub(crate) trade_symbol: Symbol,
@@ -53,79 +58,228 @@ pub(crate) struct DeliverInfo {
 
 impl SpaceTradersClient {
     /// Accept a specific contract given its ID.
-    pub async fn accept_contract(&mut self, contract_id: Id) -> STResult<()> {
+    pub async fn accept_contract(&self, contract_id: Id) -> STResult<()> {
         use reqwest::header::{
             HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE,
         };
 
-        let cache = self
-            .cache
-            .as_mut()
-            .ok_or(SpaceTradersError::EmptyCache(None))?;
-
-        let mut idx: Option<usize> = None; // Stores index of the given contract
-        for (i, contract) in cache.contracts.iter_mut().enumerate() {
-            if contract.id == *contract_id {
-                // Return w/out making API calls if the contract is already accepted
-                if contract.accepted {
-                    return Ok(());
-                } else {
-                    idx = Some(i);
-                    break;
-                }
-            }
-        }
-
-        // The contract id was not found in the cached data
-        if idx.is_none() {
-            return Err(SpaceTradersError::InvalidContractId(
-                contract_id.to_string(),
-            ));
+        // Return w/out making API calls if the contract is already accepted
+        let contract = self
+            .get_contract(&contract_id)
+            .map_err(|err| err.in_operation("accept_contract"))?;
+        if contract.accepted {
+            return Ok(());
         }
 
-        let url = format!(
-            "https://api.spacetraders.io/v2/my/contracts/{}/accept",
-            contract_id
-        );
+        let url = format!("{}/my/contracts/{}/accept", self.base_url, contract_id);
 
         let mut headers = HeaderMap::new();
         headers.insert(
             AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", self.token.as_ref().unwrap()))
+            HeaderValue::from_str(&format!("Bearer {}", self.token()?))
                 .map_err(SpaceTradersError::ReqwestHeaderError)?,
         );
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         headers.insert(CONTENT_LENGTH, HeaderValue::from_static("0"));
 
         // Send request
-        let res = self.client.post(url).headers(headers).send().await?;
+        self.rate_limiter.acquire().await;
+        let res = self.post(url).headers(headers).send().await?;
 
         #[derive(Debug, serde::Deserialize)]
         struct AcceptContractResponse {
-            #[serde(rename = "agent")]
-            _agent: Agent,
-            #[serde(rename = "contract")]
-            _contract: Contract,
+            agent: Agent,
+            contract: Contract,
         }
 
-        if let ResponseData::Error { error } =
-            res.json::<ResponseData<AcceptContractResponse>>().await?
-        {
-            return Err(SpaceTradersError::ResponseError(error));
+        match res.json::<ResponseData<AcceptContractResponse>>()? {
+            ResponseData::Data { data } => {
+                self.set_agent(data.agent)?;
+                self.update_contract(&contract_id, |contract| *contract = data.contract)?;
+                self.record_ledger(contract_payment(
+                    &contract,
+                    Activity::ContractAccepted,
+                    contract.terms.payment.on_accepted,
+                    self.balance()?,
+                    self.scheduler().clock().now(),
+                ));
+
+                Ok(())
+            }
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
+        }
+    }
+
+    /// Deliver cargo from the ship towards a contract.
+    ///
+    /// The ship must be docked at the contract's destination.
+    pub async fn deliver_contract(
+        &self,
+        contract_id: &Id,
+        ship_symbol: &Symbol,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<()> {
+        // Check if the contract and ship exist first
+        self.get_contract(contract_id)
+            .map_err(|err| err.in_operation("deliver_contract"))?;
+        self.get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("deliver_contract"))?;
+        self.prepare_ship(ship_symbol, Preparation::Dock).await?;
+
+        let url = format!("{}/my/contracts/{}/deliver", self.base_url, contract_id);
+
+        // Send request
+        self.rate_limiter.acquire().await;
+        let res = self
+            .post(url)
+            .headers(self.auth_headers()?)
+            .json(&serde_json::json!({
+                "shipSymbol": ship_symbol,
+                "tradeSymbol": trade_symbol,
+                "units": units,
+            }))
+            .send()
+            .await?;
+
+        #[derive(Debug, serde::Deserialize)]
+        struct DeliverContractResponse {
+            contract: Contract,
+            cargo: Cargo,
         }
 
-        // Find the contract with the given id, and set accepted to true
-        cache.contracts[idx.unwrap()].accepted = true;
-        cache.agent.credits += cache.contracts[idx.unwrap()].terms.payment.on_accepted;
+        match res.json::<ResponseData<DeliverContractResponse>>()? {
+            ResponseData::Data { data } => {
+                self.update_contract(contract_id, |contract| *contract = data.contract)?;
+                self.update_ship(ship_symbol, |ship| ship.cargo = data.cargo)?;
 
-        Ok(())
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_contract_delivery(contract_id, ship_symbol, trade_symbol, units);
+                }
+
+                Ok(())
+            }
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
+        }
+    }
+
+    /// Fulfill a contract once all of its deliveries have been made.
+    pub async fn fulfill_contract(&self, contract_id: &Id) -> STResult<()> {
+        use reqwest::header::{HeaderValue, CONTENT_LENGTH};
+
+        // Check if the contract exists first
+        let contract = self
+            .get_contract(contract_id)
+            .map_err(|err| err.in_operation("fulfill_contract"))?;
+
+        let url = format!("{}/my/contracts/{}/fulfill", self.base_url, contract_id);
+
+        let mut headers = self.auth_headers()?;
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("0"));
+
+        // Send request
+        self.rate_limiter.acquire().await;
+        let res = self.post(url).headers(headers).send().await?;
+
+        #[derive(Debug, serde::Deserialize)]
+        struct FulfillContractResponse {
+            agent: Agent,
+            contract: Contract,
+        }
+
+        match res.json::<ResponseData<FulfillContractResponse>>()? {
+            ResponseData::Data { data } => {
+                self.set_agent(data.agent)?;
+                self.update_contract(contract_id, |contract| *contract = data.contract)?;
+                self.record_ledger(contract_payment(
+                    &contract,
+                    Activity::ContractFulfilled,
+                    contract.terms.payment.on_fulfilled,
+                    self.balance()?,
+                    self.scheduler().clock().now(),
+                ));
+
+                Ok(())
+            }
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
+        }
+    }
+
+    /// Get the lock guarding the cached contract with the given ID.
+    fn contract_lock(&self, contract_id: &Id) -> STResult<Arc<RwLock<Contract>>> {
+        let cache = self.shared_cache()?;
+        let contracts = cache.contracts.read().unwrap();
+
+        contracts
+            .iter()
+            .find(|contract| contract.read().unwrap().id == *contract_id)
+            .cloned()
+            .ok_or_else(|| SpaceTradersError::InvalidContractId {
+                contract_id: contract_id.to_string(),
+                operation: None,
+            })
+    }
+
+    /// Get a copy of the cached contract with the given ID.
+    pub(crate) fn get_contract(&self, contract_id: &Id) -> STResult<Contract> {
+        Ok(self.contract_lock(contract_id)?.read().unwrap().clone())
+    }
+
+    /// Updates the cached contract with the given ID.
+    pub(crate) fn update_contract<R>(
+        &self,
+        contract_id: &Id,
+        update: impl FnOnce(&mut Contract) -> R,
+    ) -> STResult<R> {
+        let lock = self.contract_lock(contract_id)?;
+        let mut contract = lock.write().unwrap();
+
+        let before = contract.clone();
+        let result = update(&mut contract);
+        let after = contract.clone();
+        drop(contract);
+
+        if before != after {
+            self.emit(ClientEvent::ContractChanged {
+                contract_id: contract_id.clone(),
+                before: Box::new(before),
+                after: Box::new(after),
+            });
+        }
+
+        Ok(result)
     }
 }
 
-#[cfg(test)]
+/// Creates a [LedgerEntry] for a payment received for the contract at `timestamp`.
+fn contract_payment(
+    contract: &Contract,
+    activity: Activity,
+    amount: i32,
+    balance: i64,
+    timestamp: chrono::DateTime<chrono::Utc>,
+) -> LedgerEntry {
+    LedgerEntry {
+        timestamp,
+        activity,
+        ship_symbol: None,
+        counterpart: contract.id.to_string(),
+        trade_symbol: None,
+        units: None,
+        amount: amount as i64,
+        balance,
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
 mod tests {
     use super::*;
-    use std::str::FromStr;
+    use crate::{mock::MockServer, scheduler::ManualClock};
+    use std::{str::FromStr, sync::Arc};
 
     fn gen_callsign() -> String {
         use uuid::Uuid;
@@ -135,16 +289,21 @@ mod tests {
         callsign.to_string().get(0..13).unwrap().to_uppercase()
     }
 
-    #[test]
-    fn can_list_contracts() {
-        let client = SpaceTradersClient::load_saved().unwrap();
+    #[tokio::test]
+    async fn can_list_contracts() {
+        let clock = Arc::new(ManualClock::new(
+            chrono::DateTime::<chrono::Utc>::from_str("2023-05-17T04:18:05.930Z").unwrap(),
+        ));
+        let server = MockServer::start_with_clock(clock).await.unwrap();
+        let client = server.client();
+        client.register_callsign("TST-RS-04", None).await.unwrap();
 
         let contracts = client.contracts().unwrap();
 
         assert_eq!(contracts.len() as i32, 1);
 
         let contract = &contracts[0];
-        assert_eq!(contract.id, "clhr6zx0r07s2s60daxqce7b1");
+        assert_eq!(contract.id, "clmock0000000000000000002");
         assert_eq!(contract.faction_symbol, FactionSymbol::Cosmic);
         assert_eq!(contract.contract_type, ContractType::Procurement);
 
@@ -173,12 +332,18 @@ mod tests {
 
     #[tokio::test]
     async fn can_accept_contract() {
-        let mut client = SpaceTradersClient::new();
+        let server = MockServer::start().await.unwrap();
+        let client = server.client();
         client
             .register_callsign(&gen_callsign(), None)
             .await
             .unwrap();
 
+        // The credits are taken from the response, not added to the cached balance
+        let mut stale = client.agent().unwrap();
+        stale.credits = 0;
+        client.set_agent(stale).unwrap();
+
         let id = client.contracts().unwrap()[0].id.clone();
         client.accept_contract(id).await.unwrap();
 
@@ -188,4 +353,23 @@ mod tests {
         let credits = 100_000 + contract.terms.payment.on_accepted;
         assert_eq!(client.agent().unwrap().credits, credits);
     }
+
+    #[tokio::test]
+    async fn records_payments_at_the_clients_time() {
+        let start = chrono::DateTime::<chrono::Utc>::from_str("2023-05-17T04:18:05.930Z").unwrap();
+        let server = MockServer::start_with_clock(Arc::new(ManualClock::new(start)))
+            .await
+            .unwrap();
+        let mut client = server.client();
+        client.register_callsign("LEDGER", None).await.unwrap();
+        client.set_ledger(crate::ledger::InMemoryLedger::new());
+
+        let id = client.contracts().unwrap()[0].id.clone();
+        client.accept_contract(id).await.unwrap();
+
+        let entries = client.ledger().unwrap().entries(None, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].activity, Activity::ContractAccepted);
+        assert_eq!(entries[0].timestamp, start);
+    }
 }
@@ -0,0 +1,1052 @@
+//! Plans and executes contracts end-to-end.
+//!
+//! [plan_contract](SpaceTradersClient::plan_contract) decides, for every remaining delivery of a
+//! contract, where the goods should come from (bought at the cheapest recorded market or mined at
+//! an asteroid field), which ship should carry them, and estimates the cost and duration of the
+//! whole contract so it can be compared against its payment and deadline.
+//!
+//! A [ContractExecutor] then carries out the plan: it accepts the contract, sources and delivers
+//! the goods (making partial deliveries whenever a ship's cargo hold is full), and fulfills the
+//! contract once everything has been delivered.
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let client = SpaceTradersClient::load_saved().unwrap();
+//!
+//! let contract_id = Symbol::new("clhr6zx0r07s2s60daxqce7b1").unwrap();
+//! let system = client.view_system(&client.starting_system().unwrap()).await.unwrap();
+//!
+//! let plan = client
+//!     .plan_contract(&contract_id, &system, &ContractPlanOptions::default())
+//!     .unwrap();
+//!
+//! if plan.is_profitable() && plan.meets_deadline {
+//!     ContractExecutor::new(plan).run(&client).await.unwrap();
+//! }
+//! # })
+//! ```
+
+use crate::{
+    conditional_types::{Id, Symbol},
+    contract::Contract,
+    market::Market,
+    mining::{CancelHandle, MiningConfig, MiningEvent, MiningLoop, MiningStep},
+    ship::{Deposit, MountSymbol, Ship, ShipStatus},
+    space_traders_client::SpaceTradersClient,
+    system::System,
+    trade_routes::FUEL,
+    STResult, SpaceTradersError,
+};
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+
+/// The estimated time (in seconds) a ship spends cooling down after each extraction.
+const EXTRACTION_COOLDOWN: i64 = 70;
+
+/// Where the goods for a delivery come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sourcing {
+    /// Buy the goods at a market, at most `trade_volume` units at a time.
+    Buy {
+        market: Symbol,
+        price_per_unit: i64,
+        trade_volume: i64,
+    },
+    /// Mine the goods at an asteroid field.
+    Mine { asteroid_field: Symbol },
+    /// The goods can't be bought at any known market, and can't be mined.
+    Unavailable,
+}
+
+/// The plan for a single delivery of a contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryPlan {
+    pub trade_symbol: Symbol,
+    pub destination: Symbol,
+    /// The units still required when the plan was made.
+    pub units: i32,
+    pub source: Sourcing,
+    /// The ship that sources and delivers the goods.
+    pub ship_symbol: Symbol,
+    /// The number of round trips needed to deliver every unit.
+    pub trips: i64,
+    /// The estimated credits spent on goods and fuel.
+    pub cost: i64,
+    /// The estimated time (in seconds) the delivery takes.
+    pub duration: i64,
+}
+
+/// The plan for all remaining deliveries of a contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractPlan {
+    pub contract_id: Id,
+    pub deliveries: Vec<DeliveryPlan>,
+    /// The credits still to be paid out by the contract.
+    pub payment: i64,
+    /// The estimated credits spent over all deliveries.
+    pub cost: i64,
+    /// The estimated time (in seconds) to complete every delivery, one after another.
+    pub duration: i64,
+    pub deadline: DateTime<Utc>,
+    /// Whether the contract is estimated to be completed before its deadline.
+    pub meets_deadline: bool,
+}
+
+impl ContractPlan {
+    pub fn profit(&self) -> i64 {
+        self.payment - self.cost
+    }
+
+    pub fn is_profitable(&self) -> bool {
+        self.profit() > 0
+    }
+
+    /// Whether every delivery has a source for its goods.
+    pub fn is_feasible(&self) -> bool {
+        self.deliveries
+            .iter()
+            .all(|delivery| delivery.source != Sourcing::Unavailable)
+    }
+}
+
+/// Options for [plan_contract](SpaceTradersClient::plan_contract).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContractPlanOptions {
+    /// The asteroid field to mine at; if not set, goods will only be bought.
+    pub asteroid_field: Option<Symbol>,
+    /// The ships that can be used; if not set, any ship of the agent can be used.
+    pub ships: Option<Vec<Symbol>>,
+}
+
+/// The sum of the strengths of the ship's mining lasers.
+fn mining_strength(ship: &Ship) -> i64 {
+    ship.mounts
+        .iter()
+        .filter(|mount| {
+            matches!(
+                mount.symbol,
+                MountSymbol::MountMiningLaserI
+                    | MountSymbol::MountMiningLaserII
+                    | MountSymbol::MountMiningLaserIII
+            )
+        })
+        .map(|mount| *mount.strength)
+        .sum()
+}
+
+/// Whether the good can be extracted from an asteroid field.
+fn is_mineable(trade_symbol: &Symbol) -> bool {
+    serde_json::from_value::<Deposit>(trade_symbol.as_str().into()).is_ok()
+}
+
+fn div_ceil(a: i64, b: i64) -> i64 {
+    (a + b - 1) / b.max(1)
+}
+
+impl SpaceTradersClient {
+    /// Plans the remaining deliveries of a contract in the given system.
+    ///
+    /// Purchase prices and fuel prices are taken from the attached
+    /// [MarketStore](crate::market_history::MarketStore); without one, goods can only be mined.
+    /// Goods are bought whenever buying everything the contract requires is profitable, and mined
+    /// otherwise. Deliveries whose goods can't be bought or mined are marked
+    /// [Unavailable](Sourcing::Unavailable), which makes the plan
+    /// [infeasible](ContractPlan::is_feasible).
+    ///
+    /// **NOTE:** The durations are estimates. Mining assumes each extraction yields as many units
+    /// as the strength of the ship's mining lasers.
+    pub fn plan_contract(
+        &self,
+        contract_id: &Id,
+        system: &System,
+        options: &ContractPlanOptions,
+    ) -> STResult<ContractPlan> {
+        let contract = self
+            .get_contract(contract_id)
+            .map_err(|err| err.in_operation("plan_contract"))?;
+
+        let ships: Vec<Ship> = match &options.ships {
+            Some(symbols) => symbols
+                .iter()
+                .map(|symbol| self.get_ship(symbol))
+                .collect::<STResult<_>>()?,
+            None => self.ships()?,
+        };
+        let ships: Vec<Ship> = ships
+            .into_iter()
+            .filter(|ship| *ship.cargo.capacity > 0)
+            .collect();
+
+        let store = self.market_store();
+        let fuel_price = match store {
+            Some(store) => store
+                .latest_prices(&system.symbol)?
+                .into_iter()
+                .filter(|record| record.trade_symbol == FUEL)
+                .map(|record| record.purchase_price)
+                .min()
+                .unwrap_or(0),
+            None => 0,
+        };
+
+        let mut payment = contract.terms.payment.on_fulfilled as i64;
+        if !contract.accepted {
+            payment += contract.terms.payment.on_accepted as i64;
+        }
+
+        let mut sourcing_options = Vec::new();
+        for deliver in &contract.terms.deliver {
+            let units = deliver.units_required - deliver.units_fulfilled;
+            if units <= 0 {
+                continue;
+            }
+
+            let buy = match store {
+                Some(store) => store.best_buy_location(&system.symbol, &deliver.trade_symbol)?,
+                None => None,
+            };
+            let buy_plan = match buy {
+                Some(record) => ships
+                    .iter()
+                    .map(|ship| {
+                        let capacity = *ship.cargo.capacity;
+                        let planner = self.route_planner(&ship.symbol, system)?;
+                        let leg = |from: &Symbol, to: &Symbol| {
+                            planner
+                                .leg(from, to)
+                                .map_or((0, 0), |leg| (leg.travel_time, leg.fuel))
+                        };
+
+                        let trips = div_ceil(units as i64, capacity);
+                        let to_market = leg(&ship.nav.waypoint_symbol, &record.waypoint_symbol);
+                        let outbound = leg(&record.waypoint_symbol, &deliver.destination_symbol);
+                        let inbound = leg(&deliver.destination_symbol, &record.waypoint_symbol);
+
+                        let duration = to_market.0 + trips * outbound.0 + (trips - 1) * inbound.0;
+                        let fuel = to_market.1 + trips * outbound.1 + (trips - 1) * inbound.1;
+
+                        Ok(DeliveryPlan {
+                            trade_symbol: deliver.trade_symbol.clone(),
+                            destination: deliver.destination_symbol.clone(),
+                            units,
+                            source: Sourcing::Buy {
+                                market: record.waypoint_symbol.clone(),
+                                price_per_unit: record.purchase_price,
+                                trade_volume: record.trade_volume,
+                            },
+                            ship_symbol: ship.symbol.clone(),
+                            trips,
+                            cost: units as i64 * record.purchase_price + fuel * fuel_price,
+                            duration,
+                        })
+                    })
+                    .collect::<STResult<Vec<_>>>()?
+                    .into_iter()
+                    .min_by_key(|plan| plan.duration),
+                None => None,
+            };
+
+            let mine_plan = match &options.asteroid_field {
+                Some(field) if is_mineable(&deliver.trade_symbol) => ships
+                    .iter()
+                    .filter(|ship| mining_strength(ship) > 0)
+                    .map(|ship| {
+                        let capacity = *ship.cargo.capacity;
+                        let planner = self.route_planner(&ship.symbol, system)?;
+                        let leg = |from: &Symbol, to: &Symbol| {
+                            planner
+                                .leg(from, to)
+                                .map_or((0, 0), |leg| (leg.travel_time, leg.fuel))
+                        };
+
+                        let trips = div_ceil(units as i64, capacity);
+                        let extractions = div_ceil(units as i64, mining_strength(ship));
+                        let to_field = leg(&ship.nav.waypoint_symbol, field);
+                        let outbound = leg(field, &deliver.destination_symbol);
+                        let inbound = leg(&deliver.destination_symbol, field);
+
+                        let duration = to_field.0
+                            + extractions * EXTRACTION_COOLDOWN
+                            + trips * (outbound.0 + inbound.0);
+                        let fuel = to_field.1 + trips * (outbound.1 + inbound.1);
+
+                        Ok(DeliveryPlan {
+                            trade_symbol: deliver.trade_symbol.clone(),
+                            destination: deliver.destination_symbol.clone(),
+                            units,
+                            source: Sourcing::Mine {
+                                asteroid_field: field.clone(),
+                            },
+                            ship_symbol: ship.symbol.clone(),
+                            trips,
+                            cost: fuel * fuel_price,
+                            duration,
+                        })
+                    })
+                    .collect::<STResult<Vec<_>>>()?
+                    .into_iter()
+                    .min_by_key(|plan| plan.duration),
+                _ => None,
+            };
+
+            sourcing_options.push((deliver, units, buy_plan, mine_plan));
+        }
+
+        // Mining is only worth it if buying would cost more than the whole contract pays
+        let buy_cost: i64 = sourcing_options
+            .iter()
+            .filter_map(|(_, _, buy, mine)| buy.as_ref().or(mine.as_ref()))
+            .map(|plan| plan.cost)
+            .sum();
+        let prefer_mining = buy_cost >= payment;
+
+        let mut deliveries = Vec::new();
+        for (deliver, units, buy_plan, mine_plan) in sourcing_options {
+            let plan = match (buy_plan, mine_plan) {
+                (Some(_), Some(mine)) if prefer_mining => mine,
+                (Some(buy), _) => buy,
+                (None, Some(mine)) => mine,
+                (None, None) => DeliveryPlan {
+                    trade_symbol: deliver.trade_symbol.clone(),
+                    destination: deliver.destination_symbol.clone(),
+                    units,
+                    source: Sourcing::Unavailable,
+                    ship_symbol: ships.first().map(|ship| ship.symbol.clone()).ok_or(
+                        SpaceTradersError::EmptyCache {
+                            operation: Some("plan_contract"),
+                            data: None,
+                        },
+                    )?,
+                    trips: 0,
+                    cost: 0,
+                    duration: 0,
+                },
+            };
+            deliveries.push(plan);
+        }
+
+        let cost = deliveries.iter().map(|delivery| delivery.cost).sum();
+        let duration = deliveries.iter().map(|delivery| delivery.duration).sum();
+
+        Ok(ContractPlan {
+            contract_id: contract_id.clone(),
+            payment,
+            cost,
+            duration,
+            deadline: contract.terms.deadline,
+            meets_deadline: self.scheduler.clock().now() + Duration::seconds(duration)
+                <= contract.terms.deadline,
+            deliveries,
+        })
+    }
+}
+
+/// Progress reported by a [ContractExecutor].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContractEvent {
+    Accepted {
+        contract_id: Id,
+    },
+    Navigating {
+        ship_symbol: Symbol,
+        destination: Symbol,
+        arrival: DateTime<Utc>,
+    },
+    Purchased {
+        ship_symbol: Symbol,
+        trade_symbol: Symbol,
+        units: i64,
+        total_price: i64,
+    },
+    Delivered {
+        ship_symbol: Symbol,
+        trade_symbol: Symbol,
+        units: i32,
+        units_fulfilled: i32,
+        units_required: i32,
+    },
+    /// Progress of a ship mining goods for the contract.
+    Mining {
+        ship_symbol: Symbol,
+        event: MiningEvent,
+    },
+    Fulfilled {
+        contract_id: Id,
+    },
+    Stopped,
+}
+
+/// The outcome of a single [ContractExecutor::step].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractStep {
+    /// An action was performed; the next step can be taken immediately.
+    Continue,
+    /// The ship is busy (travelling or cooling down) until the given time.
+    WaitUntil(DateTime<Utc>),
+    /// The contract has been fulfilled.
+    Finished,
+}
+
+/// The next action a [ContractExecutor] should take.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ContractAction {
+    Accept,
+    Fulfill,
+    Finish,
+    NextDelivery,
+    Mine,
+    Wait(DateTime<Utc>),
+    Arrive,
+    Dock,
+    Orbit,
+    Navigate(Symbol),
+    Purchase(Symbol, i32),
+    Deliver(Symbol, i32),
+    Sell(Symbol, i32),
+    Jettison(Symbol, i32),
+}
+
+/// Carries out a [ContractPlan], one delivery at a time.
+#[derive(Debug)]
+pub struct ContractExecutor {
+    plan: ContractPlan,
+    delivery: usize,
+    mining: Option<MiningLoop>,
+    market: Option<Box<Market>>,
+    unsellable: Vec<Symbol>,
+    events: Option<mpsc::UnboundedSender<ContractEvent>>,
+    cancel_tx: Arc<watch::Sender<bool>>,
+    cancel_rx: watch::Receiver<bool>,
+}
+
+impl ContractExecutor {
+    pub fn new(plan: ContractPlan) -> Self {
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+
+        Self {
+            plan,
+            delivery: 0,
+            mining: None,
+            market: None,
+            unsellable: vec![],
+            events: None,
+            cancel_tx: Arc::new(cancel_tx),
+            cancel_rx,
+        }
+    }
+
+    pub fn plan(&self) -> &ContractPlan {
+        &self.plan
+    }
+
+    /// Get a receiver for the events reported by the executor.
+    ///
+    /// Only the most recent receiver gets the events.
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<ContractEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.events = Some(tx);
+        rx
+    }
+
+    /// Get a handle that can be used to stop the executor from another task.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle(self.cancel_tx.clone())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel_rx.borrow()
+    }
+
+    /// Runs the plan until the contract is fulfilled, the executor is cancelled, or an error
+    /// occurs.
+    ///
+    /// Steps that fail with a [retryable](SpaceTradersError::is_retryable) error are retried after
+    /// a short delay.
+    ///
+    /// Returns [SpaceTradersError::ContractInfeasible] if the plan isn't
+    /// [feasible](ContractPlan::is_feasible), and [SpaceTradersError::ContractDeadlinePassed] if
+    /// the contract's deadline passes before it is fulfilled.
+    pub async fn run(&mut self, client: &SpaceTradersClient) -> STResult<()> {
+        let mut cancel_rx = self.cancel_rx.clone();
+
+        while !self.is_cancelled() {
+            let step = match self.step(client).await {
+                Ok(step) => step,
+                Err(err) => ContractStep::WaitUntil(client.retry_at(&err).ok_or(err)?),
+            };
+            match step {
+                ContractStep::Continue => {}
+                ContractStep::WaitUntil(until) => {
+                    tokio::select! {
+                        _ = client.scheduler().clock().sleep_until(until) => {}
+                        _ = cancel_rx.changed() => {}
+                    }
+                }
+                ContractStep::Finished => return Ok(()),
+            }
+        }
+
+        self.emit(ContractEvent::Stopped);
+
+        Ok(())
+    }
+
+    /// Performs the next action of the plan.
+    ///
+    /// This never waits; if the ship is busy the time it will be ready is returned instead.
+    pub async fn step(&mut self, client: &SpaceTradersClient) -> STResult<ContractStep> {
+        let contract_id = self.plan.contract_id.clone();
+        if !self.plan.is_feasible() {
+            return Err(SpaceTradersError::ContractInfeasible(
+                contract_id.to_string(),
+            ));
+        }
+        let contract = client.get_contract(&contract_id)?;
+
+        let now = client.scheduler().clock().now();
+        if !contract.fulfilled && now > contract.terms.deadline {
+            return Err(SpaceTradersError::ContractDeadlinePassed(
+                contract_id.to_string(),
+            ));
+        }
+
+        let ship = match self.plan.deliveries.get(self.delivery) {
+            Some(delivery) => Some(client.get_ship(&delivery.ship_symbol)?),
+            None => None,
+        };
+        let action = self.next_action(&contract, ship.as_ref(), now);
+
+        let ship_symbol = ship.as_ref().map(|ship| ship.symbol.clone());
+        let ship_symbol = ship_symbol.as_ref();
+        match action {
+            ContractAction::Accept => {
+                client.accept_contract(contract_id.clone()).await?;
+                self.emit(ContractEvent::Accepted { contract_id });
+            }
+            ContractAction::Fulfill => {
+                client.fulfill_contract(&contract_id).await?;
+                self.emit(ContractEvent::Fulfilled { contract_id });
+                return Ok(ContractStep::Finished);
+            }
+            ContractAction::Finish => return Ok(ContractStep::Finished),
+            ContractAction::NextDelivery => {
+                if let Some(mining) = self.mining.take() {
+                    mining.cancel_handle().cancel();
+                }
+                self.unsellable.clear();
+                self.delivery += 1;
+            }
+            ContractAction::Mine => {
+                let delivery = &self.plan.deliveries[self.delivery];
+                let mining = self.mining.get_or_insert_with(|| {
+                    let asteroid_field = match &delivery.source {
+                        Sourcing::Mine { asteroid_field } => asteroid_field.clone(),
+                        _ => unreachable!(),
+                    };
+
+                    let mut config =
+                        MiningConfig::new(asteroid_field, delivery.destination.clone());
+                    config.contract_id = Some(contract_id.clone());
+                    MiningLoop::new(delivery.ship_symbol.clone(), config)
+                });
+
+                // Forward the mining events
+                let mut events = mining.subscribe();
+                let step = mining.step(client).await?;
+                let ship_symbol = mining.ship_symbol().clone();
+                while let Ok(event) = events.try_recv() {
+                    self.emit(ContractEvent::Mining {
+                        ship_symbol: ship_symbol.clone(),
+                        event,
+                    });
+                }
+
+                if let MiningStep::WaitUntil(until) = step {
+                    return Ok(ContractStep::WaitUntil(until));
+                }
+            }
+            ContractAction::Wait(until) => return Ok(ContractStep::WaitUntil(until)),
+            ContractAction::Arrive => {
+                client.update_ship(ship_symbol.unwrap(), |ship| {
+                    ship.nav.status = ShipStatus::InOrbit
+                })?;
+            }
+            ContractAction::Dock => {
+                client.dock_ship(ship_symbol.unwrap()).await?;
+            }
+            ContractAction::Orbit => {
+                client.orbit_ship(ship_symbol.unwrap()).await?;
+            }
+            ContractAction::Navigate(destination) => {
+                let nav = client
+                    .navigate_ship(ship_symbol.unwrap(), &destination)
+                    .await?;
+                self.emit(ContractEvent::Navigating {
+                    ship_symbol: ship_symbol.unwrap().clone(),
+                    destination,
+                    arrival: nav.route.arrival,
+                });
+            }
+            ContractAction::Purchase(trade_symbol, units) => {
+                let transaction = client
+                    .purchase_cargo(ship_symbol.unwrap(), &trade_symbol, units)
+                    .await?;
+                self.emit(ContractEvent::Purchased {
+                    ship_symbol: ship_symbol.unwrap().clone(),
+                    trade_symbol,
+                    units: *transaction.units,
+                    total_price: *transaction.total_price,
+                });
+            }
+            ContractAction::Deliver(trade_symbol, units) => {
+                client
+                    .deliver_contract(&contract_id, ship_symbol.unwrap(), &trade_symbol, units)
+                    .await?;
+
+                let contract = client.get_contract(&contract_id)?;
+                let deliver = contract
+                    .terms
+                    .deliver
+                    .iter()
+                    .find(|deliver| deliver.trade_symbol == trade_symbol)
+                    .unwrap();
+                self.emit(ContractEvent::Delivered {
+                    ship_symbol: ship_symbol.unwrap().clone(),
+                    trade_symbol,
+                    units,
+                    units_fulfilled: deliver.units_fulfilled,
+                    units_required: deliver.units_required,
+                });
+            }
+            ContractAction::Sell(trade_symbol, units) => {
+                let ship = ship.unwrap();
+                let market = match &self.market {
+                    Some(market) if market.symbol == ship.nav.waypoint_symbol => market,
+                    _ => self.market.insert(Box::new(
+                        client
+                            .view_market(&ship.nav.system_symbol, &ship.nav.waypoint_symbol)
+                            .await?,
+                    )),
+                };
+
+                let sold = client
+                    .sell_at_market(market, ship_symbol.unwrap(), &trade_symbol, units)
+                    .await?;
+                // The market doesn't buy this good
+                if sold.is_none() {
+                    self.unsellable.push(trade_symbol);
+                }
+            }
+            ContractAction::Jettison(trade_symbol, units) => {
+                client
+                    .jettison_cargo(ship_symbol.unwrap(), &trade_symbol, units)
+                    .await?;
+            }
+        }
+
+        Ok(ContractStep::Continue)
+    }
+
+    /// Decides the next action based on the current state of the contract and ship.
+    pub(crate) fn next_action(
+        &self,
+        contract: &Contract,
+        ship: Option<&Ship>,
+        now: DateTime<Utc>,
+    ) -> ContractAction {
+        if !contract.accepted {
+            return ContractAction::Accept;
+        }
+
+        let (delivery, ship) = match (self.plan.deliveries.get(self.delivery), ship) {
+            (Some(delivery), Some(ship)) => (delivery, ship),
+            _ if contract.fulfilled => return ContractAction::Finish,
+            _ => return ContractAction::Fulfill,
+        };
+
+        let remaining = contract
+            .terms
+            .deliver
+            .iter()
+            .find(|deliver| deliver.trade_symbol == delivery.trade_symbol)
+            .map_or(0, |deliver| {
+                deliver.units_required - deliver.units_fulfilled
+            });
+        if remaining <= 0 {
+            return ContractAction::NextDelivery;
+        }
+
+        let (market, trade_volume) = match &delivery.source {
+            Sourcing::Buy {
+                market,
+                trade_volume,
+                ..
+            } => (market, *trade_volume as i32),
+            // Mining is handled by the mining loop
+            Sourcing::Mine { .. } => return ContractAction::Mine,
+            // Infeasible plans are rejected before they get here
+            Sourcing::Unavailable => return ContractAction::NextDelivery,
+        };
+
+        let nav = &ship.nav;
+        if nav.status == ShipStatus::InTransit {
+            if nav.route.arrival > now {
+                return ContractAction::Wait(nav.route.arrival);
+            }
+            return ContractAction::Arrive;
+        }
+
+        // Moves the ship to the given waypoint and docks there
+        let go_to = |destination: &Symbol| {
+            if nav.waypoint_symbol != *destination {
+                if nav.status == ShipStatus::Docked {
+                    return Some(ContractAction::Orbit);
+                }
+                return Some(ContractAction::Navigate(destination.clone()));
+            }
+            if nav.status != ShipStatus::Docked {
+                return Some(ContractAction::Dock);
+            }
+            None
+        };
+
+        let in_cargo = ship
+            .cargo
+            .inventory
+            .iter()
+            .find(|item| item.symbol == delivery.trade_symbol)
+            .map_or(0, |item| *item.units as i32);
+        let free = (*ship.cargo.capacity - *ship.cargo.units) as i32;
+
+        // Deliver once the hold is full, or it holds everything that is still required
+        if in_cargo > 0 && (in_cargo >= remaining || free == 0) {
+            if let Some(action) = go_to(&delivery.destination) {
+                return action;
+            }
+            return ContractAction::Deliver(delivery.trade_symbol.clone(), in_cargo.min(remaining));
+        }
+
+        if let Some(action) = go_to(market) {
+            return action;
+        }
+
+        // Make room by getting rid of anything that isn't for the contract
+        if free == 0 {
+            if let Some(item) = ship
+                .cargo
+                .inventory
+                .iter()
+                .find(|item| item.symbol != delivery.trade_symbol)
+            {
+                if self.unsellable.contains(&item.symbol) {
+                    return ContractAction::Jettison(item.symbol.clone(), *item.units as i32);
+                }
+                return ContractAction::Sell(item.symbol.clone(), *item.units as i32);
+            }
+        }
+
+        ContractAction::Purchase(
+            delivery.trade_symbol.clone(),
+            (remaining - in_cargo).min(free).min(trade_volume),
+        )
+    }
+
+    fn emit(&self, event: ContractEvent) {
+        if let Some(events) = &self.events {
+            // The receiver may have been dropped, which just means nobody is listening
+            let _ = events.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures::{self, symbol},
+        market_history::{InMemoryMarketStore, MarketStore, PriceRecord, SupplyLevel},
+    };
+
+    const SYSTEM: &str = r#"{
+        "symbol": "X1-A",
+        "sectorSymbol": "X1",
+        "type": "RED_STAR",
+        "x": 0,
+        "y": 0,
+        "waypoints": [
+            { "symbol": "X1-A-1", "type": "PLANET", "x": 0, "y": 0 },
+            { "symbol": "X1-A-2", "type": "MOON", "x": 30, "y": 0 },
+            { "symbol": "X1-A-3", "type": "ASTEROID_FIELD", "x": 0, "y": 60 }
+        ],
+        "factions": []
+    }"#;
+
+    fn contract(trade_symbol: &str, required: i32, on_fulfilled: i32) -> Contract {
+        serde_json::from_value(serde_json::json!({
+            "id": "clhr6zx0r07s2s60daxqce7b1",
+            "factionSymbol": "COSMIC",
+            "type": "PROCUREMENT",
+            "terms": {
+                "deadline": (Utc::now() + Duration::days(7)).to_rfc3339(),
+                "payment": { "onAccepted": 1000, "onFulfilled": on_fulfilled },
+                "deliver": [{
+                    "tradeSymbol": trade_symbol,
+                    "destinationSymbol": "X1-A-1",
+                    "unitsRequired": required,
+                    "unitsFulfilled": 0
+                }]
+            },
+            "accepted": false,
+            "fulfilled": false,
+            "expiration": (Utc::now() + Duration::days(1)).to_rfc3339(),
+        }))
+        .unwrap()
+    }
+
+    fn client(contract: Contract) -> SpaceTradersClient {
+        let mut ship = fixtures::ship();
+        ship["mounts"] = serde_json::json!([fixtures::mining_laser()]);
+        fixtures::client(vec![ship], vec![serde_json::to_value(contract).unwrap()])
+    }
+
+    fn price(waypoint: &str, good: &str, purchase_price: i64) -> PriceRecord {
+        PriceRecord {
+            waypoint_symbol: symbol(waypoint),
+            system_symbol: symbol("X1-A"),
+            trade_symbol: symbol(good),
+            purchase_price,
+            sell_price: purchase_price - 5,
+            supply: SupplyLevel::Moderate,
+            trade_volume: 100,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn plans_purchases_when_profitable() -> STResult<()> {
+        let mut client = client(contract("IRON_ORE", 100, 10_000));
+        let store = InMemoryMarketStore::new();
+        store.record(&[price("X1-A-2", "IRON_ORE", 50), price("X1-A-1", FUEL, 2)])?;
+        client.set_market_store(store);
+
+        let system: System = serde_json::from_str(SYSTEM)?;
+        let options = ContractPlanOptions {
+            asteroid_field: Some(symbol("X1-A-3")),
+            ships: None,
+        };
+        let plan = client.plan_contract(&symbol("clhr6zx0r07s2s60daxqce7b1"), &system, &options)?;
+
+        assert_eq!(plan.payment, 11_000);
+        assert_eq!(plan.deliveries.len(), 1);
+
+        let delivery = &plan.deliveries[0];
+        assert_eq!(
+            delivery.source,
+            Sourcing::Buy {
+                market: symbol("X1-A-2"),
+                price_per_unit: 50,
+                trade_volume: 100,
+            }
+        );
+        assert_eq!(delivery.ship_symbol, "TST-RS-04-1");
+        assert_eq!(delivery.trips, 2);
+        // 3 legs of 30 units of fuel, plus 100 units of iron ore
+        assert_eq!(delivery.cost, 100 * 50 + 4 * 30 * 2);
+        assert!(plan.is_profitable());
+        assert!(plan.is_feasible());
+        assert!(plan.meets_deadline);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn plans_mining_when_buying_is_unprofitable() -> STResult<()> {
+        let mut client = client(contract("IRON_ORE", 100, 1_000));
+        let store = InMemoryMarketStore::new();
+        store.record(&[price("X1-A-2", "IRON_ORE", 50)])?;
+        client.set_market_store(store);
+
+        let system: System = serde_json::from_str(SYSTEM)?;
+        let options = ContractPlanOptions {
+            asteroid_field: Some(symbol("X1-A-3")),
+            ships: None,
+        };
+        let plan = client.plan_contract(&symbol("clhr6zx0r07s2s60daxqce7b1"), &system, &options)?;
+
+        let delivery = &plan.deliveries[0];
+        assert_eq!(
+            delivery.source,
+            Sourcing::Mine {
+                asteroid_field: symbol("X1-A-3")
+            }
+        );
+        assert!(plan.is_profitable());
+
+        // Goods that can't be bought or mined
+        let client = self::client(contract("FAB_MATS", 100, 1_000));
+        let plan = client.plan_contract(&symbol("clhr6zx0r07s2s60daxqce7b1"), &system, &options)?;
+        assert_eq!(plan.deliveries[0].source, Sourcing::Unavailable);
+        assert!(!plan.is_feasible());
+
+        // Infeasible plans aren't executed
+        let mut executor = ContractExecutor::new(plan);
+        let result = executor.step(&client).await;
+        assert!(matches!(
+            result,
+            Err(SpaceTradersError::ContractInfeasible(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn weighs_purchases_of_every_delivery_against_the_payment() -> STResult<()> {
+        // Either delivery could be bought within the payment, but not both
+        let mut contract = contract("IRON_ORE", 100, 8_000);
+        let mut copper = contract.terms.deliver[0].clone();
+        copper.trade_symbol = symbol("COPPER_ORE");
+        contract.terms.deliver.push(copper);
+
+        let mut client = client(contract);
+        let store = InMemoryMarketStore::new();
+        store.record(&[
+            price("X1-A-2", "IRON_ORE", 50),
+            price("X1-A-2", "COPPER_ORE", 50),
+        ])?;
+        client.set_market_store(store);
+
+        let system: System = serde_json::from_str(SYSTEM)?;
+        let options = ContractPlanOptions {
+            asteroid_field: Some(symbol("X1-A-3")),
+            ships: None,
+        };
+        let plan = client.plan_contract(&symbol("clhr6zx0r07s2s60daxqce7b1"), &system, &options)?;
+
+        assert_eq!(plan.deliveries.len(), 2);
+        for delivery in &plan.deliveries {
+            assert_eq!(
+                delivery.source,
+                Sourcing::Mine {
+                    asteroid_field: symbol("X1-A-3")
+                }
+            );
+        }
+        assert!(plan.is_profitable());
+
+        Ok(())
+    }
+
+    #[test]
+    fn executes_purchase_and_partial_deliveries() -> STResult<()> {
+        let mut contract = contract("IRON_ORE", 100, 10_000);
+        let client = client(contract.clone());
+        let mut ship = client.get_ship(&symbol("TST-RS-04-1"))?.clone();
+        let now = Utc::now();
+
+        let plan = ContractPlan {
+            contract_id: contract.id.clone(),
+            deliveries: vec![DeliveryPlan {
+                trade_symbol: symbol("IRON_ORE"),
+                destination: symbol("X1-A-1"),
+                units: 100,
+                source: Sourcing::Buy {
+                    market: symbol("X1-A-2"),
+                    price_per_unit: 50,
+                    trade_volume: 100,
+                },
+                ship_symbol: symbol("TST-RS-04-1"),
+                trips: 2,
+                cost: 0,
+                duration: 0,
+            }],
+            payment: 11_000,
+            cost: 0,
+            duration: 0,
+            deadline: contract.terms.deadline,
+            meets_deadline: true,
+        };
+        let mut executor = ContractExecutor::new(plan);
+
+        assert_eq!(
+            executor.next_action(&contract, Some(&ship), now),
+            ContractAction::Accept
+        );
+        contract.accepted = true;
+
+        // Fly to the market and buy a full hold
+        assert_eq!(
+            executor.next_action(&contract, Some(&ship), now),
+            ContractAction::Orbit
+        );
+        ship.nav.status = ShipStatus::InOrbit;
+        assert_eq!(
+            executor.next_action(&contract, Some(&ship), now),
+            ContractAction::Navigate(symbol("X1-A-2"))
+        );
+        ship.nav.waypoint_symbol = symbol("X1-A-2");
+        ship.nav.status = ShipStatus::Docked;
+        assert_eq!(
+            executor.next_action(&contract, Some(&ship), now),
+            ContractAction::Purchase(symbol("IRON_ORE"), 60)
+        );
+
+        // Deliver the first 60 units
+        ship.cargo = serde_json::from_value(serde_json::json!({
+            "capacity": 60,
+            "units": 60,
+            "inventory": [{ "symbol": "IRON_ORE", "name": "Iron Ore", "description": "Ore.", "units": 60 }]
+        }))?;
+        assert_eq!(
+            executor.next_action(&contract, Some(&ship), now),
+            ContractAction::Orbit
+        );
+        ship.nav.waypoint_symbol = symbol("X1-A-1");
+        assert_eq!(
+            executor.next_action(&contract, Some(&ship), now),
+            ContractAction::Deliver(symbol("IRON_ORE"), 60)
+        );
+
+        // Only the remaining 40 units are bought on the next trip
+        contract.terms.deliver[0].units_fulfilled = 60;
+        ship.cargo = serde_json::from_value(serde_json::json!({
+            "capacity": 60, "units": 0, "inventory": []
+        }))?;
+        ship.nav.waypoint_symbol = symbol("X1-A-2");
+        assert_eq!(
+            executor.next_action(&contract, Some(&ship), now),
+            ContractAction::Purchase(symbol("IRON_ORE"), 40)
+        );
+
+        // Purchases are limited to the market's trade volume
+        if let Sourcing::Buy { trade_volume, .. } = &mut executor.plan.deliveries[0].source {
+            *trade_volume = 25;
+        }
+        assert_eq!(
+            executor.next_action(&contract, Some(&ship), now),
+            ContractAction::Purchase(symbol("IRON_ORE"), 25)
+        );
+
+        contract.terms.deliver[0].units_fulfilled = 100;
+        assert_eq!(
+            executor.next_action(&contract, Some(&ship), now),
+            ContractAction::NextDelivery
+        );
+
+        executor.delivery += 1;
+        assert_eq!(
+            executor.next_action(&contract, None, now),
+            ContractAction::Fulfill
+        );
+
+        contract.fulfilled = true;
+        assert_eq!(
+            executor.next_action(&contract, None, now),
+            ContractAction::Finish
+        );
+
+        Ok(())
+    }
+}
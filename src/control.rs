@@ -0,0 +1,429 @@
+//! A local HTTP API for observing and steering a running agent.
+//!
+//! A [ControlServer] wraps a live [SpaceTradersClient], and serves its cached state and accepts
+//! commands as JSON, so other tools (dashboards, scripts, teammates) can work with the agent without
+//! being given its token. Commands are made through the wrapped client, so they share its cache
+//! and [RateLimiter](crate::rate_limiter::RateLimiter) with the bot instead of competing with it.
+//!
+//! Like the SpaceTraders API, successful responses wrap their value in `data`, and failures are
+//! returned as `{"error": {"message": ..., "code": ...}}`, where `code` is the API's error code if
+//! the API rejected the command.
+//!
+//! | Method | Path | Body | Response |
+//! | --- | --- | --- | --- |
+//! | `GET` | `/agent` | | The agent |
+//! | `GET` | `/ships` | | Every ship |
+//! | `GET` | `/ships/{ship}` | | The ship |
+//! | `GET` | `/contracts` | | Every contract |
+//! | `POST` | `/ships/{ship}/dock` | | The ship's navigation info |
+//! | `POST` | `/ships/{ship}/orbit` | | The ship's navigation info |
+//! | `POST` | `/ships/{ship}/navigate` | `{"waypointSymbol": ...}` | The ship's navigation info |
+//! | `POST` | `/ships/{ship}/extract` | | The cooldown and extraction |
+//! | `POST` | `/ships/{ship}/refuel` | | The transaction |
+//! | `POST` | `/ships/{ship}/sell` | `{"symbol": ..., "units": ...}` | The transaction |
+//! | `POST` | `/ships/{ship}/purchase` | `{"symbol": ..., "units": ...}` | The transaction |
+//! | `POST` | `/contracts/{id}/accept` | | The contract |
+//!
+//! The server only listens on the loopback interface unless bound to another address with
+//! [bind](ControlServer::bind). Servers reachable by others must require an access key, which is
+//! then expected as a bearer token in the `Authorization` header of every request.
+//!
+//! The control server is only available with the `control` feature.
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let client = SpaceTradersClient::load_saved().unwrap();
+//! let server = ControlServer::bind(
+//!     client.clone(),
+//!     "0.0.0.0:8080".parse().unwrap(),
+//!     Some("team-key".into()),
+//! )
+//! .unwrap();
+//! println!("Listening on {}", server.url());
+//!
+//! // Run the bot with `client` as usual
+//! # })
+//! ```
+
+use crate::{
+    conditional_types::Symbol, space_traders_client::SpaceTradersClient, STResult,
+    SpaceTradersError,
+};
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{
+    convert::Infallible,
+    net::{Ipv4Addr, SocketAddr, TcpListener},
+    sync::Arc,
+};
+use tokio::sync::oneshot;
+
+/// A local HTTP server exposing a [SpaceTradersClient].
+///
+/// The server is shut down when it is dropped.
+#[derive(Debug)]
+pub struct ControlServer {
+    address: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Debug)]
+struct ControlState {
+    client: SpaceTradersClient,
+    access_key: Option<String>,
+}
+
+/// The body of a `navigate` command.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NavigateBody {
+    waypoint_symbol: Symbol,
+}
+
+/// The body of a `sell` or `purchase` command.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TradeBody {
+    symbol: Symbol,
+    units: i32,
+}
+
+/// An error response of the control server.
+#[derive(Debug)]
+struct ControlError {
+    status: StatusCode,
+    body: Value,
+}
+
+impl ControlError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": { "message": message.into() } }),
+        }
+    }
+}
+
+impl From<SpaceTradersError> for ControlError {
+    fn from(err: SpaceTradersError) -> Self {
+        let status = match &err {
+            err if err.is_not_found() => StatusCode::NOT_FOUND,
+            err if err.is_rate_limited() => StatusCode::TOO_MANY_REQUESTS,
+            SpaceTradersError::ResponseError(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        match err {
+            SpaceTradersError::ResponseError(error) => Self {
+                status,
+                body: json!({ "error": { "message": error.message, "code": error.code } }),
+            },
+            err => Self::new(status, err.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ControlError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, format!("Invalid body: {}", err))
+    }
+}
+
+type ControlResult = Result<Value, ControlError>;
+
+impl ControlServer {
+    /// Starts a server for the client on a free port of the loopback interface, without an
+    /// access key.
+    pub fn start(client: SpaceTradersClient) -> STResult<Self> {
+        Self::bind(client, (Ipv4Addr::LOCALHOST, 0).into(), None)
+    }
+
+    /// Starts a server for the client on the given address.
+    ///
+    /// If `access_key` is set, requests without it as their bearer token are rejected. An access
+    /// key is required unless the address is on the loopback interface.
+    pub fn bind(
+        client: SpaceTradersClient,
+        address: SocketAddr,
+        access_key: Option<String>,
+    ) -> STResult<Self> {
+        let has_key = matches!(&access_key, Some(key) if !key.is_empty());
+        if !has_key && !address.ip().is_loopback() {
+            return Err(SpaceTradersError::FileError(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "an access key is required to serve the control API on {}",
+                    address
+                ),
+            )));
+        }
+
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let state = Arc::new(ControlState { client, access_key });
+        let make_service = make_service_fn(move |_| {
+            let state = state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+        });
+
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let server = Server::from_tcp(listener)
+            .map_err(std::io::Error::other)?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+        tokio::spawn(server);
+
+        Ok(Self {
+            address,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The URL the API is served at.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Compares an access key in constant time (for keys of the same length), so it can't be guessed
+/// byte by byte from how long rejections take.
+fn keys_match(given: &[u8], key: &[u8]) -> bool {
+    given.len() == key.len()
+        && given
+            .iter()
+            .zip(key)
+            .fold(0, |diff, (given, key)| diff | (given ^ key))
+            == 0
+}
+
+async fn handle(
+    state: Arc<ControlState>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+
+    let authorized = match &state.access_key {
+        Some(key) => parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| keys_match(given.as_bytes(), key.as_bytes())),
+        None => true,
+    };
+
+    let result = if authorized {
+        route(&state.client, &parts.method, parts.uri.path(), &body).await
+    } else {
+        Err(ControlError::new(
+            StatusCode::UNAUTHORIZED,
+            "A valid access key is required",
+        ))
+    };
+    let (status, body) = match result {
+        Ok(data) => (StatusCode::OK, json!({ "data": data })),
+        Err(err) => (err.status, err.body),
+    };
+
+    Ok(Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap())
+}
+
+async fn route(
+    client: &SpaceTradersClient,
+    method: &Method,
+    path: &str,
+    body: &[u8],
+) -> ControlResult {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+
+    match (method, segments.as_slice()) {
+        (&Method::GET, ["agent"]) => data(client.agent()),
+        (&Method::GET, ["ships"]) => data(client.ships()),
+        (&Method::GET, ["ships", ship]) => data(client.get_ship(&symbol(ship)?)),
+        (&Method::GET, ["contracts"]) => data(client.contracts()),
+        (&Method::POST, ["ships", ship, command]) => {
+            let ship = symbol(ship)?;
+            // Report unknown ships as not found, instead of as an error from the API
+            client.get_ship(&ship)?;
+
+            match *command {
+                "dock" => data(client.dock_ship(&ship).await),
+                "orbit" => data(client.orbit_ship(&ship).await),
+                "navigate" => {
+                    let body: NavigateBody = parse(body)?;
+                    data(client.navigate_ship(&ship, &body.waypoint_symbol).await)
+                }
+                "extract" => {
+                    let (cooldown, extraction) = client.extract_resources(&ship, None).await?;
+                    Ok(json!({ "cooldown": cooldown, "extraction": extraction }))
+                }
+                "refuel" => data(client.refuel_ship(&ship).await),
+                "sell" => {
+                    let body: TradeBody = parse(body)?;
+                    data(client.sell_cargo(&ship, &body.symbol, body.units).await)
+                }
+                "purchase" => {
+                    let body: TradeBody = parse(body)?;
+                    data(client.purchase_cargo(&ship, &body.symbol, body.units).await)
+                }
+                _ => Err(not_found(path)),
+            }
+        }
+        (&Method::POST, ["contracts", id, "accept"]) => {
+            let id = symbol(id)?;
+            client.accept_contract(id.clone()).await?;
+            data(client.get_contract(&id))
+        }
+        _ => Err(not_found(path)),
+    }
+}
+
+fn data(value: STResult<impl serde::Serialize>) -> ControlResult {
+    Ok(json!(value?))
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, ControlError> {
+    Ok(serde_json::from_slice(body)?)
+}
+
+fn symbol(symbol: &str) -> Result<Symbol, ControlError> {
+    Symbol::new(symbol).map_err(|err| ControlError::new(StatusCode::BAD_REQUEST, err.to_string()))
+}
+
+fn not_found(path: &str) -> ControlError {
+    ControlError::new(StatusCode::NOT_FOUND, format!("No route for `{}`", path))
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[tokio::test]
+    async fn serves_state_and_commands() -> STResult<()> {
+        let mock = MockServer::start().await?;
+        let client = mock.client();
+        client.register_callsign("CONTROL", None).await?;
+
+        let server = ControlServer::start(client.clone())?;
+        let http = reqwest::Client::new();
+        let get = |path: &str| {
+            let request = http.get(format!("{}{}", server.url(), path));
+            async move { request.send().await?.json::<Value>().await }
+        };
+
+        let ships = get("/ships").await?;
+        assert_eq!(ships["data"][0]["symbol"], "CONTROL-1");
+        assert_eq!(ships["data"][0]["nav"]["status"], "DOCKED");
+
+        // Commands go through the shared client, and update its cache
+        let nav = http
+            .post(format!("{}/ships/CONTROL-1/orbit", server.url()))
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+        assert_eq!(nav["data"]["status"], "IN_ORBIT");
+        assert_eq!(
+            get("/ships/CONTROL-1").await?["data"]["nav"]["status"],
+            "IN_ORBIT"
+        );
+
+        let response = http
+            .post(format!("{}/ships/CONTROL-1/sell", server.url()))
+            .json(&json!({ "symbol": "IRON_ORE", "units": 10 }))
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.json::<Value>().await?["error"]["code"].is_number());
+
+        let response = http
+            .post(format!("{}/ships/CONTROL-1/navigate", server.url()))
+            .body("{}")
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = http
+            .get(format!("{}/ships/UNKNOWN-1", server.url()))
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let id = get("/contracts").await?["data"][0]["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let contract = http
+            .post(format!("{}/contracts/{}/accept", server.url(), id))
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+        assert_eq!(contract["data"]["accepted"], true);
+        assert!(client.contracts()?[0].accepted);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn requires_the_access_key() -> STResult<()> {
+        let mock = MockServer::start().await?;
+        let client = mock.client();
+        client.register_callsign("LOCKED", None).await?;
+
+        let server = ControlServer::bind(
+            client,
+            (Ipv4Addr::LOCALHOST, 0).into(),
+            Some("secret".into()),
+        )?;
+        let http = reqwest::Client::new();
+        let url = format!("{}/agent", server.url());
+
+        let response = http.get(&url).send().await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = http.get(&url).bearer_auth("wrong").send().await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = http.get(&url).bearer_auth("secret").send().await?;
+        assert_eq!(response.json::<Value>().await?["data"]["symbol"], "LOCKED");
+
+        // Servers reachable by others must have a key
+        let result = ControlServer::bind(mock.client(), (Ipv4Addr::UNSPECIFIED, 0).into(), None);
+        assert!(result.is_err());
+
+        assert!(keys_match(b"secret", b"secret"));
+        assert!(!keys_match(b"secreT", b"secret"));
+        assert!(!keys_match(b"secret2", b"secret"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,642 @@
+//! An interactive terminal dashboard for a saved agent.
+//!
+//! The [Dashboard] shows the agent's fleet with each ship's status, location, fuel, cargo and
+//! cooldown, its contracts with their deadlines and progress, a chart of the agent's credits, and
+//! a log of every [ClientEvent]. It refreshes a few times per second, so countdowns tick and the
+//! effects of commands show up as soon as their responses arrive.
+//!
+//! Ships are selected with the arrow keys (or `j`/`k`), and commanded with:
+//! - `d`: dock the selected ship
+//! - `o`: move the selected ship into orbit
+//! - `n`: navigate the selected ship to a waypoint, entered at the prompt
+//! - `s`: sell cargo of the selected ship, entered at the prompt as `GOOD [UNITS]`; without units
+//!   all units of the good are sold
+//! - `q`: quit
+//!
+//! The `space-traders-tui` binary opens the dashboard for the agent saved at the given path, or
+//! for the default save file, and saves the agent back to it after every command and on exit.
+//!
+//! The dashboard is only available with the `tui` feature.
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let client = SpaceTradersClient::load_saved().unwrap();
+//! Dashboard::new(client).unwrap().run().await.unwrap();
+//! # })
+//! ```
+
+use crate::{
+    conditional_types::Symbol,
+    contract::Contract,
+    events::ClientEvent,
+    ship::{Ship, ShipStatus},
+    space_traders_client::SpaceTradersClient,
+    STResult, SpaceTradersError,
+};
+use chrono::{DateTime, Utc};
+use crossterm::{
+    cursor::Show,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Row, Sparkline, Table, TableState},
+    Frame, Terminal,
+};
+use std::{collections::VecDeque, panic, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+/// How often the dashboard redraws while no keys are pressed.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The number of lines kept in the event log.
+const LOG_CAPACITY: usize = 100;
+
+/// The number of credit balances kept for the chart.
+const CREDIT_HISTORY: usize = 200;
+
+/// A command that needs more input from the prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptKind {
+    Navigate,
+    Sell,
+}
+
+#[derive(Debug)]
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+/// The state of the terminal dashboard.
+#[derive(Debug)]
+pub struct Dashboard {
+    client: SpaceTradersClient,
+    events: broadcast::Receiver<ClientEvent>,
+    selected: usize,
+    credits: VecDeque<u64>,
+    log: VecDeque<String>,
+    prompt: Option<Prompt>,
+    save_path: Option<PathBuf>,
+    quit: bool,
+}
+
+impl Dashboard {
+    /// Creates a dashboard for the client's agent.
+    pub fn new(client: SpaceTradersClient) -> STResult<Self> {
+        let events = client.subscribe();
+        let credits = client.agent()?.credits.max(0) as u64;
+
+        Ok(Self {
+            client,
+            events,
+            selected: 0,
+            credits: VecDeque::from([credits]),
+            log: VecDeque::new(),
+            prompt: None,
+            save_path: None,
+            quit: false,
+        })
+    }
+
+    /// Saves the client to the given file after every command and when the dashboard exits.
+    pub fn set_save_path(&mut self, path: impl Into<PathBuf>) {
+        self.save_path = Some(path.into());
+    }
+
+    /// Takes over the terminal and runs the dashboard until the user quits.
+    ///
+    /// The terminal is restored when the dashboard exits, even if it fails or panics. If a save
+    /// path is set, the client is saved on exit.
+    pub async fn run(mut self) -> STResult<()> {
+        enable_raw_mode()?;
+        execute!(std::io::stdout(), EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+
+        // Restore the terminal before the panic message is printed
+        let default_hook = Arc::new(panic::take_hook());
+        let hook = default_hook.clone();
+        panic::set_hook(Box::new(move |info| {
+            let _ = restore_terminal();
+            hook(info);
+        }));
+
+        let result = self.run_in(&mut terminal).await;
+
+        restore_terminal()?;
+        drop(panic::take_hook());
+        panic::set_hook(Box::new(move |info| default_hook(info)));
+
+        result.and(self.save())
+    }
+
+    async fn run_in<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> STResult<()> {
+        while !self.quit {
+            self.update();
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if event::poll(REFRESH_INTERVAL)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.handle_key(key).await;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the user asked to quit.
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Adds the client's new events to the log and the credits chart.
+    pub fn update(&mut self) {
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => {
+                    if let ClientEvent::CreditsChanged { after, .. } = event {
+                        push_bounded(&mut self.credits, after.max(0) as u64, CREDIT_HISTORY);
+                    }
+                    let message = describe(&event);
+                    self.log(message);
+                }
+                Err(TryRecvError::Lagged(missed)) => self.log(format!("Missed {} events", missed)),
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Selects a ship, or issues a command to the selected ship.
+    ///
+    /// Errors from commands are shown in the log.
+    pub async fn handle_key(&mut self, key: KeyEvent) {
+        if let Some(prompt) = &mut self.prompt {
+            match key.code {
+                KeyCode::Char(c) => prompt.input.push(c),
+                KeyCode::Backspace => {
+                    prompt.input.pop();
+                }
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Enter => {
+                    if let Some(prompt) = self.prompt.take() {
+                        let result = self.submit(prompt).await.and(self.save());
+                        self.log_error(result);
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                let ships = self.client.ships().map_or(0, |ships| ships.len());
+                self.selected = (self.selected + 1).min(ships.saturating_sub(1));
+            }
+            KeyCode::Char('d') => {
+                let result = match self.selected_ship() {
+                    Ok(ship) => self.client.dock_ship(&ship).await.map(|_| ()),
+                    Err(err) => Err(err),
+                };
+                let result = result.and(self.save());
+                self.log_error(result);
+            }
+            KeyCode::Char('o') => {
+                let result = match self.selected_ship() {
+                    Ok(ship) => self.client.orbit_ship(&ship).await.map(|_| ()),
+                    Err(err) => Err(err),
+                };
+                let result = result.and(self.save());
+                self.log_error(result);
+            }
+            KeyCode::Char('n') => self.open_prompt(PromptKind::Navigate),
+            KeyCode::Char('s') => self.open_prompt(PromptKind::Sell),
+            _ => {}
+        }
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) {
+        self.prompt = Some(Prompt {
+            kind,
+            input: String::new(),
+        });
+    }
+
+    async fn submit(&mut self, prompt: Prompt) -> STResult<()> {
+        let ship_symbol = self.selected_ship()?;
+        let mut input = prompt.input.split_whitespace();
+        let Some(symbol) = input
+            .next()
+            .and_then(|symbol| Symbol::new(&symbol.to_uppercase()).ok())
+        else {
+            self.log(format!("Invalid input: `{}`", prompt.input));
+            return Ok(());
+        };
+
+        match prompt.kind {
+            PromptKind::Navigate => {
+                self.client.navigate_ship(&ship_symbol, &symbol).await?;
+            }
+            PromptKind::Sell => {
+                let units = match input.next().map(str::parse) {
+                    Some(Ok(units)) => units,
+                    Some(Err(_)) => {
+                        self.log(format!("Invalid number of units: `{}`", prompt.input));
+                        return Ok(());
+                    }
+                    // Sell everything the ship holds of the good
+                    None => self
+                        .client
+                        .get_ship(&ship_symbol)?
+                        .cargo
+                        .inventory
+                        .iter()
+                        .find(|item| item.symbol == symbol)
+                        .map_or(0, |item| *item.units as i32),
+                };
+                self.client.sell_cargo(&ship_symbol, &symbol, units).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Saves the client to the save path, if one is set.
+    fn save(&self) -> STResult<()> {
+        match &self.save_path {
+            Some(path) => self.client.save_client_to(path),
+            None => Ok(()),
+        }
+    }
+
+    fn selected_ship(&self) -> STResult<Symbol> {
+        self.client
+            .ships()?
+            .get(self.selected)
+            .map(|ship| ship.symbol.clone())
+            .ok_or(SpaceTradersError::EmptyCache {
+                operation: Some("selected_ship"),
+                data: None,
+            })
+    }
+
+    fn log(&mut self, message: String) {
+        let now = self.client.scheduler().clock().now();
+        let line = format!("{} {}", now.format("%H:%M:%S"), message);
+        push_bounded(&mut self.log, line, LOG_CAPACITY);
+    }
+
+    fn log_error(&mut self, result: STResult<()>) {
+        if let Err(err) = result {
+            self.log(format!("Error: {}", err));
+        }
+    }
+
+    /// Draws the dashboard.
+    pub fn draw(&self, frame: &mut Frame) {
+        let now = self.client.scheduler().clock().now();
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(40),
+                Constraint::Percentage(30),
+                Constraint::Min(3),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+        let middle = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+            .split(rows[1]);
+
+        let ships = self.client.ships().unwrap_or_default();
+        let fleet = Table::new(ships.iter().map(|ship| self.ship_row(ship, now)))
+            .header(header(&[
+                "SHIP", "STATUS", "LOCATION", "FUEL", "CARGO", "COOLDOWN",
+            ]))
+            .widths(&[
+                Constraint::Length(16),
+                Constraint::Length(10),
+                Constraint::Min(24),
+                Constraint::Length(10),
+                Constraint::Length(8),
+                Constraint::Length(9),
+            ])
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .block(Block::default().borders(Borders::ALL).title("Fleet"));
+        let mut selection = TableState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(fleet, rows[0], &mut selection);
+
+        let contracts = self.client.contracts().unwrap_or_default();
+        let contracts = Table::new(contracts.iter().map(|contract| contract_row(contract, now)))
+            .header(header(&["CONTRACT", "STATUS", "DEADLINE", "PROGRESS"]))
+            .widths(&[
+                Constraint::Length(26),
+                Constraint::Length(10),
+                Constraint::Length(9),
+                Constraint::Min(20),
+            ])
+            .block(Block::default().borders(Borders::ALL).title("Contracts"));
+        frame.render_widget(contracts, middle[0]);
+
+        let history = self.credits.iter().copied().collect::<Vec<_>>();
+        let credits = Sparkline::default().data(&history).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Credits: {}", history.last().unwrap_or(&0))),
+        );
+        frame.render_widget(credits, middle[1]);
+
+        // Only the newest lines fit, so show them first
+        let log = List::new(
+            self.log
+                .iter()
+                .rev()
+                .map(|line| ListItem::new(line.as_str()))
+                .collect::<Vec<_>>(),
+        )
+        .block(Block::default().borders(Borders::ALL).title("Events"));
+        frame.render_widget(log, rows[2]);
+
+        let status = match &self.prompt {
+            Some(Prompt {
+                kind: PromptKind::Navigate,
+                input,
+            }) => format!("Navigate to waypoint: {}", input),
+            Some(Prompt {
+                kind: PromptKind::Sell,
+                input,
+            }) => format!("Sell (GOOD [UNITS]): {}", input),
+            None => "[↑/↓] select  [d] dock  [o] orbit  [n] navigate  [s] sell  [q] quit".into(),
+        };
+        frame.render_widget(Paragraph::new(status), rows[3]);
+    }
+
+    fn ship_row(&self, ship: &Ship, now: DateTime<Utc>) -> Row<'static> {
+        let nav = &ship.nav;
+        let location = match nav.status {
+            ShipStatus::InTransit if nav.route.arrival > now => format!(
+                "{} (in {})",
+                nav.route.destination.symbol,
+                remaining(nav.route.arrival, now)
+            ),
+            _ => nav.waypoint_symbol.to_string(),
+        };
+        let cooldown = self
+            .client
+            .scheduler()
+            .cooldown_expiration(&ship.symbol)
+            .filter(|&expiration| expiration > now)
+            .map_or("-".into(), |expiration| remaining(expiration, now));
+
+        Row::new(vec![
+            ship.symbol.to_string(),
+            format!("{:?}", nav.status),
+            location,
+            format!("{}/{}", *ship.fuel.current, *ship.fuel.capacity),
+            format!("{}/{}", *ship.cargo.units, *ship.cargo.capacity),
+            cooldown,
+        ])
+    }
+}
+
+fn header(titles: &[&'static str]) -> Row<'static> {
+    Row::new(titles.to_vec()).style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+fn contract_row(contract: &Contract, now: DateTime<Utc>) -> Row<'static> {
+    let status = match (contract.accepted, contract.fulfilled) {
+        (_, true) => "Fulfilled",
+        (true, false) => "Accepted",
+        (false, false) => "Offered",
+    };
+    let deadline = if contract.terms.deadline > now {
+        remaining(contract.terms.deadline, now)
+    } else {
+        "expired".into()
+    };
+    let progress = contract
+        .terms
+        .deliver
+        .iter()
+        .map(|d| {
+            format!(
+                "{} {}/{}",
+                d.trade_symbol, d.units_fulfilled, d.units_required
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Row::new(vec![
+        contract.id.to_string(),
+        status.into(),
+        deadline,
+        progress,
+    ])
+}
+
+/// The time left until `until`, in its two largest units, e.g. `2h 05m`.
+fn remaining(until: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (until - now).num_seconds().max(0);
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", seconds),
+        (0, 0, _) => format!("{}m {:02}s", minutes, seconds % 60),
+        (0, _, _) => format!("{}h {:02}m", hours, minutes),
+        _ => format!("{}d {:02}h", days, hours),
+    }
+}
+
+/// A line for the event log.
+fn describe(event: &ClientEvent) -> String {
+    match event {
+        ClientEvent::CreditsChanged { before, after } => {
+            format!("Credits: {} -> {}", before, after)
+        }
+        ClientEvent::CargoChanged {
+            ship_symbol,
+            before,
+            after,
+        } => format!(
+            "{}: cargo {} -> {} units",
+            ship_symbol, *before.units, *after.units
+        ),
+        ClientEvent::NavChanged {
+            ship_symbol, after, ..
+        } => match after.status {
+            ShipStatus::InTransit => format!(
+                "{}: navigating to {}",
+                ship_symbol, after.route.destination.symbol
+            ),
+            status => format!("{}: {:?} at {}", ship_symbol, status, after.waypoint_symbol),
+        },
+        ClientEvent::FuelChanged {
+            ship_symbol,
+            before,
+            after,
+        } => format!(
+            "{}: fuel {} -> {}",
+            ship_symbol, *before.current, *after.current
+        ),
+        ClientEvent::ContractChanged {
+            contract_id, after, ..
+        } => {
+            let status = if after.fulfilled {
+                "fulfilled"
+            } else {
+                "updated"
+            };
+            format!("Contract {} {}", contract_id, status)
+        }
+        ClientEvent::ShipPurchased { ship_symbol } => format!("Purchased {}", ship_symbol),
+        ClientEvent::CacheReplaced => "Reloaded the agent".into(),
+        ClientEvent::LedgerFailed { error } => format!("Failed to record in the ledger: {}", error),
+    }
+}
+
+/// Leaves the dashboard's alternate screen and gives the terminal back to the shell.
+fn restore_terminal() -> std::io::Result<()> {
+    disable_raw_mode()?;
+    execute!(std::io::stdout(), LeaveAlternateScreen, Show)
+}
+
+fn push_bounded<T>(queue: &mut VecDeque<T>, value: T, capacity: usize) {
+    if queue.len() == capacity {
+        queue.pop_front();
+    }
+    queue.push_back(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "mock")]
+    use crate::mock::MockServer;
+    #[cfg(feature = "mock")]
+    use ratatui::backend::TestBackend;
+
+    #[cfg(feature = "mock")]
+    async fn press(dashboard: &mut Dashboard, keys: &str) {
+        for c in keys.chars() {
+            dashboard.handle_key(KeyCode::Char(c).into()).await;
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    fn screen(dashboard: &Dashboard) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|frame| dashboard.draw(frame)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content
+            .chunks(buffer.area.width as usize)
+            .map(|line| line.iter().map(|cell| cell.symbol.as_str()).collect())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn commands_the_selected_ship() -> STResult<()> {
+        let server = MockServer::start().await?;
+        let client = server.client();
+        client.register_callsign("DASHBOARD", None).await?;
+
+        let mut dashboard = Dashboard::new(client.clone())?;
+        let screen_before = screen(&dashboard);
+        assert!(screen_before.contains("DASHBOARD-1"));
+        assert!(screen_before.contains("Offered"));
+
+        // The selection can't move past the only ship
+        press(&mut dashboard, "jjk").await;
+        assert_eq!(dashboard.selected, 0);
+
+        press(&mut dashboard, "o").await;
+        dashboard.update();
+        assert!(dashboard
+            .log
+            .back()
+            .unwrap()
+            .ends_with("DASHBOARD-1: InOrbit at X1-ZA40-15970B"));
+
+        press(&mut dashboard, "n").await;
+        assert!(screen(&dashboard).contains("Navigate to waypoint:"));
+        press(&mut dashboard, "x1-za40-99095a").await;
+        dashboard.handle_key(KeyCode::Enter.into()).await;
+        dashboard.update();
+        assert!(dashboard
+            .log
+            .iter()
+            .any(|line| line.ends_with("DASHBOARD-1: navigating to X1-ZA40-99095A")));
+        assert!(screen(&dashboard).contains("X1-ZA40-99095A (in"));
+
+        // Mistakes end up in the log instead of stopping the dashboard
+        press(&mut dashboard, "s").await;
+        press(&mut dashboard, "IRON_ORE ten").await;
+        dashboard.handle_key(KeyCode::Enter.into()).await;
+        assert!(dashboard
+            .log
+            .back()
+            .unwrap()
+            .contains("Invalid number of units"));
+
+        press(&mut dashboard, "d").await;
+        assert!(dashboard.log.back().unwrap().contains("Error"));
+
+        press(&mut dashboard, "q").await;
+        assert!(dashboard.should_quit());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn saves_after_each_command() -> STResult<()> {
+        let server = MockServer::start().await?;
+        let client = server.client();
+        client.register_callsign("DASHBOARD", None).await?;
+
+        let path = std::env::temp_dir().join(format!("dashboard-{}.save", uuid::Uuid::new_v4()));
+        let mut dashboard = Dashboard::new(client)?;
+        dashboard.set_save_path(&path);
+
+        press(&mut dashboard, "o").await;
+        let saved = SpaceTradersClient::load_saved_from(&path)?;
+        let ship = saved.get_ship(&Symbol::new("DASHBOARD-1").unwrap())?;
+        assert_eq!(ship.nav.status, ShipStatus::InOrbit);
+
+        std::fs::remove_file(path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn formats_remaining_time() {
+        let now = Utc::now();
+        assert_eq!(remaining(now + chrono::Duration::seconds(42), now), "42s");
+        assert_eq!(
+            remaining(now + chrono::Duration::seconds(125), now),
+            "2m 05s"
+        );
+        assert_eq!(
+            remaining(now + chrono::Duration::minutes(185), now),
+            "3h 05m"
+        );
+        assert_eq!(remaining(now + chrono::Duration::hours(50), now), "2d 02h");
+        assert_eq!(remaining(now - chrono::Duration::hours(1), now), "0s");
+    }
+}
@@ -0,0 +1,141 @@
+//! Notifies subscribers of every change to the client's cached state.
+//!
+//! Whenever an API call changes the agent, a ship or a contract in the client's cache, the client
+//! broadcasts a [ClientEvent] with the values before and after the change. Dashboards, loggers and
+//! strategies can [subscribe](SpaceTradersClient::subscribe) to these instead of polling the cache.
+//!
+//! Events are only sent for values that actually changed. Subscribers that fall too far behind
+//! miss the oldest events, and receive [RecvError::Lagged](tokio::sync::broadcast::error::RecvError::Lagged)
+//! instead.
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let client = SpaceTradersClient::load_saved().unwrap();
+//! let mut events = client.subscribe();
+//!
+//! tokio::spawn(async move {
+//!     while let Ok(event) = events.recv().await {
+//!         if let ClientEvent::CreditsChanged { before, after } = event {
+//!             println!("Credits: {} -> {}", before, after);
+//!         }
+//!     }
+//! });
+//! # })
+//! ```
+
+use crate::{
+    conditional_types::{Id, Symbol},
+    contract::Contract,
+    ship::{Cargo, Fuel, Nav},
+    space_traders_client::SpaceTradersClient,
+};
+use tokio::sync::broadcast;
+
+/// The number of events kept for subscribers that haven't received them yet.
+pub(crate) const EVENT_CAPACITY: usize = 256;
+
+/// A change to the client's cached state.
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    /// The agent's credits changed.
+    CreditsChanged { before: i32, after: i32 },
+    /// The contents of a ship's cargo hold changed.
+    CargoChanged {
+        ship_symbol: Symbol,
+        before: Cargo,
+        after: Cargo,
+    },
+    /// A ship's navigation info changed, e.g. it docked, entered orbit or started navigating.
+    NavChanged {
+        ship_symbol: Symbol,
+        before: Box<Nav>,
+        after: Box<Nav>,
+    },
+    /// A ship's fuel changed.
+    FuelChanged {
+        ship_symbol: Symbol,
+        before: Fuel,
+        after: Fuel,
+    },
+    /// A contract was accepted, delivered to, or fulfilled.
+    ContractChanged {
+        contract_id: Id,
+        before: Box<Contract>,
+        after: Box<Contract>,
+    },
+    /// A new ship was purchased and added to the cache.
+    ShipPurchased { ship_symbol: Symbol },
+    /// The whole cache was replaced, e.g. after registering a new agent.
+    CacheReplaced,
+    /// An action succeeded, but couldn't be recorded in the attached
+    /// [Ledger](crate::ledger::Ledger).
+    LedgerFailed { error: String },
+}
+
+impl SpaceTradersClient {
+    /// Subscribes to the [ClientEvent]s of the client and all of its clones.
+    pub fn subscribe(&self) -> broadcast::Receiver<ClientEvent> {
+        self.events.subscribe()
+    }
+
+    pub(crate) fn emit(&self, event: ClientEvent) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.observe(self, &event);
+        }
+
+        // Sending only fails if there are no subscribers
+        let _ = self.events.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, ship::ShipStatus, STResult};
+
+    #[test]
+    fn emits_changes_to_clones() -> STResult<()> {
+        let client = fixtures::client(vec![fixtures::ship()], vec![]);
+        let mut events = client.subscribe();
+        let clone = client.clone();
+        let ship_symbol = Symbol::new("TST-RS-04-1").unwrap();
+
+        let mut agent = clone.agent()?;
+        agent.credits -= 500;
+        clone.set_agent(agent)?;
+        clone.update_ship(&ship_symbol, |ship| {
+            ship.nav.status = ShipStatus::InOrbit;
+        })?;
+
+        // Updates that don't change anything aren't reported
+        clone.update_ship(&ship_symbol, |ship| {
+            ship.nav.status = ShipStatus::InOrbit;
+        })?;
+
+        assert!(matches!(
+            events.try_recv(),
+            Ok(ClientEvent::CreditsChanged {
+                before: 100000,
+                after: 99500
+            })
+        ));
+        match events.try_recv() {
+            Ok(ClientEvent::NavChanged {
+                ship_symbol: symbol,
+                before,
+                after,
+            }) => {
+                assert_eq!(symbol, ship_symbol);
+                assert_eq!(before.status, ShipStatus::Docked);
+                assert_eq!(after.status, ShipStatus::InOrbit);
+            }
+            event => panic!("expected a nav change: {:?}", event),
+        }
+        assert!(events.try_recv().is_err());
+
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use crate::conditional_types::{Description, Headquarters, Name};
 
-#[derive(serde::Deserialize, Debug, serde::Serialize)]
+#[derive(serde::Deserialize, Debug, serde::Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Faction {
     pub(crate) symbol: FactionSymbol,
@@ -32,7 +32,7 @@ impl FactionSymbol {
     }
 }
 
-#[derive(serde::Deserialize, Debug, serde::Serialize)]
+#[derive(serde::Deserialize, Debug, serde::Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Trait {
     pub(crate) symbol: FactionTraitSymbol,
@@ -40,7 +40,7 @@ pub(crate) struct Trait {
     pub(crate) description: Description,
 }
 
-#[derive(serde::Deserialize, Debug, serde::Serialize, PartialEq, Eq)]
+#[derive(serde::Deserialize, Debug, serde::Serialize, PartialEq, Eq, Clone)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub(crate) enum FactionTraitSymbol {
     Bureaucratic,
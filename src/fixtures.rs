@@ -0,0 +1,101 @@
+//! Values shared by the unit tests.
+
+use crate::{
+    conditional_types::Symbol,
+    space_traders_client::{CachedInfo, SpaceTradersClient},
+};
+use serde_json::{json, Value};
+
+pub(crate) fn symbol(symbol: &str) -> Symbol {
+    Symbol::new(symbol).unwrap()
+}
+
+/// The ship `TST-RS-04-1`, docked at `X1-A-1` with an empty hold, a full tank and no mounts.
+pub(crate) fn ship() -> Value {
+    ship_docked_at("X1-A-1")
+}
+
+/// The ship from [ship], docked at `waypoint` instead.
+pub(crate) fn ship_docked_at(waypoint: &str) -> Value {
+    let system = waypoint.rsplit_once('-').unwrap().0;
+    let location =
+        json!({ "symbol": waypoint, "type": "PLANET", "systemSymbol": system, "x": 0, "y": 0 });
+    json!({
+        "symbol": "TST-RS-04-1",
+        "registration": { "name": "TST-RS-04-1", "factionSymbol": "COSMIC", "role": "COMMAND" },
+        "nav": {
+            "systemSymbol": system,
+            "waypointSymbol": waypoint,
+            "route": {
+                "destination": location,
+                "departure": location,
+                "departureTime": "2023-05-17T04:18:05.930Z",
+                "arrival": "2023-05-17T04:18:05.930Z"
+            },
+            "status": "DOCKED",
+            "flightMode": "CRUISE"
+        },
+        "crew": { "current": 0, "required": 59, "capacity": 80, "rotation": "STRICT", "morale": 100, "wages": 0 },
+        "frame": {
+            "symbol": "FRAME_FRIGATE", "name": "Frame Frigate", "description": "A frigate.", "condition": 100,
+            "moduleSlots": 8, "mountingPoints": 5, "fuelCapacity": 1200, "requirements": { "power": 8, "crew": 25 }
+        },
+        "reactor": {
+            "symbol": "REACTOR_FISSION_I", "name": "Fission Reactor I", "description": "A reactor.", "condition": 100,
+            "powerOutput": 31, "requirements": { "crew": 8 }
+        },
+        "engine": {
+            "symbol": "ENGINE_ION_DRIVE_II", "name": "Ion Drive II", "description": "An engine.", "condition": 100,
+            "speed": 30, "requirements": { "power": 6, "crew": 8 }
+        },
+        "modules": [],
+        "mounts": [],
+        "cargo": { "capacity": 60, "units": 0, "inventory": [] },
+        "fuel": { "current": 1200, "capacity": 1200, "consumed": { "amount": 0, "timestamp": "2023-05-17T04:18:05.930Z" } }
+    })
+}
+
+/// The ship from [ship], on its way from `X1-A-1` to `X1-A-2` and due at 04:18:35.930Z.
+pub(crate) fn ship_in_transit() -> Value {
+    let mut ship = ship();
+    ship["nav"]["waypointSymbol"] = json!("X1-A-2");
+    ship["nav"]["route"]["destination"] =
+        json!({ "symbol": "X1-A-2", "type": "MOON", "systemSymbol": "X1-A", "x": 30, "y": 0 });
+    ship["nav"]["route"]["arrival"] = json!("2023-05-17T04:18:35.930Z");
+    ship["nav"]["status"] = json!("IN_TRANSIT");
+    ship
+}
+
+pub(crate) fn mining_laser() -> Value {
+    json!({ "symbol": "MOUNT_MINING_LASER_I", "name": "Mining Laser I", "description": "A laser.", "strength": 10, "requirements": { "crew": 0, "power": 1 } })
+}
+
+pub(crate) fn surveyor() -> Value {
+    json!({ "symbol": "MOUNT_SURVEYOR_I", "name": "Surveyor I", "description": "A surveyor.", "strength": 1, "requirements": { "crew": 2, "power": 1 } })
+}
+
+/// A client whose cache holds the agent `TST-RS-04` with the given ships and contracts.
+pub(crate) fn client(ships: Vec<Value>, contracts: Vec<Value>) -> SpaceTradersClient {
+    let cache: CachedInfo = serde_json::from_value(json!({
+        "agent": {
+            "accountId": "clhr6zx0q07s1s60djj5u61ml",
+            "symbol": "TST-RS-04",
+            "headquarters": "X1-A-1",
+            "credits": 100000
+        },
+        "contracts": contracts,
+        "faction": {
+            "symbol": "COSMIC",
+            "name": "Cosmic Engineers",
+            "description": "Engineers.",
+            "headquarters": "X1-A-1",
+            "traits": []
+        },
+        "ships": ships
+    }))
+    .unwrap();
+
+    let client = SpaceTradersClient::new();
+    client.set_cache(cache);
+    client
+}
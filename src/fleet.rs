@@ -0,0 +1,538 @@
+//! Runs a behaviour for every ship of the agent at the same time.
+//!
+//! A [Fleet] runs one task per ship, each with its own clone of the [SpaceTradersClient]. Each ship
+//! is given an [Assignment] (such as mining, or fulfilling a contract), which the fleet drives one
+//! step at a time. All clones share the client's cache and
+//! [RateLimiter](crate::rate_limiter::RateLimiter), so the fleet never goes over the API's rate
+//! limit.
+//!
+//! Ships can be paused, resumed, stopped, and reassigned while the fleet is running.
+//! A step that fails with a [retryable](crate::SpaceTradersError::is_retryable) error, such as
+//! being rate limited, is retried after a short delay; any other error stops the ship's task.
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let client = SpaceTradersClient::load_saved().unwrap();
+//! let mut fleet = Fleet::new(client);
+//!
+//! let mut events = fleet.subscribe();
+//! tokio::spawn(async move {
+//!     while let Some(event) = events.recv().await {
+//!         println!("{}: {:?}", event.ship_symbol, event.kind);
+//!     }
+//! });
+//!
+//! let config = MiningConfig::new(
+//!     Symbol::new("X1-ZA40-99095A").unwrap(),
+//!     Symbol::new("X1-ZA40-15970B").unwrap(),
+//! );
+//! for ship in ["TST-RS-04-1", "TST-RS-04-2"] {
+//!     fleet
+//!         .assign(&Symbol::new(ship).unwrap(), Assignment::Mine(config.clone()))
+//!         .unwrap();
+//! }
+//!
+//! tokio::time::sleep(std::time::Duration::from_secs(3600)).await;
+//! fleet.shutdown().await;
+//! # })
+//! ```
+
+use crate::{
+    conditional_types::Symbol,
+    contract_planner::{ContractEvent, ContractExecutor, ContractPlan, ContractStep},
+    mining::{MiningConfig, MiningEvent, MiningLoop, MiningStep},
+    space_traders_client::SpaceTradersClient,
+    STResult, SpaceTradersError,
+};
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, sync::Arc};
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
+
+/// The behaviour assigned to a ship.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Assignment {
+    /// Run a [MiningLoop] until the ship is stopped.
+    Mine(MiningConfig),
+    /// Carry out a [ContractPlan] with a [ContractExecutor].
+    FulfillContract(ContractPlan),
+    /// Run the script at the path with a [ScriptRunner](crate::scripting::ScriptRunner), until it
+    /// calls `finish()`.
+    #[cfg(feature = "scripting")]
+    Script(std::path::PathBuf),
+}
+
+/// The state of a ship's task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskState {
+    /// The ship is performing an action.
+    Running,
+    /// The ship is busy (travelling or cooling down) until the given time.
+    Waiting(DateTime<Utc>),
+    Paused,
+    /// The assignment was completed.
+    Finished,
+    /// The ship was stopped before its assignment was completed.
+    Stopped,
+    /// The assignment failed with the given error.
+    Failed(String),
+}
+
+/// Progress of a single ship in the [Fleet].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FleetEvent {
+    pub ship_symbol: Symbol,
+    pub kind: FleetEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FleetEventKind {
+    Assigned,
+    Mining(MiningEvent),
+    Contract(ContractEvent),
+    #[cfg(feature = "scripting")]
+    Script(crate::scripting::ScriptEvent),
+    Paused,
+    Resumed,
+    Finished,
+    Stopped,
+    Failed(String),
+}
+
+/// Controls a ship's task from the [Fleet].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Run,
+    Pause,
+    Stop,
+}
+
+/// The behaviour being driven by a ship's task, along with its event receiver.
+enum Behaviour {
+    Mining(MiningLoop, mpsc::UnboundedReceiver<MiningEvent>),
+    Contract(ContractExecutor, mpsc::UnboundedReceiver<ContractEvent>),
+    #[cfg(feature = "scripting")]
+    Script(
+        crate::scripting::ScriptRunner,
+        mpsc::UnboundedReceiver<crate::scripting::ScriptEvent>,
+    ),
+}
+
+impl Behaviour {
+    fn new(ship_symbol: &Symbol, assignment: Assignment) -> Self {
+        match assignment {
+            Assignment::Mine(config) => {
+                let mut mining = MiningLoop::new(ship_symbol.clone(), config);
+                let events = mining.subscribe();
+                Behaviour::Mining(mining, events)
+            }
+            Assignment::FulfillContract(plan) => {
+                let mut executor = ContractExecutor::new(plan);
+                let events = executor.subscribe();
+                Behaviour::Contract(executor, events)
+            }
+            #[cfg(feature = "scripting")]
+            Assignment::Script(path) => {
+                let mut runner = crate::scripting::ScriptRunner::new(ship_symbol.clone(), path);
+                let events = runner.subscribe();
+                Behaviour::Script(runner, events)
+            }
+        }
+    }
+
+    async fn step(&mut self, client: &SpaceTradersClient) -> STResult<ContractStep> {
+        match self {
+            Behaviour::Mining(mining, _) => Ok(match mining.step(client).await? {
+                MiningStep::Continue => ContractStep::Continue,
+                MiningStep::WaitUntil(until) => ContractStep::WaitUntil(until),
+            }),
+            Behaviour::Contract(executor, _) => executor.step(client).await,
+            #[cfg(feature = "scripting")]
+            Behaviour::Script(runner, _) => {
+                use crate::scripting::ScriptStep;
+
+                Ok(match runner.step(client).await? {
+                    ScriptStep::Continue => ContractStep::Continue,
+                    ScriptStep::WaitUntil(until) => ContractStep::WaitUntil(until),
+                    ScriptStep::Finished => ContractStep::Finished,
+                })
+            }
+        }
+    }
+
+    /// Takes the events reported during the last step.
+    fn events(&mut self) -> Vec<FleetEventKind> {
+        let mut events = Vec::new();
+        match self {
+            Behaviour::Mining(_, rx) => {
+                while let Ok(event) = rx.try_recv() {
+                    events.push(FleetEventKind::Mining(event));
+                }
+            }
+            Behaviour::Contract(_, rx) => {
+                while let Ok(event) = rx.try_recv() {
+                    events.push(FleetEventKind::Contract(event));
+                }
+            }
+            #[cfg(feature = "scripting")]
+            Behaviour::Script(_, rx) => {
+                while let Ok(event) = rx.try_recv() {
+                    events.push(FleetEventKind::Script(event));
+                }
+            }
+        }
+        events
+    }
+}
+
+type EventSender = Arc<std::sync::Mutex<Option<mpsc::UnboundedSender<FleetEvent>>>>;
+
+#[derive(Debug)]
+struct ShipTask {
+    assignment: Assignment,
+    control: watch::Sender<Control>,
+    state: Arc<std::sync::Mutex<TaskState>>,
+    handle: JoinHandle<()>,
+}
+
+/// Runs an [Assignment] for each ship concurrently, sharing a single [SpaceTradersClient].
+#[derive(Debug)]
+pub struct Fleet {
+    client: SpaceTradersClient,
+    tasks: HashMap<Symbol, ShipTask>,
+    /// Tasks that were stopped, but may still be completing their current action.
+    stopping: HashMap<Symbol, JoinHandle<()>>,
+    events: EventSender,
+}
+
+impl Fleet {
+    pub fn new(client: SpaceTradersClient) -> Self {
+        Self {
+            client,
+            tasks: HashMap::new(),
+            stopping: HashMap::new(),
+            events: Arc::new(std::sync::Mutex::new(None)),
+        }
+    }
+
+    /// Get the client shared by every ship in the fleet.
+    pub fn client(&self) -> &SpaceTradersClient {
+        &self.client
+    }
+
+    /// Get a receiver for the events reported by every ship in the fleet.
+    ///
+    /// Only the most recent receiver gets the events.
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<FleetEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.events.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    /// Starts running the assignment for the ship.
+    ///
+    /// If the ship already has an assignment, it is stopped once its current action completes and
+    /// replaced by the new one. The new assignment only starts once the old one has stopped.
+    pub fn assign(&mut self, ship_symbol: &Symbol, assignment: Assignment) -> STResult<()> {
+        // Check if ship_symbol exists first
+        self.client
+            .get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("assign"))?;
+
+        let previous = match self.tasks.remove(ship_symbol) {
+            Some(task) => {
+                task.control.send_replace(Control::Stop);
+                Some(task.handle)
+            }
+            None => self.stopping.remove(ship_symbol),
+        };
+
+        let (control, control_rx) = watch::channel(Control::Run);
+        let state = Arc::new(std::sync::Mutex::new(TaskState::Running));
+
+        emit(&self.events, ship_symbol, FleetEventKind::Assigned);
+
+        let handle = tokio::spawn(run_task(
+            previous,
+            ship_symbol.clone(),
+            Behaviour::new(ship_symbol, assignment.clone()),
+            self.client.clone(),
+            control_rx,
+            state.clone(),
+            self.events.clone(),
+        ));
+
+        self.tasks.insert(
+            ship_symbol.clone(),
+            ShipTask {
+                assignment,
+                control,
+                state,
+                handle,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Pauses the ship once its current action completes.
+    pub fn pause(&self, ship_symbol: &Symbol) -> STResult<()> {
+        self.control(ship_symbol, Control::Pause)
+    }
+
+    /// Resumes a paused ship.
+    pub fn resume(&self, ship_symbol: &Symbol) -> STResult<()> {
+        self.control(ship_symbol, Control::Run)
+    }
+
+    /// Stops the ship once its current action completes, and removes its assignment.
+    pub fn stop(&mut self, ship_symbol: &Symbol) -> STResult<()> {
+        self.control(ship_symbol, Control::Stop)?;
+        if let Some(task) = self.tasks.remove(ship_symbol) {
+            self.stopping.retain(|_, handle| !handle.is_finished());
+            self.stopping.insert(ship_symbol.clone(), task.handle);
+        }
+
+        Ok(())
+    }
+
+    /// Get the symbols of every ship with an assignment.
+    pub fn ships(&self) -> Vec<Symbol> {
+        self.tasks.keys().cloned().collect()
+    }
+
+    /// Get the assignment of the ship, if it has one.
+    pub fn assignment(&self, ship_symbol: &Symbol) -> Option<&Assignment> {
+        self.tasks.get(ship_symbol).map(|task| &task.assignment)
+    }
+
+    /// Get the state of the ship's task, if it has an assignment.
+    pub fn state(&self, ship_symbol: &Symbol) -> Option<TaskState> {
+        self.tasks
+            .get(ship_symbol)
+            .map(|task| task.state.lock().unwrap().clone())
+    }
+
+    /// Stops every ship and waits for their current actions to complete.
+    pub async fn shutdown(mut self) {
+        for task in self.tasks.values() {
+            task.control.send_replace(Control::Stop);
+        }
+
+        let handles = self.tasks.drain().map(|(_, task)| task.handle);
+        for handle in handles.chain(self.stopping.drain().map(|(_, handle)| handle)) {
+            // A task only fails to join if it panicked, in which case it's already stopped
+            let _ = handle.await;
+        }
+    }
+
+    fn control(&self, ship_symbol: &Symbol, control: Control) -> STResult<()> {
+        let task =
+            self.tasks
+                .get(ship_symbol)
+                .ok_or_else(|| SpaceTradersError::InvalidShipSymbol {
+                    ship_symbol: ship_symbol.to_string(),
+                    operation: Some(match control {
+                        Control::Run => "resume",
+                        Control::Pause => "pause",
+                        Control::Stop => "stop",
+                    }),
+                })?;
+        task.control.send_replace(control);
+
+        Ok(())
+    }
+}
+
+fn emit(events: &EventSender, ship_symbol: &Symbol, kind: FleetEventKind) {
+    if let Some(events) = events.lock().unwrap().as_ref() {
+        // The receiver may have been dropped, which just means nobody is listening
+        let _ = events.send(FleetEvent {
+            ship_symbol: ship_symbol.clone(),
+            kind,
+        });
+    }
+}
+
+/// Drives the behaviour of a single ship until it finishes, fails, or is stopped.
+///
+/// The ship's previous task, if any, is waited for first so two tasks never control the ship at
+/// once.
+async fn run_task(
+    previous: Option<JoinHandle<()>>,
+    ship_symbol: Symbol,
+    mut behaviour: Behaviour,
+    client: SpaceTradersClient,
+    mut control: watch::Receiver<Control>,
+    state: Arc<std::sync::Mutex<TaskState>>,
+    events: EventSender,
+) {
+    let set_state = |new_state: TaskState| *state.lock().unwrap() = new_state;
+
+    if let Some(previous) = previous {
+        // A task only fails to join if it panicked, in which case it's already stopped
+        let _ = previous.await;
+    }
+
+    loop {
+        // The task stops when the fleet is dropped
+        let current = match control.has_changed() {
+            Ok(_) => *control.borrow_and_update(),
+            Err(_) => Control::Stop,
+        };
+        match current {
+            Control::Stop => {
+                set_state(TaskState::Stopped);
+                emit(&events, &ship_symbol, FleetEventKind::Stopped);
+                return;
+            }
+            Control::Pause => {
+                set_state(TaskState::Paused);
+                emit(&events, &ship_symbol, FleetEventKind::Paused);
+
+                if control.changed().await.is_ok() && *control.borrow() == Control::Run {
+                    emit(&events, &ship_symbol, FleetEventKind::Resumed);
+                }
+                continue;
+            }
+            Control::Run => set_state(TaskState::Running),
+        }
+
+        let step = behaviour.step(&client).await;
+        for event in behaviour.events() {
+            emit(&events, &ship_symbol, event);
+        }
+
+        // Retryable errors, like being rate limited, are expected while other ships share the
+        // client, so only other errors stop the task
+        let step = match step {
+            Err(err) => client
+                .retry_at(&err)
+                .map(ContractStep::WaitUntil)
+                .ok_or(err),
+            step => step,
+        };
+
+        match step {
+            Ok(ContractStep::Continue) => {}
+            Ok(ContractStep::WaitUntil(until)) => {
+                set_state(TaskState::Waiting(until));
+                tokio::select! {
+                    _ = client.scheduler().clock().sleep_until(until) => {}
+                    _ = control.changed() => {}
+                }
+            }
+            Ok(ContractStep::Finished) => {
+                set_state(TaskState::Finished);
+                emit(&events, &ship_symbol, FleetEventKind::Finished);
+                return;
+            }
+            Err(err) => {
+                set_state(TaskState::Failed(err.to_string()));
+                emit(
+                    &events,
+                    &ship_symbol,
+                    FleetEventKind::Failed(err.to_string()),
+                );
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, symbol};
+    use chrono::Duration;
+
+    /// A client with a single ship and a contract that has already been fulfilled.
+    fn client() -> SpaceTradersClient {
+        let contract = serde_json::json!({
+            "id": "clhr6zx0r07s2s60daxqce7b1",
+            "factionSymbol": "COSMIC",
+            "type": "PROCUREMENT",
+            "terms": {
+                "deadline": (Utc::now() + Duration::days(7)).to_rfc3339(),
+                "payment": { "onAccepted": 1000, "onFulfilled": 10000 },
+                "deliver": []
+            },
+            "accepted": true,
+            "fulfilled": true,
+            "expiration": (Utc::now() + Duration::days(1)).to_rfc3339(),
+        });
+        fixtures::client(vec![fixtures::ship()], vec![contract])
+    }
+
+    fn plan() -> ContractPlan {
+        ContractPlan {
+            contract_id: symbol("clhr6zx0r07s2s60daxqce7b1"),
+            deliveries: vec![],
+            payment: 0,
+            cost: 0,
+            duration: 0,
+            deadline: Utc::now() + Duration::days(7),
+            meets_deadline: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_assignments_to_completion() -> STResult<()> {
+        let mut fleet = Fleet::new(client());
+        let mut events = fleet.subscribe();
+        let ship = symbol("TST-RS-04-1");
+        let assignment = Assignment::FulfillContract(plan());
+
+        fleet.assign(&ship, assignment.clone())?;
+        assert_eq!(fleet.ships(), vec![ship.clone()]);
+        assert_eq!(fleet.assignment(&ship), Some(&assignment));
+
+        assert_eq!(events.recv().await.unwrap().kind, FleetEventKind::Assigned);
+        assert_eq!(events.recv().await.unwrap().kind, FleetEventKind::Finished);
+        assert_eq!(fleet.state(&ship), Some(TaskState::Finished));
+
+        fleet.shutdown().await;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_previous_assignment_to_stop() -> STResult<()> {
+        let mut fleet = Fleet::new(client());
+        let mut events = fleet.subscribe();
+        let ship = symbol("TST-RS-04-1");
+
+        fleet.assign(&ship, Assignment::FulfillContract(plan()))?;
+        fleet.pause(&ship)?;
+        assert_eq!(events.recv().await.unwrap().kind, FleetEventKind::Assigned);
+        assert_eq!(events.recv().await.unwrap().kind, FleetEventKind::Paused);
+
+        // The paused task stops before the new one runs
+        fleet.assign(&ship, Assignment::FulfillContract(plan()))?;
+        assert_eq!(events.recv().await.unwrap().kind, FleetEventKind::Assigned);
+        assert_eq!(events.recv().await.unwrap().kind, FleetEventKind::Stopped);
+        assert_eq!(events.recv().await.unwrap().kind, FleetEventKind::Finished);
+
+        fleet.shutdown().await;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_ships() {
+        let mut fleet = Fleet::new(client());
+        let ship = symbol("TST-RS-04-9");
+
+        assert!(matches!(
+            fleet.assign(&ship, Assignment::FulfillContract(plan())),
+            Err(SpaceTradersError::InvalidShipSymbol { .. })
+        ));
+        assert!(fleet.pause(&ship).is_err());
+        assert!(fleet.stop(&ship).is_err());
+        assert_eq!(fleet.state(&ship), None);
+    }
+}
@@ -0,0 +1,500 @@
+//! Provides a persistent, [SQLite](https://www.sqlite.org/) backed cache of galaxy data.
+//!
+//! Systems, waypoints (along with their traits and charts), markets and shipyards are stored
+//! locally with the time they were fetched. When a [GalaxyCache] is attached to a
+//! [SpaceTradersClient], the client checks the cache before making any API calls, so repeatedly
+//! scanning the same systems does not use up the rate limit.
+//!
+//! # Example
+//! ```
+//! # use space_traders::prelude::*;
+//! let mut client = SpaceTradersClient::new();
+//!
+//! // Data is stored in `galaxy.db`, and market data is considered stale after 5 minutes.
+//! let path = std::env::temp_dir().join("galaxy.db");
+//! let mut cache = GalaxyCache::open(&path).unwrap();
+//! cache.set_max_age(CachedData::Markets, Some(chrono::Duration::minutes(5)));
+//!
+//! client.set_galaxy_cache(cache);
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use crate::{
+    conditional_types::Symbol, market::Market, ship::Shipyard,
+    space_traders_client::SpaceTradersClient, system::System, waypoint::Waypoint, STResult,
+};
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+/// The kinds of data stored in a [GalaxyCache].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachedData {
+    Systems,
+    Waypoints,
+    Markets,
+    Shipyards,
+}
+
+impl CachedData {
+    fn table(&self) -> &'static str {
+        match self {
+            CachedData::Systems => "systems",
+            CachedData::Waypoints => "waypoints",
+            CachedData::Markets => "markets",
+            CachedData::Shipyards => "shipyards",
+        }
+    }
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS systems (
+        symbol TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS waypoints (
+        symbol TEXT PRIMARY KEY,
+        system_symbol TEXT NOT NULL,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS waypoints_by_system ON waypoints (system_symbol);
+    CREATE TABLE IF NOT EXISTS waypoint_traits (
+        waypoint_symbol TEXT NOT NULL,
+        trait_symbol TEXT NOT NULL,
+        PRIMARY KEY (waypoint_symbol, trait_symbol)
+    );
+    CREATE TABLE IF NOT EXISTS charts (
+        waypoint_symbol TEXT PRIMARY KEY,
+        submitted_by TEXT,
+        submitted_on TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS markets (
+        symbol TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS shipyards (
+        symbol TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        fetched_at TEXT NOT NULL
+    );
+";
+
+/// A local store of galaxy data, backed by a SQLite database.
+///
+/// By default, systems and waypoints never expire, while markets and shipyards are refetched
+/// after 15 minutes since their listings change frequently. Markets are only cached when they list
+/// their trade goods (which requires a ship at the market), so prices are never missing from a
+/// cached market.
+#[derive(Debug)]
+pub struct GalaxyCache {
+    conn: Mutex<Connection>,
+    system_max_age: Option<Duration>,
+    waypoint_max_age: Option<Duration>,
+    market_max_age: Option<Duration>,
+    shipyard_max_age: Option<Duration>,
+}
+
+impl GalaxyCache {
+    /// Opens (or creates) the cache database at the given path.
+    pub fn open(path: impl AsRef<Path>) -> STResult<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Creates a cache that only lives in memory.
+    pub fn open_in_memory() -> STResult<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> STResult<Self> {
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            system_max_age: None,
+            waypoint_max_age: None,
+            market_max_age: Some(Duration::minutes(15)),
+            shipyard_max_age: Some(Duration::minutes(15)),
+        })
+    }
+
+    /// Sets how long the given kind of data is considered fresh.
+    ///
+    /// Data older than `max_age` is ignored and refetched from the API; `None` means the data
+    /// never expires.
+    pub fn set_max_age(&mut self, kind: CachedData, max_age: Option<Duration>) {
+        match kind {
+            CachedData::Systems => self.system_max_age = max_age,
+            CachedData::Waypoints => self.waypoint_max_age = max_age,
+            CachedData::Markets => self.market_max_age = max_age,
+            CachedData::Shipyards => self.shipyard_max_age = max_age,
+        }
+    }
+
+    /// Returns the time the given entry was last fetched, if it is cached.
+    pub fn fetched_at(&self, kind: CachedData, symbol: &str) -> STResult<Option<DateTime<Utc>>> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+
+        Ok(conn
+            .query_row(
+                &format!("SELECT fetched_at FROM {} WHERE symbol = ?1", kind.table()),
+                params![symbol],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Removes all cached entries of the given kind.
+    pub fn clear(&self, kind: CachedData) -> STResult<()> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+
+        conn.execute(&format!("DELETE FROM {}", kind.table()), [])?;
+        if kind == CachedData::Waypoints {
+            conn.execute("DELETE FROM waypoint_traits", [])?;
+            conn.execute("DELETE FROM charts", [])?;
+        }
+
+        Ok(())
+    }
+
+    /// Get all cached waypoints in the given system, regardless of their age.
+    pub fn waypoints_in_system(&self, system_symbol: &str) -> STResult<Vec<Waypoint>> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut stmt =
+            conn.prepare("SELECT data FROM waypoints WHERE system_symbol = ?1 ORDER BY symbol")?;
+        let rows = stmt.query_map(params![system_symbol], |row| row.get::<_, String>(0))?;
+
+        let mut waypoints = Vec::new();
+        for data in rows {
+            waypoints.push(serde_json::from_str(&data?)?);
+        }
+
+        Ok(waypoints)
+    }
+
+    /// Get the symbols of all cached waypoints in the given system that have the specified trait
+    /// (i.e. `"MARKETPLACE"` or `"SHIPYARD"`).
+    pub fn waypoints_with_trait(
+        &self,
+        system_symbol: &str,
+        trait_symbol: &str,
+    ) -> STResult<Vec<Symbol>> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut stmt = conn.prepare(
+            "SELECT waypoints.symbol FROM waypoints
+             JOIN waypoint_traits ON waypoints.symbol = waypoint_traits.waypoint_symbol
+             WHERE waypoints.system_symbol = ?1 AND waypoint_traits.trait_symbol = ?2
+             ORDER BY waypoints.symbol",
+        )?;
+        let rows = stmt.query_map(params![system_symbol, trait_symbol], |row| {
+            row.get::<_, String>(0)
+        })?;
+
+        let mut symbols = Vec::new();
+        for symbol in rows {
+            // Symbols are only ever stored from deserialized (non-empty) values
+            symbols.push(Symbol::new(&symbol?).unwrap());
+        }
+
+        Ok(symbols)
+    }
+
+    pub(crate) fn system(&self, symbol: &str) -> STResult<Option<System>> {
+        self.get(CachedData::Systems, symbol, self.system_max_age)
+    }
+
+    pub(crate) fn waypoint(&self, symbol: &str) -> STResult<Option<Waypoint>> {
+        self.get(CachedData::Waypoints, symbol, self.waypoint_max_age)
+    }
+
+    pub(crate) fn market(&self, symbol: &str) -> STResult<Option<Market>> {
+        self.get(CachedData::Markets, symbol, self.market_max_age)
+    }
+
+    pub(crate) fn shipyard(&self, symbol: &str) -> STResult<Option<Shipyard>> {
+        self.get(CachedData::Shipyards, symbol, self.shipyard_max_age)
+    }
+
+    pub(crate) fn store_system(&self, system: &System) -> STResult<()> {
+        self.put(CachedData::Systems, &system.symbol, system, Utc::now())
+    }
+
+    pub(crate) fn store_market(&self, market: &Market) -> STResult<()> {
+        // Markets without prices would hide them from later requests made with a ship present
+        if market.trade_goods.is_none() {
+            return Ok(());
+        }
+
+        self.put(CachedData::Markets, &market.symbol, market, Utc::now())
+    }
+
+    pub(crate) fn store_shipyard(&self, shipyard: &Shipyard) -> STResult<()> {
+        self.put(
+            CachedData::Shipyards,
+            &shipyard.symbol,
+            shipyard,
+            Utc::now(),
+        )
+    }
+
+    pub(crate) fn store_waypoint(&self, waypoint: &Waypoint) -> STResult<()> {
+        self.store_waypoint_at(waypoint, Utc::now())
+    }
+
+    fn store_waypoint_at(&self, waypoint: &Waypoint, fetched_at: DateTime<Utc>) -> STResult<()> {
+        let mut conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "INSERT OR REPLACE INTO waypoints (symbol, system_symbol, data, fetched_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                waypoint.symbol.as_str(),
+                waypoint.system_symbol.as_str(),
+                serde_json::to_string(waypoint)?,
+                fetched_at
+            ],
+        )?;
+
+        // Traits and charts are stored separately so they can be queried directly
+        tx.execute(
+            "DELETE FROM waypoint_traits WHERE waypoint_symbol = ?1",
+            params![waypoint.symbol.as_str()],
+        )?;
+        for waypoint_trait in &waypoint.traits {
+            tx.execute(
+                "INSERT INTO waypoint_traits (waypoint_symbol, trait_symbol) VALUES (?1, ?2)",
+                params![
+                    waypoint.symbol.as_str(),
+                    serde_json::to_value(waypoint_trait.symbol)?.as_str()
+                ],
+            )?;
+        }
+        if let Some(chart) = &waypoint.chart {
+            tx.execute(
+                "INSERT OR REPLACE INTO charts (waypoint_symbol, submitted_by, submitted_on)
+                 VALUES (?1, ?2, ?3)",
+                params![
+                    waypoint.symbol.as_str(),
+                    chart.submitted_by.as_ref().map(|s| s.as_str()),
+                    chart.submitted_on
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn get<T: DeserializeOwned>(
+        &self,
+        kind: CachedData,
+        symbol: &str,
+        max_age: Option<Duration>,
+    ) -> STResult<Option<T>> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let row: Option<(String, DateTime<Utc>)> = conn
+            .query_row(
+                &format!(
+                    "SELECT data, fetched_at FROM {} WHERE symbol = ?1",
+                    kind.table()
+                ),
+                params![symbol],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        match row {
+            Some((_, fetched_at)) if max_age.is_some_and(|age| Utc::now() - fetched_at > age) => {
+                Ok(None)
+            }
+            Some((data, _)) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put<T: Serialize>(
+        &self,
+        kind: CachedData,
+        symbol: &str,
+        data: &T,
+        fetched_at: DateTime<Utc>,
+    ) -> STResult<()> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+
+        conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} (symbol, data, fetched_at) VALUES (?1, ?2, ?3)",
+                kind.table()
+            ),
+            params![symbol, serde_json::to_string(data)?, fetched_at],
+        )?;
+
+        Ok(())
+    }
+}
+
+impl SpaceTradersClient {
+    /// Attach a [GalaxyCache] to the client.
+    ///
+    /// All subsequent calls to `view_system`, `view_waypoint`, `view_market` and `view_shipyard`
+    /// will check the cache before making an API call, and store their responses in it.
+    pub fn set_galaxy_cache(&mut self, cache: GalaxyCache) {
+        self.galaxy_cache = Some(std::sync::Arc::new(cache));
+    }
+
+    /// Get a reference to the [GalaxyCache] attached to the client, if there is one.
+    pub fn galaxy_cache(&self) -> Option<&GalaxyCache> {
+        self.galaxy_cache.as_deref()
+    }
+
+    pub(crate) fn cached_system(&self, system_symbol: &Symbol) -> STResult<Option<System>> {
+        match &self.galaxy_cache {
+            Some(cache) => cache.system(system_symbol),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn cached_waypoint(&self, waypoint_symbol: &Symbol) -> STResult<Option<Waypoint>> {
+        match &self.galaxy_cache {
+            Some(cache) => cache.waypoint(waypoint_symbol),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn cached_market(&self, waypoint_symbol: &Symbol) -> STResult<Option<Market>> {
+        match &self.galaxy_cache {
+            Some(cache) => cache.market(waypoint_symbol),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn cached_shipyard(&self, waypoint_symbol: &Symbol) -> STResult<Option<Shipyard>> {
+        match &self.galaxy_cache {
+            Some(cache) => cache.shipyard(waypoint_symbol),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::waypoint::WaypointTraitSymbols;
+
+    const WAYPOINT: &str = r#"{
+        "symbol": "X1-ZA40-15970B",
+        "type": "PLANET",
+        "systemSymbol": "X1-ZA40",
+        "x": 10,
+        "y": 0,
+        "orbitals": [{ "symbol": "X1-ZA40-69371X" }],
+        "faction": { "symbol": "COSMIC" },
+        "traits": [
+            {
+                "symbol": "MARKETPLACE",
+                "name": "Marketplace",
+                "description": "A thriving center of commerce where traders from across the galaxy gather to buy, sell, and exchange goods."
+            }
+        ],
+        "chart": { "submittedBy": "COSMIC", "submittedOn": "2023-05-13T17:48:46.579Z" }
+    }"#;
+
+    #[test]
+    fn can_store_and_load_waypoints() -> STResult<()> {
+        let cache = GalaxyCache::open_in_memory()?;
+        let waypoint: Waypoint = serde_json::from_str(WAYPOINT)?;
+
+        assert!(cache.waypoint("X1-ZA40-15970B")?.is_none());
+
+        cache.store_waypoint(&waypoint)?;
+
+        let cached = cache.waypoint("X1-ZA40-15970B")?.unwrap();
+        assert_eq!(cached.symbol, "X1-ZA40-15970B");
+        assert_eq!(cached.system_symbol, "X1-ZA40");
+        assert_eq!(cached.traits[0].symbol, WaypointTraitSymbols::Marketplace);
+        assert_eq!(cached.chart.unwrap().submitted_by.unwrap(), "COSMIC");
+        assert!(cache
+            .fetched_at(CachedData::Waypoints, "X1-ZA40-15970B")?
+            .is_some());
+
+        assert_eq!(cache.waypoints_in_system("X1-ZA40")?.len(), 1);
+        assert_eq!(
+            cache.waypoints_with_trait("X1-ZA40", "MARKETPLACE")?,
+            vec![Symbol::new("X1-ZA40-15970B").unwrap()]
+        );
+        assert!(cache
+            .waypoints_with_trait("X1-ZA40", "SHIPYARD")?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn client_checks_the_cache_before_the_token() -> STResult<()> {
+        let cache = GalaxyCache::open_in_memory()?;
+        cache.store_waypoint(&serde_json::from_str(WAYPOINT)?)?;
+
+        let mut client = SpaceTradersClient::new();
+        client.set_galaxy_cache(cache);
+
+        let waypoint = client
+            .view_waypoint(
+                Symbol::new("X1-ZA40").unwrap(),
+                Symbol::new("X1-ZA40-15970B").unwrap(),
+            )
+            .await?;
+        assert_eq!(waypoint.symbol, "X1-ZA40-15970B");
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_stores_markets_with_prices() -> STResult<()> {
+        let cache = GalaxyCache::open_in_memory()?;
+        let mut market: Market = serde_json::from_value(serde_json::json!({
+            "symbol": "X1-ZA40-15970B",
+            "exports": [],
+            "imports": [],
+            "exchange": [],
+        }))?;
+
+        cache.store_market(&market)?;
+        assert!(cache.market("X1-ZA40-15970B")?.is_none());
+
+        market.trade_goods = Some(vec![]);
+        cache.store_market(&market)?;
+        assert!(cache.market("X1-ZA40-15970B")?.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ignores_stale_entries() -> STResult<()> {
+        let mut cache = GalaxyCache::open_in_memory()?;
+        let waypoint: Waypoint = serde_json::from_str(WAYPOINT)?;
+
+        cache.store_waypoint_at(&waypoint, Utc::now() - Duration::hours(2))?;
+        assert!(cache.waypoint("X1-ZA40-15970B")?.is_some());
+
+        cache.set_max_age(CachedData::Waypoints, Some(Duration::hours(1)));
+        assert!(cache.waypoint("X1-ZA40-15970B")?.is_none());
+
+        cache.clear(CachedData::Waypoints)?;
+        assert!(cache
+            .fetched_at(CachedData::Waypoints, "X1-ZA40-15970B")?
+            .is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+//! An append-only file of JSON values, one per line, shared by the file-backed stores.
+
+use crate::STResult;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+/// A file that values are appended to in the JSON Lines format.
+#[derive(Debug)]
+pub(crate) struct JsonLinesFile {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl JsonLinesFile {
+    /// Opens (or creates) the file at the given path, and reads the values already in it.
+    ///
+    /// Blank lines are skipped.
+    pub(crate) fn open<T: DeserializeOwned>(path: impl AsRef<Path>) -> STResult<(Self, Vec<T>)> {
+        let path = path.as_ref().to_path_buf();
+
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+
+        let mut values = Vec::new();
+        for line in BufReader::new(&file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                values.push(serde_json::from_str(&line)?);
+            }
+        }
+
+        Ok((
+            Self {
+                path,
+                file: Mutex::new(file),
+            },
+            values,
+        ))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the values to the end of the file in a single write, so concurrent appends don't
+    /// interleave.
+    pub(crate) fn append<'a, T: Serialize + 'a>(
+        &self,
+        values: impl IntoIterator<Item = &'a T>,
+    ) -> STResult<()> {
+        let mut lines = String::new();
+        for value in values {
+            lines.push_str(&serde_json::to_string(value)?);
+            lines.push('\n');
+        }
+
+        self.file
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_all(lines.as_bytes())?;
+
+        Ok(())
+    }
+}
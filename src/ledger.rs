@@ -0,0 +1,431 @@
+//! Provides a record of every action that changed the agent's credits.
+//!
+//! When a [Ledger] is attached to a [SpaceTradersClient], every ship purchase, market trade,
+//! refuel and contract payment made through the client is recorded in it, along with the agent's
+//! balance afterwards. The ledger can then report the profit and loss over a time window, per ship
+//! or per activity. If an entry can't be recorded, the action still succeeds and a
+//! [ClientEvent::LedgerFailed] is emitted instead.
+//!
+//! Repairs aren't recorded, as the client has no endpoint for repairing ships.
+//!
+//! # Example
+//! ```
+//! # use space_traders::prelude::*;
+//! let mut client = SpaceTradersClient::new();
+//!
+//! // Entries are appended to `ledger.jsonl` so they persist between runs.
+//! let path = std::env::temp_dir().join("ledger.jsonl");
+//! client.set_ledger(FileLedger::open(&path).unwrap());
+//!
+//! let report = client.ledger().unwrap().report(None, None).unwrap();
+//! assert_eq!(report.profit(), 0);
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use crate::{
+    conditional_types::Symbol,
+    events::ClientEvent,
+    json_lines::JsonLinesFile,
+    market::{MarketTransaction, TransactionType},
+    space_traders_client::SpaceTradersClient,
+    STResult,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::Path,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// The kind of action that changed the agent's credits.
+#[derive(Deserialize, Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Activity {
+    ShipPurchase,
+    /// Buying goods at a market.
+    MarketPurchase,
+    /// Selling goods at a market.
+    MarketSale,
+    Refuel,
+    /// The payment received when accepting a contract.
+    ContractAccepted,
+    /// The payment received when fulfilling a contract.
+    ContractFulfilled,
+}
+
+/// A single change to the agent's credits.
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+    pub timestamp: DateTime<Utc>,
+    pub activity: Activity,
+    /// The ship involved, if there was one.
+    pub ship_symbol: Option<Symbol>,
+    /// Who the credits were exchanged with: the waypoint of a market or shipyard, or the ID of a
+    /// contract.
+    pub counterpart: String,
+    /// The good traded, if there was one.
+    pub trade_symbol: Option<Symbol>,
+    pub units: Option<i64>,
+    /// The change in credits: positive for income, negative for expenses.
+    pub amount: i64,
+    /// The agent's credits after the change.
+    pub balance: i64,
+}
+
+impl LedgerEntry {
+    /// Creates an entry for a trade or refuel at a market.
+    pub(crate) fn from_market_transaction(
+        activity: Activity,
+        transaction: &MarketTransaction,
+        balance: i64,
+    ) -> Self {
+        let total = *transaction.total_price;
+
+        Self {
+            timestamp: transaction.timestamp,
+            activity,
+            ship_symbol: Some(transaction.ship_symbol.clone()),
+            counterpart: transaction.waypoint_symbol.to_string(),
+            trade_symbol: Some(transaction.trade_symbol.clone()),
+            units: Some(*transaction.units),
+            amount: match transaction.transaction_type {
+                TransactionType::Purchase => -total,
+                TransactionType::Sell => total,
+            },
+            balance,
+        }
+    }
+}
+
+/// Income and expenses over a set of [LedgerEntry]s.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProfitAndLoss {
+    /// The total credits received.
+    pub income: i64,
+    /// The total credits spent, as a positive number.
+    pub expenses: i64,
+}
+
+impl ProfitAndLoss {
+    pub fn profit(&self) -> i64 {
+        self.income - self.expenses
+    }
+
+    fn add(&mut self, entry: &LedgerEntry) {
+        if entry.amount >= 0 {
+            self.income += entry.amount;
+        } else {
+            self.expenses -= entry.amount;
+        }
+    }
+}
+
+/// A store for [LedgerEntry]s.
+///
+/// Implementors only need to provide a way to record and retrieve entries; the reports are built
+/// on top of [entries](Ledger::entries).
+pub trait Ledger: Debug + Send + Sync {
+    /// Records the given entry.
+    fn record(&self, entry: &LedgerEntry) -> STResult<()>;
+
+    /// Get every recorded entry, oldest first.
+    ///
+    /// If `since` is specified, only entries at or after that time are returned. If `until` is
+    /// specified, only entries before that time are returned.
+    fn entries(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> STResult<Vec<LedgerEntry>>;
+
+    /// Get the profit and loss of every entry in the time window.
+    fn report(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> STResult<ProfitAndLoss> {
+        let mut report = ProfitAndLoss::default();
+        for entry in self.entries(since, until)? {
+            report.add(&entry);
+        }
+
+        Ok(report)
+    }
+
+    /// Get the profit and loss of each ship in the time window.
+    ///
+    /// Entries without a ship (e.g. contract payments) aren't included.
+    fn report_by_ship(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> STResult<HashMap<Symbol, ProfitAndLoss>> {
+        let mut reports: HashMap<Symbol, ProfitAndLoss> = HashMap::new();
+        for entry in self.entries(since, until)? {
+            if let Some(ship_symbol) = &entry.ship_symbol {
+                reports.entry(ship_symbol.clone()).or_default().add(&entry);
+            }
+        }
+
+        Ok(reports)
+    }
+
+    /// Get the profit and loss of each activity in the time window.
+    fn report_by_activity(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> STResult<HashMap<Activity, ProfitAndLoss>> {
+        let mut reports: HashMap<Activity, ProfitAndLoss> = HashMap::new();
+        for entry in self.entries(since, until)? {
+            reports.entry(entry.activity).or_default().add(&entry);
+        }
+
+        Ok(reports)
+    }
+}
+
+/// A [Ledger] that only keeps entries in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryLedger {
+    entries: RwLock<Vec<LedgerEntry>>,
+}
+
+impl InMemoryLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Ledger for InMemoryLedger {
+    fn record(&self, entry: &LedgerEntry) -> STResult<()> {
+        self.entries
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(entry.clone());
+
+        Ok(())
+    }
+
+    fn entries(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> STResult<Vec<LedgerEntry>> {
+        let entries = self.entries.read().unwrap_or_else(PoisonError::into_inner);
+
+        let mut entries: Vec<LedgerEntry> = entries
+            .iter()
+            .filter(|entry| {
+                since.is_none_or(|since| entry.timestamp >= since)
+                    && until.is_none_or(|until| entry.timestamp < until)
+            })
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| entry.timestamp);
+
+        Ok(entries)
+    }
+}
+
+/// A [Ledger] kept in a file, so entries persist between runs.
+///
+/// Each entry is appended to the file as a line of JSON when it's recorded. The whole ledger is
+/// read when the file is opened, and reports are made from the copy kept in memory.
+#[derive(Debug)]
+pub struct FileLedger {
+    file: JsonLinesFile,
+    entries: InMemoryLedger,
+}
+
+impl FileLedger {
+    /// Opens (or creates) the ledger file at the given path.
+    pub fn open(path: impl AsRef<Path>) -> STResult<Self> {
+        let (file, entries) = JsonLinesFile::open(path)?;
+
+        Ok(Self {
+            file,
+            entries: InMemoryLedger {
+                entries: RwLock::new(entries),
+            },
+        })
+    }
+
+    /// The path of the underlying ledger file.
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+}
+
+impl Ledger for FileLedger {
+    fn record(&self, entry: &LedgerEntry) -> STResult<()> {
+        self.file.append([entry])?;
+
+        self.entries.record(entry)
+    }
+
+    fn entries(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> STResult<Vec<LedgerEntry>> {
+        self.entries.entries(since, until)
+    }
+}
+
+impl SpaceTradersClient {
+    /// Attach a [Ledger] to the client.
+    ///
+    /// Every credit-affecting action made by subsequent calls will be recorded in the ledger. The
+    /// ledger is shared with clones made after this call.
+    pub fn set_ledger(&mut self, ledger: impl Ledger + 'static) {
+        self.ledger = Some(Arc::new(ledger));
+    }
+
+    /// Get a reference to the [Ledger] attached to the client, if there is one.
+    pub fn ledger(&self) -> Option<&dyn Ledger> {
+        self.ledger.as_deref()
+    }
+
+    /// Records the entry in the attached [Ledger], and contract payments and costs in the attached
+    /// [Metrics](crate::metrics::Metrics).
+    ///
+    /// The action being recorded has already succeeded, so a failure to record it is reported as
+    /// a [ClientEvent::LedgerFailed] instead of being returned.
+    pub(crate) fn record_ledger(&self, entry: LedgerEntry) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            if let Activity::ContractAccepted | Activity::ContractFulfilled = entry.activity {
+                metrics.record_contract_payment(&entry.counterpart, entry.amount);
+            }
+            metrics.record_ledger_entry(&entry);
+        }
+
+        if let Some(ledger) = &self.ledger {
+            if let Err(err) = ledger.record(&entry) {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %err, "failed to record ledger entry");
+                self.emit(ClientEvent::LedgerFailed {
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Get the agent's current credits, to record as the balance of a [LedgerEntry].
+    pub(crate) fn balance(&self) -> STResult<i64> {
+        Ok(self.agent()?.credits as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn entry(activity: Activity, ship: Option<&str>, amount: i64, age: i64) -> LedgerEntry {
+        LedgerEntry {
+            timestamp: Utc::now() - Duration::hours(age),
+            activity,
+            ship_symbol: ship.map(|ship| Symbol::new(ship).unwrap()),
+            counterpart: "X1-ZA40-15970B".into(),
+            trade_symbol: None,
+            units: None,
+            amount,
+            balance: 0,
+        }
+    }
+
+    fn check_reports(ledger: &dyn Ledger) -> STResult<()> {
+        ledger.record(&entry(Activity::ShipPurchase, Some("TST-1"), -80000, 5))?;
+        ledger.record(&entry(Activity::ContractAccepted, None, 20000, 4))?;
+        ledger.record(&entry(Activity::Refuel, Some("TST-1"), -200, 3))?;
+        ledger.record(&entry(Activity::MarketSale, Some("TST-1"), 3000, 2))?;
+        ledger.record(&entry(Activity::MarketSale, Some("TST-2"), 1500, 1))?;
+
+        let report = ledger.report(None, None)?;
+        assert_eq!(report.income, 24500);
+        assert_eq!(report.expenses, 80200);
+        assert_eq!(report.profit(), -55700);
+
+        // Only the last 3.5 hours
+        let since = Utc::now() - Duration::minutes(210);
+        let report = ledger.report(Some(since), None)?;
+        assert_eq!(report.profit(), 4300);
+
+        let by_ship = ledger.report_by_ship(Some(since), None)?;
+        assert_eq!(by_ship[&Symbol::new("TST-1").unwrap()].profit(), 2800);
+        assert_eq!(by_ship[&Symbol::new("TST-2").unwrap()].profit(), 1500);
+
+        let by_activity = ledger.report_by_activity(None, Some(since))?;
+        assert_eq!(by_activity.len(), 2);
+        assert_eq!(by_activity[&Activity::ShipPurchase].expenses, 80000);
+        assert_eq!(by_activity[&Activity::ContractAccepted].income, 20000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_report_from_in_memory_ledger() -> STResult<()> {
+        check_reports(&InMemoryLedger::new())
+    }
+
+    #[test]
+    fn can_report_from_file_ledger() -> STResult<()> {
+        let path = std::env::temp_dir().join(format!("ledger-{}.jsonl", uuid::Uuid::new_v4()));
+
+        check_reports(&FileLedger::open(&path)?)?;
+
+        // Entries are loaded back from the file
+        let reopened = FileLedger::open(&path)?;
+        assert_eq!(reopened.entries(None, None)?.len(), 5);
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// A ledger that can't record anything, like a file ledger on a full disk.
+    #[cfg(feature = "mock")]
+    #[derive(Debug)]
+    struct BrokenLedger;
+
+    #[cfg(feature = "mock")]
+    impl Ledger for BrokenLedger {
+        fn record(&self, _: &LedgerEntry) -> STResult<()> {
+            Err(std::io::Error::other("disk full").into())
+        }
+
+        fn entries(
+            &self,
+            _: Option<DateTime<Utc>>,
+            _: Option<DateTime<Utc>>,
+        ) -> STResult<Vec<LedgerEntry>> {
+            Ok(vec![])
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn reports_failures_without_failing_the_action() -> STResult<()> {
+        let server = crate::mock::MockServer::start().await?;
+        let mut client = server.client();
+        client.register_callsign("LEDGER", None).await?;
+        client.set_ledger(BrokenLedger);
+        let mut events = client.subscribe();
+
+        let ship = Symbol::new("LEDGER-1").unwrap();
+        client
+            .purchase_cargo(&ship, &Symbol::new("FOOD").unwrap(), 1)
+            .await?;
+
+        let failures = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| matches!(event, ClientEvent::LedgerFailed { .. }))
+            .count();
+        assert_eq!(failures, 1);
+
+        Ok(())
+    }
+}
@@ -7,24 +7,95 @@ use std::fmt::Display;
 
 use meta::Meta;
 
+mod auto_prepare;
 mod contract;
 mod faction;
+mod market;
 mod meta;
 mod ship;
+mod system;
 mod waypoint;
 
 pub mod agent;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "cassette")]
+pub mod cassette;
+#[cfg(feature = "cli")]
+pub mod cli;
 pub mod conditional_types;
+pub mod contract_planner;
+#[cfg(feature = "control")]
+pub mod control;
+#[cfg(feature = "tui")]
+pub mod dashboard;
+pub mod events;
+#[cfg(test)]
+mod fixtures;
+pub mod fleet;
+#[cfg(feature = "sqlite")]
+pub mod galaxy_cache;
+mod json_lines;
+pub mod ledger;
+pub mod market_history;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod mining;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod rate_limiter;
+pub mod route_planner;
+pub mod scheduler;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod secrets;
+pub mod ship_handle;
+#[cfg(feature = "simulator")]
+pub mod simulator;
 pub mod space_traders_client;
+pub mod trade_routes;
+pub mod transport;
 
 pub mod prelude {
     //! Provides common structs and functions.
 
     pub use crate::agent::*;
-    pub use crate::conditional_types::ints::*;
+    #[cfg(feature = "blocking")]
+    pub use crate::blocking::*;
+    #[cfg(feature = "cassette")]
+    pub use crate::cassette::*;
+    #[cfg(feature = "cli")]
+    pub use crate::cli::*;
     pub use crate::conditional_types::strings::*;
     pub use crate::conditional_types::*;
+    pub use crate::contract_planner::*;
+    #[cfg(feature = "control")]
+    pub use crate::control::*;
+    #[cfg(feature = "tui")]
+    pub use crate::dashboard::*;
+    pub use crate::events::*;
+    pub use crate::fleet::*;
+    #[cfg(feature = "sqlite")]
+    pub use crate::galaxy_cache::*;
+    pub use crate::ledger::*;
+    pub use crate::market_history::*;
+    #[cfg(feature = "metrics")]
+    pub use crate::metrics::*;
+    pub use crate::mining::*;
+    #[cfg(feature = "mock")]
+    pub use crate::mock::*;
+    pub use crate::rate_limiter::*;
+    pub use crate::route_planner::*;
+    pub use crate::scheduler::*;
+    #[cfg(feature = "scripting")]
+    pub use crate::scripting::*;
+    pub use crate::secrets::*;
+    pub use crate::ship_handle::*;
+    #[cfg(feature = "simulator")]
+    pub use crate::simulator::*;
     pub use crate::space_traders_client::*;
+    pub use crate::trade_routes::*;
+    pub use crate::transport::*;
 }
 
 /// Represents all possible errors for the [SpaceTradersClient](space_traders_client::SpaceTradersClient).
@@ -56,8 +127,17 @@ pub enum SpaceTradersError {
     SerdeJsonError(#[from] serde_json::Error),
 
     /// The client data was not populated correctly.
-    #[error("EmptyCache: The cache should not be empty if the client was initalized properly\ndata: {:?}", 0)]
-    EmptyCache(Option<String>),
+    #[error(
+        "EmptyCache: The cache should not be empty if the client was initalized properly{}{}",
+        operation_suffix(.operation),
+        .data.as_ref().map(|data| format!("\ndata: {data}")).unwrap_or_default()
+    )]
+    EmptyCache {
+        /// The client method that needed the cache, e.g. `starting_system`.
+        operation: Option<&'static str>,
+        /// Data that was returned by the API but couldn't be cached.
+        data: Option<String>,
+    },
 
     /// The format of the savefile was incorrect, or the savefile is corrupted.
     #[error("InvalidSave: There was an error reading the savefile: {0}")]
@@ -70,19 +150,185 @@ pub enum SpaceTradersError {
     #[error("UrlParseError: There was an error with parsing the URL: {0}")]
     UrlParseError(String),
 
-    #[error("The contract ID `{0}` does not exist in the current client")]
-    InvalidContractId(String),
+    #[error(
+        "The contract ID `{contract_id}` does not exist in the current client{}",
+        operation_suffix(.operation)
+    )]
+    InvalidContractId {
+        contract_id: String,
+        /// The method that looked up the contract, e.g. `deliver_contract`.
+        operation: Option<&'static str>,
+    },
 
-    #[error("The ship `{0}` does not exist in the current client")]
-    InvalidShipSymbol(String),
+    #[error(
+        "The ship `{ship_symbol}` does not exist in the current client{}",
+        operation_suffix(.operation)
+    )]
+    InvalidShipSymbol {
+        ship_symbol: String,
+        /// The method that looked up the ship, e.g. `orbit_ship`.
+        operation: Option<&'static str>,
+    },
+
+    /// The waypoint symbol doesn't contain the symbol of its system, e.g. `X1-ZA40` in `X1-ZA40-99095A`.
+    #[error("The waypoint symbol `{0}` is not of the form `SECTOR-SYSTEM-WAYPOINT`")]
+    InvalidWaypointSymbol(String),
+
+    /// The deadline of the contract passed before it was fulfilled.
+    #[error("The deadline of the contract `{0}` has passed")]
+    ContractDeadlinePassed(String),
+
+    /// Tried executing a contract plan with goods that can't be bought or mined.
+    #[error("The contract `{0}` can't be completed: some of its goods have no source")]
+    ContractInfeasible(String),
+
+    /// Tried querying market data without a [MarketStore](market_history::MarketStore).
+    #[error("MarketStore must be set first: use `set_market_store` to attach one.")]
+    MarketStoreNotSet,
+
+    /// A [SecretStore](secrets::SecretStore) couldn't load or store the token.
+    #[error("SecretStoreError: {0}")]
+    SecretStoreError(String),
+
+    /// A save couldn't be encrypted or decrypted, e.g. because the key is wrong.
+    #[cfg(feature = "encryption")]
+    #[error("EncryptionError: {0}")]
+    EncryptionError(String),
+
+    /// A script failed to compile or run.
+    #[cfg(feature = "scripting")]
+    #[error("ScriptError: {0}")]
+    ScriptError(String),
+
+    /// Errors from the [GalaxyCache](galaxy_cache::GalaxyCache) database.
+    #[cfg(feature = "sqlite")]
+    #[error("SqliteError: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+}
+
+/// Describes the operation an error occurred in, for the end of its message.
+fn operation_suffix(operation: &Option<&'static str>) -> String {
+    operation
+        .map(|operation| format!(" (in `{operation}`)"))
+        .unwrap_or_default()
+}
+
+impl SpaceTradersError {
+    /// Records the operation a cache error occurred in, unless it already has one.
+    ///
+    /// Other errors are returned unchanged.
+    pub(crate) fn in_operation(mut self, name: &'static str) -> Self {
+        match &mut self {
+            Self::EmptyCache { operation, .. }
+            | Self::InvalidContractId { operation, .. }
+            | Self::InvalidShipSymbol { operation, .. } => {
+                operation.get_or_insert(name);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Whether the API rejected the request because the rate limit was exceeded.
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, Self::ResponseError(error) if error.status() == Some(429) || error.code == 429)
+    }
+
+    /// Whether the ship, contract or other resource the request was for doesn't exist, either in
+    /// the API or in the client's cache.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Self::ResponseError(error) => error.status() == Some(404) || error.code == 404,
+            Self::InvalidShipSymbol { .. } | Self::InvalidContractId { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the same request may succeed if it's sent again later, e.g. after being rate
+    /// limited, a server error, or a timeout.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::ReqwestError(error) => error.is_timeout() || error.is_connect(),
+            Self::ResponseError(error) => {
+                self.is_rate_limited() || error.status().is_some_and(|status| status >= 500)
+            }
+            _ => false,
+        }
+    }
 }
 
+/// An error returned by the SpaceTraders API.
+///
+/// Errors returned for a request of the client also contain the request, e.g. the
+/// [path](Self::path) `/my/ships/TST-RS-04-1/orbit` for orbiting a ship.
 #[derive(serde::Deserialize, Debug, thiserror::Error)]
 #[serde(rename_all = "camelCase")]
 pub struct ResponseError {
     code: i32,
     message: String,
     data: Option<serde_json::Value>,
+    #[serde(skip)]
+    request: Option<RequestInfo>,
+}
+
+/// The request that an API error was returned for.
+#[derive(Debug, Clone)]
+pub(crate) struct RequestInfo {
+    pub(crate) method: reqwest::Method,
+    /// The path relative to the base URL, without the query.
+    pub(crate) path: String,
+    pub(crate) status: u16,
+}
+
+impl ResponseError {
+    /// The API's error code, e.g. `4000` if the ship is on cooldown.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Details of the error, e.g. the remaining cooldown.
+    pub fn data(&self) -> Option<&serde_json::Value> {
+        self.data.as_ref()
+    }
+
+    /// The HTTP status of the response.
+    pub fn status(&self) -> Option<u16> {
+        Some(self.request.as_ref()?.status)
+    }
+
+    /// The method and endpoint of the request, e.g. `POST /my/ships/{shipSymbol}/orbit`.
+    pub fn operation(&self) -> Option<String> {
+        let request = self.request.as_ref()?;
+        Some(format!(
+            "{} {}",
+            request.method,
+            transport::endpoint_label(&request.path)
+        ))
+    }
+
+    /// The path of the request relative to the base URL, e.g. `/my/ships/TST-RS-04-1/orbit`.
+    pub fn path(&self) -> Option<&str> {
+        Some(&self.request.as_ref()?.path)
+    }
+
+    /// The symbol of the ship the request was for.
+    pub fn ship_symbol(&self) -> Option<&str> {
+        transport::segment_after(self.path()?, "ships")
+    }
+
+    /// The ID of the contract the request was for.
+    pub fn contract_id(&self) -> Option<&str> {
+        transport::segment_after(self.path()?, "contracts")
+    }
+
+    pub(crate) fn with_request(mut self, request: RequestInfo) -> Self {
+        self.request = Some(request);
+        self
+    }
 }
 
 impl Display for ResponseError {
@@ -90,7 +336,16 @@ impl Display for ResponseError {
         f.write_fmt(format_args!(
             "ResponseError {{ code: {}, message: {}, data: {:?} }}",
             self.code, self.message, self.data
-        ))
+        ))?;
+
+        match &self.request {
+            Some(request) => write!(
+                f,
+                " for {} {} ({})",
+                request.method, request.path, request.status
+            ),
+            None => Ok(()),
+        }
     }
 }
 
@@ -0,0 +1,266 @@
+use crate::{
+    agent::Agent,
+    auto_prepare::Preparation,
+    conditional_types::ints::NonNegative,
+    conditional_types::strings::{Description, Name, Symbol},
+    ledger::{Activity, LedgerEntry},
+    ship::Cargo,
+    space_traders_client::SpaceTradersClient,
+    ResponseData, STResult, SpaceTradersError,
+};
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION};
+use serde::{Deserialize, Serialize};
+
+/// Represents a `Market` in the API.
+///
+/// **NOTE:** `transactions` and `trade_goods` are only populated if a ship is present at the
+/// market's waypoint.
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Market {
+    pub(crate) symbol: Symbol,
+    pub(crate) exports: Vec<TradeGood>,
+    pub(crate) imports: Vec<TradeGood>,
+    pub(crate) exchange: Vec<TradeGood>,
+    pub(crate) transactions: Option<Vec<MarketTransaction>>,
+    pub(crate) trade_goods: Option<Vec<MarketTradeGood>>,
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TradeGood {
+    pub(crate) symbol: Symbol,
+    pub(crate) name: Name,
+    pub(crate) description: Description,
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MarketTradeGood {
+    pub(crate) symbol: Symbol,
+    pub(crate) trade_volume: NonNegative,
+    pub(crate) supply: SupplyLevel,
+    pub(crate) purchase_price: NonNegative,
+    pub(crate) sell_price: NonNegative,
+}
+
+#[derive(Deserialize, Debug, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SupplyLevel {
+    Scarce,
+    Limited,
+    Moderate,
+    Abundant,
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketTransaction {
+    pub(crate) waypoint_symbol: Symbol,
+    pub(crate) ship_symbol: Symbol,
+    pub(crate) trade_symbol: Symbol,
+    #[serde(rename = "type")]
+    pub(crate) transaction_type: TransactionType,
+    pub(crate) units: NonNegative,
+    pub(crate) price_per_unit: NonNegative,
+    pub(crate) total_price: NonNegative,
+    pub(crate) timestamp: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TransactionType {
+    Purchase,
+    Sell,
+}
+
+/// The API's error code for trading a good the market doesn't trade.
+pub(crate) const NOT_TRADED: i32 = 4601;
+
+impl Market {
+    /// The most units of the good that can be traded in a single transaction.
+    ///
+    /// Returns `Ok(None)` if the limit is unknown because no ship is at the market, and an error
+    /// if the market doesn't trade the good.
+    pub(crate) fn trade_volume(&self, trade_symbol: &Symbol) -> Result<Option<i64>, ()> {
+        match &self.trade_goods {
+            Some(goods) => goods
+                .iter()
+                .find(|good| good.symbol == *trade_symbol)
+                .map(|good| Some(*good.trade_volume))
+                .ok_or(()),
+            None => Ok(None),
+        }
+    }
+}
+
+impl SpaceTradersError {
+    /// Whether the market rejected the trade because it doesn't trade the good.
+    pub(crate) fn is_not_traded(&self) -> bool {
+        matches!(self, Self::ResponseError(error) if error.code() == NOT_TRADED)
+    }
+}
+
+impl SpaceTradersClient {
+    /// Get info on the market at the specified waypoint.
+    pub async fn view_market(
+        &self,
+        system_symbol: &Symbol,
+        waypoint_symbol: &Symbol,
+    ) -> STResult<Market> {
+        #[cfg(feature = "sqlite")]
+        if let Some(market) = self.cached_market(waypoint_symbol)? {
+            return Ok(market);
+        }
+
+        let url = format!(
+            "{}/systems/{}/waypoints/{}/market",
+            self.base_url, system_symbol, waypoint_symbol
+        );
+
+        let mut headers = HeaderMap::with_capacity(2);
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", self.token()?))?,
+        );
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+
+        // Send request
+        self.rate_limiter.acquire().await;
+        let res = self.get(url).headers(headers).send().await?;
+
+        match res.json::<ResponseData<Market>>()? {
+            ResponseData::Data { data } => {
+                self.record_market(system_symbol, &data)?;
+
+                #[cfg(feature = "sqlite")]
+                if let Some(cache) = &self.galaxy_cache {
+                    cache.store_market(&data)?;
+                }
+
+                Ok(data)
+            }
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
+        }
+    }
+
+    /// Sell cargo from the ship at the market it is docked at.
+    pub async fn sell_cargo(
+        &self,
+        ship_symbol: &Symbol,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<MarketTransaction> {
+        // Check if ship_symbol exists first
+        self.get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("sell_cargo"))?;
+        self.prepare_ship(ship_symbol, Preparation::Dock).await?;
+
+        let url = format!("{}/my/ships/{}/sell", self.base_url, ship_symbol);
+
+        // Send request
+        self.rate_limiter.acquire().await;
+        let res = self
+            .post(url)
+            .headers(self.auth_headers()?)
+            .json(&serde_json::json!({ "symbol": trade_symbol, "units": units }))
+            .send()
+            .await?;
+
+        #[derive(Debug, Deserialize, Serialize)]
+        struct SellCargoResponse {
+            agent: Agent,
+            cargo: Cargo,
+            transaction: MarketTransaction,
+        }
+
+        match res.json::<ResponseData<SellCargoResponse>>()? {
+            ResponseData::Data { data } => {
+                self.set_agent(data.agent)?;
+                self.update_ship(ship_symbol, |ship| ship.cargo = data.cargo)?;
+                self.record_ledger(LedgerEntry::from_market_transaction(
+                    Activity::MarketSale,
+                    &data.transaction,
+                    self.balance()?,
+                ));
+
+                Ok(data.transaction)
+            }
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
+        }
+    }
+
+    /// Sells cargo at the given market (which the ship must be at), at most the market's trade
+    /// volume at a time.
+    ///
+    /// Returns `None` if the market doesn't trade the good.
+    pub(crate) async fn sell_at_market(
+        &self,
+        market: &Market,
+        ship_symbol: &Symbol,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<Option<MarketTransaction>> {
+        let units = match market.trade_volume(trade_symbol) {
+            Ok(Some(trade_volume)) => units.min(trade_volume as i32),
+            Ok(None) => units,
+            Err(()) => return Ok(None),
+        };
+
+        match self.sell_cargo(ship_symbol, trade_symbol, units).await {
+            Ok(transaction) => Ok(Some(transaction)),
+            Err(err) if err.is_not_traded() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Purchase cargo for the ship at the market it is docked at.
+    pub async fn purchase_cargo(
+        &self,
+        ship_symbol: &Symbol,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<MarketTransaction> {
+        // Check if ship_symbol exists first
+        self.get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("purchase_cargo"))?;
+        self.prepare_ship(ship_symbol, Preparation::Dock).await?;
+
+        let url = format!("{}/my/ships/{}/purchase", self.base_url, ship_symbol);
+
+        // Send request
+        self.rate_limiter.acquire().await;
+        let res = self
+            .post(url)
+            .headers(self.auth_headers()?)
+            .json(&serde_json::json!({ "symbol": trade_symbol, "units": units }))
+            .send()
+            .await?;
+
+        #[derive(Debug, Deserialize, Serialize)]
+        struct PurchaseCargoResponse {
+            agent: Agent,
+            cargo: Cargo,
+            transaction: MarketTransaction,
+        }
+
+        match res.json::<ResponseData<PurchaseCargoResponse>>()? {
+            ResponseData::Data { data } => {
+                self.set_agent(data.agent)?;
+                self.update_ship(ship_symbol, |ship| ship.cargo = data.cargo)?;
+                self.record_ledger(LedgerEntry::from_market_transaction(
+                    Activity::MarketPurchase,
+                    &data.transaction,
+                    self.balance()?,
+                ));
+
+                Ok(data.transaction)
+            }
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
+        }
+    }
+}
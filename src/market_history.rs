@@ -0,0 +1,590 @@
+//! Provides a record of every observed trade good price.
+//!
+//! When a [MarketStore] is attached to a [SpaceTradersClient], every call to `view_market` that
+//! returns trade goods (i.e. a ship is present at the market) is recorded in the store. The store
+//! can then be queried for the latest prices in a system, the price of a good over time, and the
+//! best places to buy or sell a good.
+//!
+//! # Example
+//! ```
+//! # use space_traders::prelude::*;
+//! let mut client = SpaceTradersClient::new();
+//!
+//! // Prices are appended to `prices.jsonl` so they persist between runs.
+//! let path = std::env::temp_dir().join("prices.jsonl");
+//! client.set_market_store(FileMarketStore::open(&path).unwrap());
+//!
+//! let best = client
+//!     .market_store()
+//!     .unwrap()
+//!     .best_sell_location("X1-ZA40", "IRON_ORE")
+//!     .unwrap();
+//! assert!(best.is_none());
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use crate::{
+    conditional_types::Symbol, json_lines::JsonLinesFile, market::Market,
+    space_traders_client::SpaceTradersClient, STResult,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::Path,
+    sync::{PoisonError, RwLock},
+};
+
+pub use crate::market::SupplyLevel;
+
+/// A single observed price of a trade good at a market.
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceRecord {
+    pub waypoint_symbol: Symbol,
+    pub system_symbol: Symbol,
+    pub trade_symbol: Symbol,
+    /// The price to buy one unit of the good at the market.
+    pub purchase_price: i64,
+    /// The price the market pays for one unit of the good.
+    pub sell_price: i64,
+    pub supply: SupplyLevel,
+    pub trade_volume: i64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl PriceRecord {
+    /// Creates a record for every trade good listed in the market.
+    ///
+    /// Returns an empty list if the market's trade goods aren't visible.
+    pub(crate) fn from_market(
+        system_symbol: &Symbol,
+        market: &Market,
+        timestamp: DateTime<Utc>,
+    ) -> Vec<Self> {
+        market
+            .trade_goods
+            .iter()
+            .flatten()
+            .map(|good| Self {
+                waypoint_symbol: market.symbol.clone(),
+                system_symbol: system_symbol.clone(),
+                trade_symbol: good.symbol.clone(),
+                purchase_price: *good.purchase_price,
+                sell_price: *good.sell_price,
+                supply: good.supply,
+                trade_volume: *good.trade_volume,
+                timestamp,
+            })
+            .collect()
+    }
+}
+
+/// A store for observed trade good prices.
+///
+/// Implementors only need to provide a way to record prices and retrieve them; the queries for
+/// the best buy/sell locations are built on top of [latest_prices](MarketStore::latest_prices).
+pub trait MarketStore: Debug + Send + Sync {
+    /// Records the given prices.
+    fn record(&self, records: &[PriceRecord]) -> STResult<()>;
+
+    /// Get the most recent price of every good at every market in the given system.
+    fn latest_prices(&self, system_symbol: &str) -> STResult<Vec<PriceRecord>>;
+
+    /// Get every recorded price of a good at a market, oldest first.
+    ///
+    /// If `since` is specified, only prices observed at or after that time are returned.
+    fn price_history(
+        &self,
+        waypoint_symbol: &str,
+        trade_symbol: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> STResult<Vec<PriceRecord>>;
+
+    /// Get the latest price of the given good at the given market.
+    fn latest_price(
+        &self,
+        waypoint_symbol: &str,
+        trade_symbol: &str,
+    ) -> STResult<Option<PriceRecord>> {
+        Ok(self
+            .price_history(waypoint_symbol, trade_symbol, None)?
+            .pop())
+    }
+
+    /// Get the market in the system with the lowest (latest) purchase price for the given good.
+    fn best_buy_location(
+        &self,
+        system_symbol: &str,
+        trade_symbol: &str,
+    ) -> STResult<Option<PriceRecord>> {
+        Ok(self
+            .latest_prices(system_symbol)?
+            .into_iter()
+            .filter(|record| record.trade_symbol == trade_symbol)
+            .min_by_key(|record| record.purchase_price))
+    }
+
+    /// Get the market in the system with the highest (latest) sell price for the given good.
+    fn best_sell_location(
+        &self,
+        system_symbol: &str,
+        trade_symbol: &str,
+    ) -> STResult<Option<PriceRecord>> {
+        Ok(self
+            .latest_prices(system_symbol)?
+            .into_iter()
+            .filter(|record| record.trade_symbol == trade_symbol)
+            .max_by_key(|record| record.sell_price))
+    }
+}
+
+/// Keeps only the most recent record for each good at each market.
+fn latest_of<'a>(records: impl Iterator<Item = &'a PriceRecord>) -> Vec<PriceRecord> {
+    let mut latest: HashMap<(&Symbol, &Symbol), &PriceRecord> = HashMap::new();
+    for record in records {
+        let key = (&record.waypoint_symbol, &record.trade_symbol);
+        match latest.get(&key) {
+            Some(current) if current.timestamp > record.timestamp => {}
+            _ => {
+                latest.insert(key, record);
+            }
+        }
+    }
+
+    let mut latest: Vec<PriceRecord> = latest.into_values().cloned().collect();
+    latest.sort_by(|a, b| {
+        (a.waypoint_symbol.as_str(), a.trade_symbol.as_str())
+            .cmp(&(b.waypoint_symbol.as_str(), b.trade_symbol.as_str()))
+    });
+    latest
+}
+
+/// A [MarketStore] that only keeps prices in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryMarketStore {
+    records: RwLock<Vec<PriceRecord>>,
+}
+
+impl InMemoryMarketStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MarketStore for InMemoryMarketStore {
+    fn record(&self, records: &[PriceRecord]) -> STResult<()> {
+        self.records
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(records);
+
+        Ok(())
+    }
+
+    fn latest_prices(&self, system_symbol: &str) -> STResult<Vec<PriceRecord>> {
+        let records = self.records.read().unwrap_or_else(PoisonError::into_inner);
+
+        Ok(latest_of(
+            records
+                .iter()
+                .filter(|record| record.system_symbol == system_symbol),
+        ))
+    }
+
+    fn price_history(
+        &self,
+        waypoint_symbol: &str,
+        trade_symbol: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> STResult<Vec<PriceRecord>> {
+        let records = self.records.read().unwrap_or_else(PoisonError::into_inner);
+
+        let mut history: Vec<PriceRecord> = records
+            .iter()
+            .filter(|record| {
+                record.waypoint_symbol == waypoint_symbol
+                    && record.trade_symbol == trade_symbol
+                    && since.is_none_or(|since| record.timestamp >= since)
+            })
+            .cloned()
+            .collect();
+        history.sort_by_key(|record| record.timestamp);
+
+        Ok(history)
+    }
+}
+
+/// A [MarketStore] backed by a file of every price it has seen.
+///
+/// Each market view appends one line of JSON per trade good. The file is never compacted, so it
+/// grows with every view, and all of it is loaded into memory when opened; the `sqlite` feature's
+/// `SqliteMarketStore` suits long-running agents better.
+#[derive(Debug)]
+pub struct FileMarketStore {
+    file: JsonLinesFile,
+    records: InMemoryMarketStore,
+}
+
+impl FileMarketStore {
+    /// Opens (or creates) the price file at the given path.
+    pub fn open(path: impl AsRef<Path>) -> STResult<Self> {
+        let (file, records) = JsonLinesFile::open(path)?;
+
+        Ok(Self {
+            file,
+            records: InMemoryMarketStore {
+                records: RwLock::new(records),
+            },
+        })
+    }
+
+    /// The path of the underlying price file.
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+}
+
+impl MarketStore for FileMarketStore {
+    fn record(&self, records: &[PriceRecord]) -> STResult<()> {
+        self.file.append(records)?;
+
+        self.records.record(records)
+    }
+
+    fn latest_prices(&self, system_symbol: &str) -> STResult<Vec<PriceRecord>> {
+        self.records.latest_prices(system_symbol)
+    }
+
+    fn price_history(
+        &self,
+        waypoint_symbol: &str,
+        trade_symbol: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> STResult<Vec<PriceRecord>> {
+        self.records
+            .price_history(waypoint_symbol, trade_symbol, since)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteMarketStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::*;
+    use rusqlite::{params, Connection, Row};
+    use std::sync::Mutex;
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS prices (
+            waypoint_symbol TEXT NOT NULL,
+            system_symbol TEXT NOT NULL,
+            trade_symbol TEXT NOT NULL,
+            purchase_price INTEGER NOT NULL,
+            sell_price INTEGER NOT NULL,
+            supply TEXT NOT NULL,
+            trade_volume INTEGER NOT NULL,
+            timestamp TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS prices_by_good
+            ON prices (waypoint_symbol, trade_symbol, timestamp);
+        CREATE INDEX IF NOT EXISTS prices_by_system ON prices (system_symbol);
+    ";
+
+    const COLUMNS: &str = "waypoint_symbol, system_symbol, trade_symbol, purchase_price, \
+                           sell_price, supply, trade_volume, timestamp";
+
+    /// A [MarketStore] backed by a SQLite database.
+    #[derive(Debug)]
+    pub struct SqliteMarketStore {
+        conn: Mutex<Connection>,
+    }
+
+    impl SqliteMarketStore {
+        /// Opens (or creates) the price database at the given path.
+        ///
+        /// This can be the same database used by a [GalaxyCache](crate::galaxy_cache::GalaxyCache).
+        pub fn open(path: impl AsRef<Path>) -> STResult<Self> {
+            Self::with_connection(Connection::open(path)?)
+        }
+
+        /// Creates a price database that only lives in memory.
+        pub fn open_in_memory() -> STResult<Self> {
+            Self::with_connection(Connection::open_in_memory()?)
+        }
+
+        fn with_connection(conn: Connection) -> STResult<Self> {
+            conn.execute_batch(SCHEMA)?;
+
+            Ok(Self {
+                conn: Mutex::new(conn),
+            })
+        }
+
+        fn query(&self, sql: &str, params: &[&dyn rusqlite::ToSql]) -> STResult<Vec<PriceRecord>> {
+            let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+
+            let mut stmt = conn.prepare(sql)?;
+            let rows = stmt.query_map(params, RawRecord::from_row)?;
+
+            let mut records = Vec::new();
+            for row in rows {
+                records.push(row?.into_record()?);
+            }
+
+            Ok(records)
+        }
+    }
+
+    /// A row of the `prices` table, before its symbols are validated.
+    struct RawRecord {
+        waypoint_symbol: String,
+        system_symbol: String,
+        trade_symbol: String,
+        purchase_price: i64,
+        sell_price: i64,
+        supply: String,
+        trade_volume: i64,
+        timestamp: DateTime<Utc>,
+    }
+
+    impl RawRecord {
+        fn from_row(row: &Row) -> rusqlite::Result<Self> {
+            Ok(Self {
+                waypoint_symbol: row.get(0)?,
+                system_symbol: row.get(1)?,
+                trade_symbol: row.get(2)?,
+                purchase_price: row.get(3)?,
+                sell_price: row.get(4)?,
+                supply: row.get(5)?,
+                trade_volume: row.get(6)?,
+                timestamp: row.get(7)?,
+            })
+        }
+
+        fn into_record(self) -> STResult<PriceRecord> {
+            Ok(PriceRecord {
+                waypoint_symbol: serde_json::from_value(self.waypoint_symbol.into())?,
+                system_symbol: serde_json::from_value(self.system_symbol.into())?,
+                trade_symbol: serde_json::from_value(self.trade_symbol.into())?,
+                purchase_price: self.purchase_price,
+                sell_price: self.sell_price,
+                supply: serde_json::from_value(self.supply.into())?,
+                trade_volume: self.trade_volume,
+                timestamp: self.timestamp,
+            })
+        }
+    }
+
+    impl MarketStore for SqliteMarketStore {
+        fn record(&self, records: &[PriceRecord]) -> STResult<()> {
+            let mut conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+            let tx = conn.transaction()?;
+
+            for record in records {
+                tx.execute(
+                    &format!(
+                        "INSERT INTO prices ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                        COLUMNS
+                    ),
+                    params![
+                        record.waypoint_symbol.as_str(),
+                        record.system_symbol.as_str(),
+                        record.trade_symbol.as_str(),
+                        record.purchase_price,
+                        record.sell_price,
+                        serde_json::to_value(record.supply)?.as_str(),
+                        record.trade_volume,
+                        record.timestamp
+                    ],
+                )?;
+            }
+
+            tx.commit()?;
+
+            Ok(())
+        }
+
+        fn latest_prices(&self, system_symbol: &str) -> STResult<Vec<PriceRecord>> {
+            self.query(
+                &format!(
+                    "SELECT {} FROM prices AS p WHERE system_symbol = ?1 AND timestamp = (
+                         SELECT MAX(timestamp) FROM prices
+                         WHERE waypoint_symbol = p.waypoint_symbol
+                           AND trade_symbol = p.trade_symbol
+                     )
+                     GROUP BY waypoint_symbol, trade_symbol
+                     ORDER BY waypoint_symbol, trade_symbol",
+                    COLUMNS
+                ),
+                &[&system_symbol],
+            )
+        }
+
+        fn price_history(
+            &self,
+            waypoint_symbol: &str,
+            trade_symbol: &str,
+            since: Option<DateTime<Utc>>,
+        ) -> STResult<Vec<PriceRecord>> {
+            let since = since.unwrap_or(DateTime::<Utc>::MIN_UTC);
+
+            self.query(
+                &format!(
+                    "SELECT {} FROM prices
+                     WHERE waypoint_symbol = ?1 AND trade_symbol = ?2 AND timestamp >= ?3
+                     ORDER BY timestamp",
+                    COLUMNS
+                ),
+                &[&waypoint_symbol, &trade_symbol, &since],
+            )
+        }
+    }
+}
+
+impl SpaceTradersClient {
+    /// Attach a [MarketStore] to the client.
+    ///
+    /// All trade good prices seen by subsequent calls to `view_market` will be recorded in the
+    /// store. The store is shared with clones made after this call.
+    pub fn set_market_store(&mut self, store: impl MarketStore + 'static) {
+        self.market_store = Some(std::sync::Arc::new(store));
+    }
+
+    /// Get a reference to the [MarketStore] attached to the client, if there is one.
+    pub fn market_store(&self) -> Option<&dyn MarketStore> {
+        self.market_store.as_deref()
+    }
+
+    /// Records the trade good prices of the market in the attached [MarketStore].
+    pub(crate) fn record_market(&self, system_symbol: &Symbol, market: &Market) -> STResult<()> {
+        if let Some(store) = &self.market_store {
+            let records = PriceRecord::from_market(system_symbol, market, Utc::now());
+            if !records.is_empty() {
+                store.record(&records)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn record(waypoint: &str, good: &str, purchase: i64, sell: i64, age: i64) -> PriceRecord {
+        PriceRecord {
+            waypoint_symbol: Symbol::new(waypoint).unwrap(),
+            system_symbol: Symbol::new("X1-ZA40").unwrap(),
+            trade_symbol: Symbol::new(good).unwrap(),
+            purchase_price: purchase,
+            sell_price: sell,
+            supply: SupplyLevel::Moderate,
+            trade_volume: 100,
+            timestamp: Utc::now() - Duration::minutes(age),
+        }
+    }
+
+    fn sample_records() -> Vec<PriceRecord> {
+        vec![
+            record("X1-ZA40-15970B", "IRON_ORE", 40, 30, 60),
+            record("X1-ZA40-15970B", "IRON_ORE", 45, 35, 10),
+            record("X1-ZA40-68707C", "IRON_ORE", 50, 42, 20),
+            record("X1-ZA40-68707C", "FUEL", 120, 110, 20),
+        ]
+    }
+
+    fn check_queries(store: &dyn MarketStore) -> STResult<()> {
+        store.record(&sample_records())?;
+
+        let latest = store.latest_prices("X1-ZA40")?;
+        assert_eq!(latest.len(), 3);
+        assert_eq!(latest[0].waypoint_symbol, "X1-ZA40-15970B");
+        assert_eq!(latest[0].purchase_price, 45);
+
+        let history = store.price_history("X1-ZA40-15970B", "IRON_ORE", None)?;
+        assert_eq!(history.len(), 2);
+        assert!(history[0].timestamp < history[1].timestamp);
+
+        let since = Utc::now() - Duration::minutes(30);
+        let history = store.price_history("X1-ZA40-15970B", "IRON_ORE", Some(since))?;
+        assert_eq!(history.len(), 1);
+
+        let latest = store.latest_price("X1-ZA40-68707C", "FUEL")?.unwrap();
+        assert_eq!(latest.sell_price, 110);
+
+        let best_buy = store.best_buy_location("X1-ZA40", "IRON_ORE")?.unwrap();
+        assert_eq!(best_buy.waypoint_symbol, "X1-ZA40-15970B");
+
+        let best_sell = store.best_sell_location("X1-ZA40", "IRON_ORE")?.unwrap();
+        assert_eq!(best_sell.waypoint_symbol, "X1-ZA40-68707C");
+
+        assert!(store.best_sell_location("X1-ZA40", "GOLD")?.is_none());
+        assert!(store.latest_prices("X1-ZZ99")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_query_in_memory_store() -> STResult<()> {
+        check_queries(&InMemoryMarketStore::new())
+    }
+
+    #[test]
+    fn can_query_file_store() -> STResult<()> {
+        let path = std::env::temp_dir().join(format!("{}.jsonl", uuid::Uuid::new_v4()));
+
+        check_queries(&FileMarketStore::open(&path)?)?;
+
+        // Prices should persist when the file is reopened
+        let store = FileMarketStore::open(&path)?;
+        assert_eq!(store.latest_prices("X1-ZA40")?.len(), 3);
+
+        std::fs::remove_file(path)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn can_query_sqlite_store() -> STResult<()> {
+        check_queries(&SqliteMarketStore::open_in_memory()?)
+    }
+
+    #[test]
+    fn records_visible_trade_goods() -> STResult<()> {
+        let market: Market = serde_json::from_str(
+            r#"{
+                "symbol": "X1-ZA40-15970B",
+                "exports": [],
+                "imports": [],
+                "exchange": [],
+                "tradeGoods": [
+                    {
+                        "symbol": "FUEL",
+                        "tradeVolume": 100,
+                        "supply": "ABUNDANT",
+                        "purchasePrice": 122,
+                        "sellPrice": 118
+                    }
+                ]
+            }"#,
+        )?;
+
+        let mut client = SpaceTradersClient::new();
+        client.set_market_store(InMemoryMarketStore::new());
+        client.record_market(&Symbol::new("X1-ZA40").unwrap(), &market)?;
+
+        let latest = client.market_store().unwrap().latest_prices("X1-ZA40")?;
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].trade_symbol, "FUEL");
+        assert_eq!(latest[0].supply, SupplyLevel::Abundant);
+        assert_eq!(latest[0].purchase_price, 122);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,720 @@
+//! Collects metrics about API usage and the agent's performance, in the Prometheus text format.
+//!
+//! When [Metrics] are attached to a [SpaceTradersClient], the client records every request it
+//! sends, along with the state of the agent as it changes. The [Metrics] handle can be rendered
+//! with [render](Metrics::render) at any time, e.g. to serve it on a `/metrics` endpoint or to
+//! write it to a file for the Prometheus node exporter.
+//!
+//! The following metrics are collected:
+//! - `space_traders_requests_total`: requests sent, by method, endpoint and response status.
+//!   Requests that didn't get a response have the status `error`.
+//! - `space_traders_request_duration_seconds`: a histogram of request latencies, by method and
+//!   endpoint.
+//! - `space_traders_rate_limited_total`: responses with the status `429 Too Many Requests`, by
+//!   endpoint.
+//! - `space_traders_retries_total`: retried requests, by endpoint. The client never retries a
+//!   request by itself; these are the steps retried by a
+//!   [MiningLoop](crate::mining::MiningLoop), and any retries the application records with
+//!   [record_retry](Metrics::record_retry).
+//! - `space_traders_credits`: the agent's credits.
+//! - `space_traders_cargo_units`, `space_traders_cargo_capacity` and
+//!   `space_traders_cargo_utilization`: the cargo hold of each ship.
+//! - `space_traders_extractions_total`: extractions, by waypoint.
+//! - `space_traders_extracted_units_total`: extracted units, by waypoint and trade good.
+//! - `space_traders_contract_payments_total`: credits received for each contract.
+//! - `space_traders_contract_costs_total`: credits spent on each contract: what the goods delivered
+//!   to it were bought for, plus the fuel the delivering ship bought since its previous delivery.
+//!   Mined goods cost nothing.
+//! - `space_traders_contract_profit`: the payments of each contract minus its costs.
+//!
+//! Endpoints are labelled with their path, with symbols replaced by placeholders, e.g.
+//! `/my/ships/{shipSymbol}/orbit`, so there is one series per endpoint instead of one per ship.
+//!
+//! Metrics are only available with the `metrics` feature.
+//!
+//! # Example
+//! ```
+//! # use space_traders::prelude::*;
+//! let mut client = SpaceTradersClient::new();
+//! let metrics = Metrics::new();
+//! client.set_metrics(metrics.clone());
+//!
+//! // After making some requests...
+//! let text = metrics.render();
+//! assert!(text.contains("# TYPE space_traders_requests_total counter"));
+//! ```
+
+use crate::{
+    events::ClientEvent,
+    ledger::{Activity, LedgerEntry},
+    space_traders_client::SpaceTradersClient,
+    transport::{endpoint_label, relative_path},
+    SpaceTradersError,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Write},
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+/// The upper bounds of the request latency histogram's buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 9] = [0.025, 0.05, 0.1, 0.25, 0.5, 1., 2.5, 5., 10.];
+
+/// A handle to the metrics of a [SpaceTradersClient].
+///
+/// Clones share the same metrics.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// By method, endpoint and status.
+    requests: BTreeMap<(String, String, String), u64>,
+    /// By method and endpoint.
+    latencies: BTreeMap<(String, String), Histogram>,
+    /// By endpoint.
+    rate_limited: BTreeMap<String, u64>,
+    /// By endpoint.
+    retries: BTreeMap<String, u64>,
+    credits: Option<i64>,
+    /// The units and capacity of each ship's cargo hold.
+    cargo: BTreeMap<String, (i64, i64)>,
+    /// By waypoint.
+    extractions: BTreeMap<String, u64>,
+    /// By waypoint and trade good.
+    extracted_units: BTreeMap<(String, String), u64>,
+    /// By contract id.
+    contract_payments: BTreeMap<String, i64>,
+    /// By contract id.
+    contract_costs: BTreeMap<String, i64>,
+    /// The units bought of each good still held by each ship, and what they cost.
+    cargo_costs: BTreeMap<(String, String), (i64, i64)>,
+    /// By ship, since its previous contract delivery.
+    fuel_costs: BTreeMap<String, i64>,
+}
+
+impl Registry {
+    /// Removes units of a good from a ship's purchased cargo, and returns what they cost.
+    fn take_cargo_cost(&mut self, ship_symbol: &str, trade_symbol: &str, units: i64) -> i64 {
+        let key = (ship_symbol.to_string(), trade_symbol.to_string());
+        let Some((held, cost)) = self.cargo_costs.get_mut(&key) else {
+            return 0;
+        };
+
+        let units = units.clamp(0, *held);
+        let taken = *cost * units / *held;
+        *held -= units;
+        *cost -= taken;
+        if *held == 0 {
+            self.cargo_costs.remove(&key);
+        }
+
+        taken
+    }
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// The number of observations in each of the [LATENCY_BUCKETS], not cumulative.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|bound| value <= *bound) {
+            self.buckets[i] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that the application retried a request to the endpoint, e.g. after it was rate
+    /// limited.
+    ///
+    /// The endpoint is the path of the request relative to the base URL, e.g.
+    /// `/my/ships/TST-RS-04-1/extract`. Symbols in it are replaced like in the client's requests.
+    pub fn record_retry(&self, endpoint: &str) {
+        *self
+            .registry()
+            .retries
+            .entry(endpoint_label(endpoint))
+            .or_default() += 1;
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        self.to_string()
+    }
+
+    fn registry(&self) -> std::sync::MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Records a request sent by the client, and its response status if there was a response.
+    pub(crate) fn record_request(
+        &self,
+        method: &str,
+        endpoint: String,
+        status: Option<u16>,
+        latency: Duration,
+    ) {
+        let mut registry = self.registry();
+        let status = status.map_or_else(|| "error".into(), |status| status.to_string());
+        if status == "429" {
+            *registry.rate_limited.entry(endpoint.clone()).or_default() += 1;
+        }
+
+        *registry
+            .requests
+            .entry((method.into(), endpoint.clone(), status))
+            .or_default() += 1;
+        registry
+            .latencies
+            .entry((method.into(), endpoint))
+            .or_default()
+            .observe(latency.as_secs_f64());
+    }
+
+    pub(crate) fn record_extraction(&self, waypoint: &str, trade_symbol: &str, units: i32) {
+        let mut registry = self.registry();
+        *registry.extractions.entry(waypoint.into()).or_default() += 1;
+        *registry
+            .extracted_units
+            .entry((waypoint.into(), trade_symbol.into()))
+            .or_default() += units.max(0) as u64;
+    }
+
+    pub(crate) fn record_contract_payment(&self, contract_id: &str, amount: i64) {
+        *self
+            .registry()
+            .contract_payments
+            .entry(contract_id.into())
+            .or_default() += amount;
+    }
+
+    /// Records a ledger entry's effect on the costs attributed to contracts.
+    pub(crate) fn record_ledger_entry(&self, entry: &LedgerEntry) {
+        let mut registry = self.registry();
+        let (Some(ship_symbol), cost) = (&entry.ship_symbol, -entry.amount) else {
+            return;
+        };
+
+        match (entry.activity, &entry.trade_symbol, entry.units) {
+            (Activity::MarketPurchase, Some(trade_symbol), Some(units)) => {
+                let (held, total) = registry
+                    .cargo_costs
+                    .entry((ship_symbol.to_string(), trade_symbol.to_string()))
+                    .or_default();
+                *held += units;
+                *total += cost;
+            }
+            (Activity::MarketSale, Some(trade_symbol), Some(units)) => {
+                registry.take_cargo_cost(ship_symbol, trade_symbol, units);
+            }
+            (Activity::Refuel, ..) => {
+                *registry
+                    .fuel_costs
+                    .entry(ship_symbol.to_string())
+                    .or_default() += cost;
+            }
+            _ => {}
+        }
+    }
+
+    /// Removes jettisoned goods from the ship's purchased cargo.
+    pub(crate) fn record_jettison(&self, ship_symbol: &str, trade_symbol: &str, units: i32) {
+        self.registry()
+            .take_cargo_cost(ship_symbol, trade_symbol, units as i64);
+    }
+
+    /// Attributes the cost of the delivered goods, and the ship's fuel since its previous
+    /// delivery, to the contract.
+    pub(crate) fn record_contract_delivery(
+        &self,
+        contract_id: &str,
+        ship_symbol: &str,
+        trade_symbol: &str,
+        units: i32,
+    ) {
+        let mut registry = self.registry();
+        let goods = registry.take_cargo_cost(ship_symbol, trade_symbol, units as i64);
+        let fuel = registry.fuel_costs.remove(ship_symbol).unwrap_or_default();
+        *registry
+            .contract_costs
+            .entry(contract_id.into())
+            .or_default() += goods + fuel;
+    }
+
+    /// Updates the agent's state from an event of the client.
+    pub(crate) fn observe(&self, client: &SpaceTradersClient, event: &ClientEvent) {
+        match event {
+            ClientEvent::CreditsChanged { after, .. } => {
+                self.registry().credits = Some(*after as i64);
+            }
+            ClientEvent::CargoChanged {
+                ship_symbol, after, ..
+            } => {
+                self.registry()
+                    .cargo
+                    .insert(ship_symbol.to_string(), (*after.units, *after.capacity));
+            }
+            ClientEvent::ShipPurchased { .. } | ClientEvent::CacheReplaced => self.sync(client),
+            _ => {}
+        }
+    }
+
+    /// Sets the agent's credits and the cargo of its ships from the client's cache.
+    pub(crate) fn sync(&self, client: &SpaceTradersClient) {
+        let (Ok(agent), Ok(ships)) = (client.agent(), client.ships()) else {
+            return;
+        };
+
+        let mut registry = self.registry();
+        registry.credits = Some(agent.credits as i64);
+        registry.cargo = ships
+            .into_iter()
+            .map(|ship| {
+                (
+                    ship.symbol.to_string(),
+                    (*ship.cargo.units, *ship.cargo.capacity),
+                )
+            })
+            .collect();
+    }
+}
+
+impl Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registry = self.registry();
+
+        header(
+            f,
+            "space_traders_requests_total",
+            "counter",
+            "API requests sent, by response status.",
+        )?;
+        for ((method, endpoint, status), count) in &registry.requests {
+            let labels = labels(&[
+                ("method", method),
+                ("endpoint", endpoint),
+                ("status", status),
+            ]);
+            writeln!(f, "space_traders_requests_total{} {}", labels, count)?;
+        }
+
+        header(
+            f,
+            "space_traders_request_duration_seconds",
+            "histogram",
+            "The latency of API requests.",
+        )?;
+        for ((method, endpoint), histogram) in &registry.latencies {
+            let name = "space_traders_request_duration_seconds";
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let labels = labels(&[
+                    ("method", method),
+                    ("endpoint", endpoint),
+                    ("le", &bound.to_string()),
+                ]);
+                writeln!(f, "{}_bucket{} {}", name, labels, cumulative)?;
+            }
+            let labels_inf = labels(&[("method", method), ("endpoint", endpoint), ("le", "+Inf")]);
+            writeln!(f, "{}_bucket{} {}", name, labels_inf, histogram.count)?;
+
+            let labels = labels(&[("method", method), ("endpoint", endpoint)]);
+            writeln!(f, "{}_sum{} {}", name, labels, histogram.sum)?;
+            writeln!(f, "{}_count{} {}", name, labels, histogram.count)?;
+        }
+
+        header(
+            f,
+            "space_traders_rate_limited_total",
+            "counter",
+            "API requests rejected by the rate limit.",
+        )?;
+        for (endpoint, count) in &registry.rate_limited {
+            let labels = labels(&[("endpoint", endpoint)]);
+            writeln!(f, "space_traders_rate_limited_total{} {}", labels, count)?;
+        }
+
+        header(
+            f,
+            "space_traders_retries_total",
+            "counter",
+            "API requests retried by the application.",
+        )?;
+        for (endpoint, count) in &registry.retries {
+            let labels = labels(&[("endpoint", endpoint)]);
+            writeln!(f, "space_traders_retries_total{} {}", labels, count)?;
+        }
+
+        header(f, "space_traders_credits", "gauge", "The agent's credits.")?;
+        if let Some(credits) = registry.credits {
+            writeln!(f, "space_traders_credits {}", credits)?;
+        }
+
+        header(
+            f,
+            "space_traders_cargo_units",
+            "gauge",
+            "The units of cargo in each ship's hold.",
+        )?;
+        for (ship, (units, _)) in &registry.cargo {
+            let labels = labels(&[("ship", ship)]);
+            writeln!(f, "space_traders_cargo_units{} {}", labels, units)?;
+        }
+
+        header(
+            f,
+            "space_traders_cargo_capacity",
+            "gauge",
+            "The capacity of each ship's cargo hold.",
+        )?;
+        for (ship, (_, capacity)) in &registry.cargo {
+            let labels = labels(&[("ship", ship)]);
+            writeln!(f, "space_traders_cargo_capacity{} {}", labels, capacity)?;
+        }
+
+        header(
+            f,
+            "space_traders_cargo_utilization",
+            "gauge",
+            "The fraction of each ship's cargo hold that is in use.",
+        )?;
+        for (ship, (units, capacity)) in &registry.cargo {
+            let utilization = match capacity {
+                0 => 0.,
+                capacity => *units as f64 / *capacity as f64,
+            };
+            let labels = labels(&[("ship", ship)]);
+            writeln!(
+                f,
+                "space_traders_cargo_utilization{} {}",
+                labels, utilization
+            )?;
+        }
+
+        header(
+            f,
+            "space_traders_extractions_total",
+            "counter",
+            "Extractions, by waypoint.",
+        )?;
+        for (waypoint, count) in &registry.extractions {
+            let labels = labels(&[("waypoint", waypoint)]);
+            writeln!(f, "space_traders_extractions_total{} {}", labels, count)?;
+        }
+
+        header(
+            f,
+            "space_traders_extracted_units_total",
+            "counter",
+            "Units extracted, by waypoint and trade good.",
+        )?;
+        for ((waypoint, symbol), units) in &registry.extracted_units {
+            let labels = labels(&[("waypoint", waypoint), ("symbol", symbol)]);
+            writeln!(f, "space_traders_extracted_units_total{} {}", labels, units)?;
+        }
+
+        header(
+            f,
+            "space_traders_contract_payments_total",
+            "counter",
+            "Credits received for each contract.",
+        )?;
+        for (contract, credits) in &registry.contract_payments {
+            let labels = labels(&[("contract", contract)]);
+            writeln!(
+                f,
+                "space_traders_contract_payments_total{} {}",
+                labels, credits
+            )?;
+        }
+
+        header(
+            f,
+            "space_traders_contract_costs_total",
+            "counter",
+            "Credits spent on the goods delivered to each contract, and on the delivering ships' fuel.",
+        )?;
+        for (contract, credits) in &registry.contract_costs {
+            let labels = labels(&[("contract", contract)]);
+            writeln!(
+                f,
+                "space_traders_contract_costs_total{} {}",
+                labels, credits
+            )?;
+        }
+
+        header(
+            f,
+            "space_traders_contract_profit",
+            "gauge",
+            "Credits received for each contract, minus its costs.",
+        )?;
+        let contracts: std::collections::BTreeSet<_> = registry
+            .contract_payments
+            .keys()
+            .chain(registry.contract_costs.keys())
+            .collect();
+        for contract in contracts {
+            let payments = registry.contract_payments.get(contract).copied();
+            let costs = registry.contract_costs.get(contract).copied();
+            let labels = labels(&[("contract", contract)]);
+            writeln!(
+                f,
+                "space_traders_contract_profit{} {}",
+                labels,
+                payments.unwrap_or_default() - costs.unwrap_or_default()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn header(f: &mut fmt::Formatter<'_>, name: &str, kind: &str, help: &str) -> fmt::Result {
+    writeln!(f, "# HELP {} {}", name, help)?;
+    writeln!(f, "# TYPE {} {}", name, kind)
+}
+
+/// Formats the labels of a sample, escaping their values.
+fn labels(labels: &[(&str, &str)]) -> String {
+    let mut formatted = String::from("{");
+    for (i, (name, value)) in labels.iter().enumerate() {
+        if i > 0 {
+            formatted.push(',');
+        }
+        let value = value
+            .replace('\\', r"\\")
+            .replace('"', r#"\""#)
+            .replace('\n', r"\n");
+        // Writing to a string can't fail
+        let _ = write!(formatted, "{}=\"{}\"", name, value);
+    }
+    formatted.push('}');
+
+    formatted
+}
+
+impl SpaceTradersClient {
+    /// Attach [Metrics] to the client.
+    ///
+    /// Subsequent requests and changes to the agent are recorded in the metrics, starting from
+    /// the agent's current state. The metrics are shared with clones made after this call.
+    pub fn set_metrics(&mut self, metrics: Metrics) {
+        metrics.sync(self);
+        self.metrics = Some(metrics);
+    }
+
+    /// Get the [Metrics] attached to the client, if there are any.
+    pub fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+
+    /// Records that the request that failed with the error is being retried.
+    pub(crate) fn record_retry(&self, err: &SpaceTradersError) {
+        let Some(metrics) = &self.metrics else {
+            return;
+        };
+
+        let endpoint = match err {
+            SpaceTradersError::ResponseError(error) => error.path(),
+            SpaceTradersError::ReqwestError(error) => error
+                .url()
+                .map(|url| relative_path(url.as_str(), &self.base_url)),
+            _ => None,
+        };
+        metrics.record_retry(endpoint.unwrap_or("unknown"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        conditional_types::Symbol,
+        transport::{relative_path, HttpRequest, HttpResponse, Transport, TransportFuture},
+        STResult,
+    };
+
+    #[cfg(feature = "simulator")]
+    #[tokio::test]
+    async fn records_requests_and_the_agent() -> STResult<()> {
+        let simulator =
+            crate::simulator::Simulator::start("2023-05-17T04:18:05.930Z".parse().unwrap()).await?;
+        let mut client = simulator.client();
+        client.register_callsign("METRICS", None).await?;
+        client.set_auto_prepare(true);
+
+        let metrics = Metrics::new();
+        client.set_metrics(metrics.clone());
+        let ship = Symbol::new("METRICS-1").unwrap();
+        let asteroid_field = Symbol::new("X1-ZA40-99095A").unwrap();
+
+        let (_, extraction) = simulator
+            .run_for(chrono::Duration::hours(1), async {
+                client.navigate_ship(&ship, &asteroid_field).await?;
+                client.extract_resources(&ship, None).await
+            })
+            .await
+            .expect("the extraction should finish")?;
+        let contract = client.contracts()?[0].clone();
+        client.accept_contract(contract.id.clone()).await?;
+
+        let text = metrics.render();
+        let lines: Vec<_> = text.lines().collect();
+        let expected = [
+            r#"space_traders_requests_total{method="POST",endpoint="/my/ships/{shipSymbol}/orbit",status="200"} 1"#.to_string(),
+            r#"space_traders_request_duration_seconds_count{method="POST",endpoint="/my/ships/{shipSymbol}/extract"} 1"#.into(),
+            r#"space_traders_extractions_total{waypoint="X1-ZA40-99095A"} 1"#.into(),
+            format!(
+                r#"space_traders_extracted_units_total{{waypoint="X1-ZA40-99095A",symbol="{}"}} {}"#,
+                extraction.yield_.symbol, extraction.yield_.units
+            ),
+            format!(
+                r#"space_traders_cargo_units{{ship="METRICS-1"}} {}"#,
+                *client.get_ship(&ship)?.cargo.units
+            ),
+            format!("space_traders_credits {}", client.agent()?.credits),
+            format!(
+                r#"space_traders_contract_payments_total{{contract="{}"}} {}"#,
+                contract.id, contract.terms.payment.on_accepted
+            ),
+        ];
+        for line in expected {
+            assert!(
+                lines.contains(&line.as_str()),
+                "missing {}:\n{}",
+                line,
+                text
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn counts_rate_limited_requests() -> STResult<()> {
+        /// Rejects every request.
+        #[derive(Debug)]
+        struct RateLimited;
+
+        impl Transport for RateLimited {
+            fn send(&self, _request: HttpRequest) -> TransportFuture<'_> {
+                Box::pin(async {
+                    Ok(HttpResponse::new(
+                        429,
+                        r#"{"error":{"code":429,"message":"Too many requests"}}"#,
+                    ))
+                })
+            }
+        }
+
+        let mut client = SpaceTradersClient::new();
+        client.set_transport(RateLimited);
+        *client.token.write().unwrap() = Some("token".into());
+        let metrics = Metrics::new();
+        client.set_metrics(metrics.clone());
+
+        let system = Symbol::new("X1-ZA40").unwrap();
+        client.view_system(&system).await.unwrap_err();
+        metrics.record_retry("/systems/X1-ZA40");
+        client.view_system(&system).await.unwrap_err();
+
+        let text = metrics.render();
+        let lines: Vec<_> = text.lines().collect();
+        for line in [
+            r#"space_traders_requests_total{method="GET",endpoint="/systems/{systemSymbol}",status="429"} 2"#,
+            r#"space_traders_rate_limited_total{endpoint="/systems/{systemSymbol}"} 2"#,
+            r#"space_traders_retries_total{endpoint="/systems/{systemSymbol}"} 1"#,
+            r#"space_traders_request_duration_seconds_bucket{method="GET",endpoint="/systems/{systemSymbol}",le="+Inf"} 2"#,
+        ] {
+            assert!(lines.contains(&line), "missing {}:\n{}", line, text);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn attributes_costs_to_contracts() {
+        let metrics = Metrics::new();
+        let entry = |activity, trade_symbol: Option<&str>, units, amount| LedgerEntry {
+            timestamp: chrono::Utc::now(),
+            activity,
+            ship_symbol: Some(Symbol::new("TST-1").unwrap()),
+            counterpart: "X1-ZA40-15970B".into(),
+            trade_symbol: trade_symbol.map(|symbol| Symbol::new(symbol).unwrap()),
+            units,
+            amount,
+            balance: 0,
+        };
+
+        metrics.record_contract_payment("contract-1", 2000);
+        metrics.record_ledger_entry(&entry(
+            Activity::MarketPurchase,
+            Some("IRON_ORE"),
+            Some(20),
+            -1000,
+        ));
+        metrics.record_ledger_entry(&entry(Activity::Refuel, Some("FUEL"), Some(1), -100));
+        metrics.record_ledger_entry(&entry(Activity::MarketSale, Some("IRON_ORE"), Some(5), 200));
+
+        // 10 of the 15 units still held, and the fuel
+        metrics.record_contract_delivery("contract-1", "TST-1", "IRON_ORE", 10);
+        // Jettisoned and mined goods cost nothing
+        metrics.record_jettison("TST-1", "IRON_ORE", 5);
+        metrics.record_contract_delivery("contract-1", "TST-1", "IRON_ORE", 10);
+
+        let text = metrics.render();
+        let lines: Vec<_> = text.lines().collect();
+        for line in [
+            r#"space_traders_contract_payments_total{contract="contract-1"} 2000"#,
+            r#"space_traders_contract_costs_total{contract="contract-1"} 600"#,
+            r#"space_traders_contract_profit{contract="contract-1"} 1400"#,
+        ] {
+            assert!(lines.contains(&line), "missing {}:\n{}", line, text);
+        }
+    }
+
+    #[test]
+    fn labels_endpoints_without_symbols() {
+        let base_url = "https://api.spacetraders.io/v2";
+        for (url, label) in [
+            ("/my/agent", "/my/agent"),
+            ("/my/ships", "/my/ships"),
+            ("/my/ships/TST-1/nav", "/my/ships/{shipSymbol}/nav"),
+            (
+                "/my/contracts/abc/deliver",
+                "/my/contracts/{contractId}/deliver",
+            ),
+            (
+                "/systems/X1-A/waypoints/X1-A-1/market?page=2",
+                "/systems/{systemSymbol}/waypoints/{waypointSymbol}/market",
+            ),
+        ] {
+            assert_eq!(
+                endpoint_label(relative_path(&format!("{}{}", base_url, url), base_url)),
+                label
+            );
+        }
+
+        assert_eq!(
+            labels(&[("a", "1"), ("b", "say \"hi\"\n")]),
+            r#"{a="1",b="say \"hi\"\n"}"#
+        );
+    }
+}
@@ -0,0 +1,768 @@
+//! Provides a reusable mining behaviour for a single ship.
+//!
+//! A [MiningLoop] orbits an asteroid field, surveys it (if the ship has a surveyor), and extracts
+//! resources until the ship's cargo hold is full, waiting for the ship's cooldown between
+//! extractions. Once full, the ship unloads its cargo: goods required by the configured contract
+//! are delivered to the contract's destination, and everything else is sold at the market that
+//! pays the most for it (based on the client's [MarketStore](crate::market_history::MarketStore)).
+//! The ship then refuels and returns to the asteroid field.
+//!
+//! Progress is reported through [MiningEvent]s, and the loop can be stopped at any time with a
+//! [CancelHandle].
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let client = SpaceTradersClient::load_saved().unwrap();
+//!
+//! let ship = Symbol::new("TST-RS-04-1").unwrap();
+//! let config = MiningConfig::new(
+//!     Symbol::new("X1-ZA40-99095A").unwrap(),
+//!     Symbol::new("X1-ZA40-15970B").unwrap(),
+//! );
+//!
+//! let mut mining = MiningLoop::new(ship, config);
+//! let mut events = mining.subscribe();
+//! tokio::spawn(async move {
+//!     while let Some(event) = events.recv().await {
+//!         println!("{:?}", event);
+//!     }
+//! });
+//!
+//! mining.run(&client).await.unwrap();
+//! # })
+//! ```
+
+use crate::{
+    conditional_types::{Id, Symbol},
+    contract::Contract,
+    market::Market,
+    ship::{MountSymbol, Ship, ShipStatus, Survey},
+    space_traders_client::SpaceTradersClient,
+    STResult,
+};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+
+/// Configuration for a [MiningLoop].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiningConfig {
+    /// The waypoint to extract resources at.
+    pub asteroid_field: Symbol,
+    /// The market to sell at if the client has no recorded prices for the ship's cargo.
+    pub market: Symbol,
+    /// Whether to survey the asteroid field before extracting (if the ship has a surveyor).
+    pub survey: bool,
+    /// A contract to deliver extracted goods to, instead of selling them.
+    pub contract_id: Option<Id>,
+}
+
+impl MiningConfig {
+    /// Creates a configuration that surveys before extracting and sells everything it mines.
+    pub fn new(asteroid_field: Symbol, market: Symbol) -> Self {
+        Self {
+            asteroid_field,
+            market,
+            survey: true,
+            contract_id: None,
+        }
+    }
+}
+
+/// Progress reported by a [MiningLoop].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MiningEvent {
+    Navigating {
+        destination: Symbol,
+        arrival: DateTime<Utc>,
+    },
+    Arrived {
+        waypoint: Symbol,
+    },
+    Docked {
+        waypoint: Symbol,
+    },
+    Orbiting {
+        waypoint: Symbol,
+    },
+    Surveyed {
+        surveys: usize,
+    },
+    Extracted {
+        trade_symbol: Symbol,
+        units: i32,
+        cargo_units: i64,
+        cargo_capacity: i64,
+    },
+    CoolingDown {
+        until: DateTime<Utc>,
+    },
+    Sold {
+        trade_symbol: Symbol,
+        units: i64,
+        total_price: i64,
+    },
+    Delivered {
+        contract_id: Id,
+        trade_symbol: Symbol,
+        units: i32,
+    },
+    Jettisoned {
+        trade_symbol: Symbol,
+        units: i32,
+    },
+    Refueled {
+        total_price: i64,
+    },
+    Stopped,
+}
+
+/// The outcome of a single [MiningLoop::step].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiningStep {
+    /// An action was performed; the next step can be taken immediately.
+    Continue,
+    /// The ship is busy (travelling or cooling down) until the given time.
+    WaitUntil(DateTime<Utc>),
+}
+
+/// The next action a [MiningLoop] should take.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum MiningAction {
+    Wait(DateTime<Utc>),
+    Arrive,
+    Dock,
+    Orbit,
+    Navigate(Symbol),
+    Survey,
+    Extract,
+    Sell(Symbol, i32),
+    Deliver(Id, Symbol, i32),
+    Jettison(Symbol, i32),
+    Refuel,
+    FinishUnloading,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Mining,
+    Unloading,
+}
+
+/// Stops a running [MiningLoop].
+#[derive(Debug, Clone)]
+pub struct CancelHandle(pub(crate) Arc<watch::Sender<bool>>);
+
+impl CancelHandle {
+    /// Stops the loop after its current action completes.
+    pub fn cancel(&self) {
+        self.0.send_replace(true);
+    }
+}
+
+/// Mines resources with a single ship until cancelled.
+#[derive(Debug)]
+pub struct MiningLoop {
+    ship_symbol: Symbol,
+    config: MiningConfig,
+    phase: Phase,
+    cooldown: Option<DateTime<Utc>>,
+    survey: Option<Survey>,
+    unload_market: Option<Symbol>,
+    market: Option<Market>,
+    unsellable: Vec<Symbol>,
+    refueled: bool,
+    events: Option<mpsc::UnboundedSender<MiningEvent>>,
+    cancel_tx: Arc<watch::Sender<bool>>,
+    cancel_rx: watch::Receiver<bool>,
+}
+
+impl MiningLoop {
+    pub fn new(ship_symbol: Symbol, config: MiningConfig) -> Self {
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+
+        Self {
+            ship_symbol,
+            config,
+            phase: Phase::Mining,
+            cooldown: None,
+            survey: None,
+            unload_market: None,
+            market: None,
+            unsellable: vec![],
+            refueled: false,
+            events: None,
+            cancel_tx: Arc::new(cancel_tx),
+            cancel_rx,
+        }
+    }
+
+    pub fn ship_symbol(&self) -> &Symbol {
+        &self.ship_symbol
+    }
+
+    pub fn config(&self) -> &MiningConfig {
+        &self.config
+    }
+
+    /// Get a receiver for the events reported by the loop.
+    ///
+    /// Only the most recent receiver gets the events.
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<MiningEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.events = Some(tx);
+        rx
+    }
+
+    /// Get a handle that can be used to stop the loop from another task.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle(self.cancel_tx.clone())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel_rx.borrow()
+    }
+
+    /// Runs the loop until it is cancelled or an error occurs.
+    ///
+    /// Steps that fail with a [retryable](crate::SpaceTradersError::is_retryable) error are retried
+    /// after a short delay.
+    pub async fn run(&mut self, client: &SpaceTradersClient) -> STResult<()> {
+        let mut cancel_rx = self.cancel_rx.clone();
+
+        while !self.is_cancelled() {
+            let step = match self.step(client).await {
+                Ok(step) => step,
+                Err(err) => MiningStep::WaitUntil(client.retry_at(&err).ok_or(err)?),
+            };
+
+            if let MiningStep::WaitUntil(until) = step {
+                tokio::select! {
+                    _ = client.scheduler().clock().sleep_until(until) => {}
+                    _ = cancel_rx.changed() => {}
+                }
+            }
+        }
+
+        self.emit(MiningEvent::Stopped);
+
+        Ok(())
+    }
+
+    /// Performs the next action of the loop.
+    ///
+    /// This never waits; if the ship is busy the time it will be ready is returned instead.
+    pub async fn step(&mut self, client: &SpaceTradersClient) -> STResult<MiningStep> {
+        let ship = client.get_ship(&self.ship_symbol)?;
+        if self.phase == Phase::Mining && *ship.cargo.units >= *ship.cargo.capacity {
+            self.phase = Phase::Unloading;
+            self.unload_market = Some(self.choose_market(client, &ship)?);
+        }
+
+        let ship = client.get_ship(&self.ship_symbol)?;
+        let contract = match &self.config.contract_id {
+            Some(id) => Some(client.get_contract(id)?),
+            None => None,
+        };
+        let now = client.scheduler().clock().now();
+        // The ship may be cooling down from an action the client saw before this loop started,
+        // e.g. an extraction by another task
+        self.cooldown = self
+            .cooldown
+            .max(client.cooldown_expiration(&self.ship_symbol));
+        let action = self.next_action(&ship, contract.as_ref(), now);
+
+        let ship_symbol = self.ship_symbol.clone();
+        let waypoint = ship.nav.waypoint_symbol.clone();
+        match action {
+            MiningAction::Wait(until) => {
+                if self.cooldown == Some(until) {
+                    self.emit(MiningEvent::CoolingDown { until });
+                }
+
+                return Ok(MiningStep::WaitUntil(until));
+            }
+            MiningAction::Arrive => {
+                client.update_ship(&ship_symbol, |ship| ship.nav.status = ShipStatus::InOrbit)?;
+                self.emit(MiningEvent::Arrived { waypoint });
+            }
+            MiningAction::Dock => {
+                client.dock_ship(&ship_symbol).await?;
+                self.emit(MiningEvent::Docked { waypoint });
+            }
+            MiningAction::Orbit => {
+                client.orbit_ship(&ship_symbol).await?;
+                self.emit(MiningEvent::Orbiting { waypoint });
+            }
+            MiningAction::Navigate(destination) => {
+                let nav = client.navigate_ship(&ship_symbol, &destination).await?;
+                self.refueled = false;
+                self.emit(MiningEvent::Navigating {
+                    destination,
+                    arrival: nav.route.arrival,
+                });
+            }
+            MiningAction::Survey => {
+                let (cooldown, surveys) = client.create_survey(&ship_symbol).await?;
+                self.cooldown = Some(cooldown.expiration);
+                self.emit(MiningEvent::Surveyed {
+                    surveys: surveys.len(),
+                });
+                self.survey = surveys.into_iter().max_by_key(|survey| survey.size);
+            }
+            MiningAction::Extract => {
+                let (cooldown, extraction) = client
+                    .extract_resources(&ship_symbol, self.survey.clone())
+                    .await?;
+                self.cooldown = Some(cooldown.expiration);
+
+                let cargo = client.get_ship(&ship_symbol)?.cargo;
+                self.emit(MiningEvent::Extracted {
+                    trade_symbol: extraction.yield_.symbol,
+                    units: extraction.yield_.units,
+                    cargo_units: *cargo.units,
+                    cargo_capacity: *cargo.capacity,
+                });
+            }
+            MiningAction::Sell(trade_symbol, units) => {
+                let market = match &self.market {
+                    Some(market) if market.symbol == waypoint => market,
+                    _ => self.market.insert(
+                        client
+                            .view_market(&ship.nav.system_symbol, &waypoint)
+                            .await?,
+                    ),
+                };
+
+                match client
+                    .sell_at_market(market, &ship_symbol, &trade_symbol, units)
+                    .await?
+                {
+                    Some(transaction) => self.emit(MiningEvent::Sold {
+                        trade_symbol,
+                        units: *transaction.units,
+                        total_price: *transaction.total_price,
+                    }),
+                    // The market doesn't buy this good
+                    None => self.unsellable.push(trade_symbol),
+                }
+            }
+            MiningAction::Deliver(contract_id, trade_symbol, units) => {
+                client
+                    .deliver_contract(&contract_id, &ship_symbol, &trade_symbol, units)
+                    .await?;
+                self.emit(MiningEvent::Delivered {
+                    contract_id,
+                    trade_symbol,
+                    units,
+                });
+            }
+            MiningAction::Jettison(trade_symbol, units) => {
+                client
+                    .jettison_cargo(&ship_symbol, &trade_symbol, units)
+                    .await?;
+                self.emit(MiningEvent::Jettisoned {
+                    trade_symbol,
+                    units,
+                });
+            }
+            MiningAction::Refuel => {
+                self.refueled = true;
+                match client.refuel_ship(&ship_symbol).await {
+                    Ok(transaction) => self.emit(MiningEvent::Refueled {
+                        total_price: *transaction.total_price,
+                    }),
+                    // Fuel isn't sold here, so try again at the next market
+                    Err(err) if err.is_not_traded() => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            MiningAction::FinishUnloading => {
+                self.phase = Phase::Mining;
+                self.unload_market = None;
+                self.market = None;
+                self.unsellable.clear();
+            }
+        }
+
+        Ok(MiningStep::Continue)
+    }
+
+    /// Decides the next action based on the current state of the ship.
+    pub(crate) fn next_action(
+        &self,
+        ship: &Ship,
+        contract: Option<&Contract>,
+        now: DateTime<Utc>,
+    ) -> MiningAction {
+        let nav = &ship.nav;
+        if nav.status == ShipStatus::InTransit {
+            if nav.route.arrival > now {
+                return MiningAction::Wait(nav.route.arrival);
+            }
+            return MiningAction::Arrive;
+        }
+
+        // Moves the ship to the given waypoint, leaving orbit first if needed
+        let go_to = |destination: &Symbol| {
+            if nav.status == ShipStatus::Docked {
+                MiningAction::Orbit
+            } else {
+                MiningAction::Navigate(destination.clone())
+            }
+        };
+
+        if self.phase == Phase::Mining {
+            if nav.waypoint_symbol != self.config.asteroid_field {
+                return go_to(&self.config.asteroid_field);
+            }
+            if nav.status == ShipStatus::Docked {
+                return MiningAction::Orbit;
+            }
+            if let Some(cooldown) = self.cooldown.filter(|&cooldown| cooldown > now) {
+                return MiningAction::Wait(cooldown);
+            }
+
+            let has_surveyor = ship.mounts.iter().any(|mount| {
+                matches!(
+                    mount.symbol,
+                    MountSymbol::MountSurveyorI
+                        | MountSymbol::MountSurveyorII
+                        | MountSymbol::MountSurveyorIII
+                )
+            });
+            let survey_valid = self.survey.as_ref().is_some_and(|survey| {
+                survey.expiration > now && survey.symbol == self.config.asteroid_field
+            });
+            if self.config.survey && has_surveyor && !survey_valid {
+                return MiningAction::Survey;
+            }
+
+            return MiningAction::Extract;
+        }
+
+        // Deliver any goods required by the contract
+        if let Some(contract) = contract {
+            for deliver in &contract.terms.deliver {
+                let remaining = deliver.units_required - deliver.units_fulfilled;
+                let item = ship
+                    .cargo
+                    .inventory
+                    .iter()
+                    .find(|item| item.symbol == deliver.trade_symbol);
+
+                if let (Some(item), true) = (item, remaining > 0) {
+                    if nav.waypoint_symbol != deliver.destination_symbol {
+                        return go_to(&deliver.destination_symbol);
+                    }
+                    if nav.status != ShipStatus::Docked {
+                        return MiningAction::Dock;
+                    }
+
+                    return MiningAction::Deliver(
+                        contract.id.clone(),
+                        item.symbol.clone(),
+                        (*item.units as i32).min(remaining),
+                    );
+                }
+            }
+        }
+
+        // Sell everything else at the chosen market
+        let market = self.unload_market.as_ref().unwrap_or(&self.config.market);
+        if let Some(item) = ship.cargo.inventory.first() {
+            if nav.waypoint_symbol != *market {
+                return go_to(market);
+            }
+            if nav.status != ShipStatus::Docked {
+                return MiningAction::Dock;
+            }
+
+            if self.unsellable.contains(&item.symbol) {
+                return MiningAction::Jettison(item.symbol.clone(), *item.units as i32);
+            }
+            return MiningAction::Sell(item.symbol.clone(), *item.units as i32);
+        }
+
+        if !self.refueled
+            && nav.status == ShipStatus::Docked
+            && *ship.fuel.current < *ship.fuel.capacity
+        {
+            return MiningAction::Refuel;
+        }
+
+        MiningAction::FinishUnloading
+    }
+
+    /// Chooses the market that pays the most for the ship's cargo.
+    fn choose_market(&self, client: &SpaceTradersClient, ship: &Ship) -> STResult<Symbol> {
+        let store = match client.market_store() {
+            Some(store) => store,
+            None => return Ok(self.config.market.clone()),
+        };
+
+        let mut values: Vec<(Symbol, i64)> = Vec::new();
+        for record in store.latest_prices(&ship.nav.system_symbol)? {
+            let units = match ship
+                .cargo
+                .inventory
+                .iter()
+                .find(|item| item.symbol == record.trade_symbol)
+            {
+                Some(item) => *item.units,
+                None => continue,
+            };
+
+            let value = units * record.sell_price;
+            match values
+                .iter_mut()
+                .find(|(market, _)| *market == record.waypoint_symbol)
+            {
+                Some((_, total)) => *total += value,
+                None => values.push((record.waypoint_symbol, value)),
+            }
+        }
+
+        Ok(values
+            .into_iter()
+            .max_by_key(|(_, value)| *value)
+            .map(|(market, _)| market)
+            .unwrap_or_else(|| self.config.market.clone()))
+    }
+
+    fn emit(&self, event: MiningEvent) {
+        if let Some(events) = &self.events {
+            // The receiver may have been dropped, which just means nobody is listening
+            let _ = events.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, symbol};
+    use chrono::Duration;
+
+    const CONTRACT: &str = r#"{
+        "id": "clhr6zx0r07s2s60daxqce7b1",
+        "factionSymbol": "COSMIC",
+        "type": "PROCUREMENT",
+        "terms": {
+            "deadline": "2023-05-24T04:18:05.930Z",
+            "payment": { "onAccepted": 100280, "onFulfilled": 401120 },
+            "deliver": [
+                { "tradeSymbol": "IRON_ORE", "destinationSymbol": "X1-ZA40-15970B", "unitsRequired": 100, "unitsFulfilled": 90 }
+            ]
+        },
+        "accepted": true,
+        "fulfilled": false,
+        "expiration": "2023-05-20T04:18:05.930Z"
+    }"#;
+
+    fn ship() -> Ship {
+        let mut ship = fixtures::ship_docked_at("X1-ZA40-15970B");
+        ship["mounts"] = serde_json::json!([fixtures::mining_laser(), fixtures::surveyor()]);
+        ship["fuel"]["current"] = 1000.into();
+        serde_json::from_value(ship).unwrap()
+    }
+
+    fn fill_cargo(ship: &mut Ship, items: &[(&str, i64)]) {
+        ship.cargo.inventory = items
+            .iter()
+            .map(|(good, units)| {
+                serde_json::from_value(serde_json::json!({
+                    "symbol": good,
+                    "name": good,
+                    "description": good,
+                    "units": units,
+                }))
+                .unwrap()
+            })
+            .collect();
+        ship.cargo.units =
+            serde_json::from_value(items.iter().map(|(_, units)| units).sum::<i64>().into())
+                .unwrap();
+    }
+
+    fn mining_loop() -> MiningLoop {
+        MiningLoop::new(
+            symbol("TST-RS-04-1"),
+            MiningConfig::new(symbol("X1-ZA40-99095A"), symbol("X1-ZA40-15970B")),
+        )
+    }
+
+    #[test]
+    fn travels_to_asteroid_field_and_mines() {
+        let mut mining = mining_loop();
+        let mut ship = ship();
+        let now = Utc::now();
+
+        // Docked at the wrong waypoint
+        assert_eq!(mining.next_action(&ship, None, now), MiningAction::Orbit);
+
+        ship.nav.status = ShipStatus::InOrbit;
+        assert_eq!(
+            mining.next_action(&ship, None, now),
+            MiningAction::Navigate(symbol("X1-ZA40-99095A"))
+        );
+
+        ship.nav.status = ShipStatus::InTransit;
+        ship.nav.waypoint_symbol = symbol("X1-ZA40-99095A");
+        ship.nav.route.arrival = now + Duration::seconds(30);
+        assert_eq!(
+            mining.next_action(&ship, None, now),
+            MiningAction::Wait(ship.nav.route.arrival)
+        );
+        assert_eq!(
+            mining.next_action(&ship, None, now + Duration::seconds(31)),
+            MiningAction::Arrive
+        );
+
+        ship.nav.status = ShipStatus::InOrbit;
+        assert_eq!(mining.next_action(&ship, None, now), MiningAction::Survey);
+
+        mining.config.survey = false;
+        assert_eq!(mining.next_action(&ship, None, now), MiningAction::Extract);
+
+        // Waits for the cooldown between extractions
+        mining.cooldown = Some(now + Duration::seconds(70));
+        assert_eq!(
+            mining.next_action(&ship, None, now),
+            MiningAction::Wait(now + Duration::seconds(70))
+        );
+        assert_eq!(
+            mining.next_action(&ship, None, now + Duration::seconds(71)),
+            MiningAction::Extract
+        );
+    }
+
+    #[test]
+    fn unloads_full_cargo() {
+        let mut mining = mining_loop();
+        mining.phase = Phase::Unloading;
+        let contract: Contract = serde_json::from_str(CONTRACT).unwrap();
+        mining.config.contract_id = Some(contract.id.clone());
+
+        let now = Utc::now();
+        let mut ship = ship();
+        fill_cargo(&mut ship, &[("IRON_ORE", 20), ("QUARTZ_SAND", 40)]);
+        ship.nav.waypoint_symbol = symbol("X1-ZA40-99095A");
+        ship.nav.status = ShipStatus::InOrbit;
+
+        // Contract goods are delivered first (only the units still required)
+        assert_eq!(
+            mining.next_action(&ship, Some(&contract), now),
+            MiningAction::Navigate(symbol("X1-ZA40-15970B"))
+        );
+        ship.nav.waypoint_symbol = symbol("X1-ZA40-15970B");
+        assert_eq!(
+            mining.next_action(&ship, Some(&contract), now),
+            MiningAction::Dock
+        );
+        ship.nav.status = ShipStatus::Docked;
+        assert_eq!(
+            mining.next_action(&ship, Some(&contract), now),
+            MiningAction::Deliver(contract.id.clone(), symbol("IRON_ORE"), 10)
+        );
+
+        // Everything else is sold, or jettisoned if the market won't buy it
+        fill_cargo(&mut ship, &[("QUARTZ_SAND", 40)]);
+        assert_eq!(
+            mining.next_action(&ship, Some(&contract), now),
+            MiningAction::Sell(symbol("QUARTZ_SAND"), 40)
+        );
+        mining.unsellable.push(symbol("QUARTZ_SAND"));
+        assert_eq!(
+            mining.next_action(&ship, Some(&contract), now),
+            MiningAction::Jettison(symbol("QUARTZ_SAND"), 40)
+        );
+
+        // Refuel before heading back
+        fill_cargo(&mut ship, &[]);
+        assert_eq!(
+            mining.next_action(&ship, Some(&contract), now),
+            MiningAction::Refuel
+        );
+        mining.refueled = true;
+        assert_eq!(
+            mining.next_action(&ship, Some(&contract), now),
+            MiningAction::FinishUnloading
+        );
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_cooldown_the_client_knows_about() -> STResult<()> {
+        let mut ship = serde_json::to_value(ship()).unwrap();
+        ship["nav"]["waypointSymbol"] = "X1-ZA40-99095A".into();
+        ship["nav"]["status"] = "IN_ORBIT".into();
+        let client = fixtures::client(vec![ship], vec![]);
+
+        // Another task extracted with the ship just before the loop started
+        let cooldown = client.scheduler().clock().now() + Duration::seconds(70);
+        client
+            .scheduler()
+            .record_cooldown(&symbol("TST-RS-04-1"), cooldown);
+
+        let mut mining = mining_loop();
+        mining.config.survey = false;
+        assert_eq!(mining.step(&client).await?, MiningStep::WaitUntil(cooldown));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "simulator")]
+    #[tokio::test]
+    async fn sells_within_the_trade_volume() -> STResult<()> {
+        let simulator =
+            crate::simulator::Simulator::start("2023-05-17T04:18:05.930Z".parse().unwrap()).await?;
+        let client = simulator.client();
+        client.register_callsign("BACKTEST", None).await?;
+
+        // The shipyard's market only trades 10 units at a time
+        let mut config = MiningConfig::new(symbol("X1-ZA40-99095A"), symbol("X1-ZA40-68707C"));
+        config.survey = false;
+        let mut mining = MiningLoop::new(symbol("BACKTEST-1"), config);
+        let mut events = mining.subscribe();
+
+        let output = simulator
+            .run_for(Duration::hours(2), mining.run(&client))
+            .await;
+        assert!(output.is_none());
+
+        let mut iron_ore_sold = 0;
+        while let Ok(event) = events.try_recv() {
+            match event {
+                MiningEvent::Sold {
+                    trade_symbol,
+                    units,
+                    ..
+                } => {
+                    assert!(units <= 10);
+                    if trade_symbol == symbol("IRON_ORE") {
+                        iron_ore_sold += units;
+                    }
+                }
+                MiningEvent::Jettisoned { trade_symbol, .. } => {
+                    assert_ne!(trade_symbol, symbol("IRON_ORE"));
+                }
+                _ => {}
+            }
+        }
+        assert!(iron_ore_sold > 10);
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_cancel_loop() {
+        let mining = mining_loop();
+        let handle = mining.cancel_handle();
+
+        assert!(!mining.is_cancelled());
+        handle.cancel();
+        assert!(mining.is_cancelled());
+    }
+}
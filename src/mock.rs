@@ -0,0 +1,2150 @@
+//! An in-process mock of the `SpaceTraders API`, for testing without a network connection.
+//!
+//! A [MockServer] listens on a local port and serves every endpoint the client supports from a
+//! small, fixed universe: the `X1-ZA40` system, with the `X1-ZA40-15970B` headquarters planet and
+//! its three orbitals, the `X1-ZA40-68707C` shipyard and the `X1-ZA40-99095A` asteroid field.
+//! Agents registered with the server start with the same credits, ship and contract as agents of
+//! the `COSMIC` faction in the real game, and every action updates the server's state: ships use
+//! fuel and take time to travel, extracting and surveying trigger cooldowns, and trading changes
+//! the agent's credits and the ship's cargo. Trading also moves the market's prices, which recover
+//! over time.
+//!
+//! Everything is deterministic, including IDs, so tests can assert on exact values. The server reads
+//! the time from a [Clock]; with a [ManualClock](crate::scheduler::ManualClock), ships only arrive
+//! and cooldowns only expire when the test advances the clock.
+//!
+//! The mock is only available with the `mock` feature.
+//!
+//! # Example
+//! ```
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let server = MockServer::start().await.unwrap();
+//!
+//! // The client sends its requests to the mock server
+//! let client = server.client();
+//! client.register_callsign("MOCK-AGENT", None).await.unwrap();
+//!
+//! // Every agent starts with a command ship docked at its headquarters
+//! let ship = Symbol::new("MOCK-AGENT-1").unwrap();
+//! client.orbit_ship(&ship).await.unwrap();
+//! client
+//!     .navigate_ship(&ship, &Symbol::new("X1-ZA40-99095A").unwrap())
+//!     .await
+//!     .unwrap();
+//! # })
+//! ```
+
+use crate::{
+    agent::Agent,
+    conditional_types::ints::{LowerBoundInt, NonNegative},
+    conditional_types::strings::Symbol,
+    contract::Contract,
+    market::{Market, MarketTransaction, TransactionType},
+    rate_limiter::RateLimiter,
+    route_planner::{distance, fuel_cost, travel_time},
+    scheduler::{Clock, Scheduler, SystemClock},
+    ship::{
+        Cargo, Cooldown, Deposit, InventoryItem, ModuleSymbol, MountSymbol, Ship, ShipStatus,
+        ShipType, Shipyard, ShipyardShip, ShipyardTransaction, Survey, SurveyDeposit, SurveySize,
+    },
+    space_traders_client::SpaceTradersClient,
+    system::System,
+    waypoint::{Waypoint, WaypointTraitSymbols},
+    STResult,
+};
+use chrono::{DateTime, Duration, Utc};
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::{Ipv4Addr, SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+const SYSTEM: &str = "X1-ZA40";
+const HEADQUARTERS: &str = "X1-ZA40-15970B";
+const CHARTED_ON: &str = "2023-05-13T17:48:46.579Z";
+
+/// The number of credits agents start with.
+const STARTING_CREDITS: i32 = 100_000;
+
+/// The length of the cooldown after extracting or surveying, in seconds.
+const COOLDOWN_SECONDS: i64 = 70;
+
+/// How long surveys can be used for, in minutes.
+const SURVEY_MINUTES: i64 = 15;
+
+/// How much each unit traded moves a price, as a fraction of its base price.
+const PRICE_IMPACT: f64 = 0.002;
+
+/// How long it takes for half of a price's deviation from its base price to recover, in minutes.
+const PRICE_HALF_LIFE_MINUTES: f64 = 60.;
+
+/// The symbol, name and description of every trade good in the universe.
+const TRADE_GOODS: &[(&str, &str, &str)] = &[
+    ("ANTIMATTER", "Antimatter", "A highly valuable and dangerous substance used for advanced propulsion and weapons systems."),
+    ("FUEL", "Fuel", "High-energy fuel used in spacecraft propulsion systems to enable long-distance space travel."),
+    ("FOOD", "Food", "Nutritious and tasty food, essential for the survival and well-being of crew and passengers."),
+    ("MACHINERY", "Machinery", "Heavy equipment used in construction, mining and manufacturing."),
+    ("IRON", "Iron", "A versatile and widely used metal, refined from iron ore."),
+    ("IRON_ORE", "Iron Ore", "A common and versatile mineral used in the production of steel and other metals."),
+    ("COPPER_ORE", "Copper Ore", "A naturally occurring mineral used in the production of copper metal and alloys."),
+    ("ALUMINUM_ORE", "Aluminum Ore", "A lightweight and versatile mineral used in the production of aluminum metal."),
+    ("QUARTZ_SAND", "Quartz Sand", "A granular material made primarily of quartz, used in the production of glass and electronics."),
+    ("SILICON_CRYSTALS", "Silicon Crystals", "Crystalline silicon, used in the production of semiconductors and solar panels."),
+    ("ICE_WATER", "Ice Water", "Frozen water harvested from asteroids, used for life support and fuel production."),
+];
+
+/// How a trade good is traded at a market.
+#[derive(Debug, Clone, Copy)]
+enum Trade {
+    Import,
+    Export,
+    Exchange,
+}
+
+/// A trade good's symbol, how it is traded, and its purchase and sell prices.
+type TradeGood = (&'static str, Trade, i64, i64);
+
+/// The trade volume and goods traded at each market.
+const MARKETS: &[(&str, i64, &[TradeGood])] = &[
+    (
+        "X1-ZA40-15970B",
+        100,
+        &[
+            ("IRON_ORE", Trade::Import, 48, 40),
+            ("COPPER_ORE", Trade::Import, 62, 52),
+            ("ALUMINUM_ORE", Trade::Import, 70, 58),
+            ("FOOD", Trade::Export, 28, 22),
+            ("MACHINERY", Trade::Export, 180, 150),
+            ("FUEL", Trade::Exchange, 3, 2),
+        ],
+    ),
+    (
+        "X1-ZA40-97262C",
+        100,
+        &[
+            ("ICE_WATER", Trade::Exchange, 14, 10),
+            ("QUARTZ_SAND", Trade::Exchange, 22, 18),
+            ("SILICON_CRYSTALS", Trade::Exchange, 36, 30),
+            ("FUEL", Trade::Exchange, 3, 2),
+        ],
+    ),
+    (
+        "X1-ZA40-68707C",
+        10,
+        &[
+            ("IRON_ORE", Trade::Import, 52, 44),
+            ("QUARTZ_SAND", Trade::Import, 26, 21),
+            ("IRON", Trade::Export, 95, 80),
+            ("FUEL", Trade::Exchange, 3, 2),
+        ],
+    ),
+];
+
+/// The ship types sold at each shipyard.
+const SHIPYARDS: &[(&str, &[ShipType])] = &[(
+    "X1-ZA40-68707C",
+    &[
+        ShipType::ShipRefiningFreighter,
+        ShipType::ShipProbe,
+        ShipType::ShipOreHound,
+        ShipType::ShipMiningDrone,
+    ],
+)];
+
+/// The resources that can be extracted from asteroid fields.
+const DEPOSITS: &[Deposit] = &[
+    Deposit::IronOre,
+    Deposit::QuartzSand,
+    Deposit::IronOre,
+    Deposit::CopperOre,
+    Deposit::SiliconCrystals,
+    Deposit::AluminumOre,
+    Deposit::IceWater,
+];
+
+/// A mock `SpaceTraders API` running in the background.
+///
+/// The server is shut down when it is dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    address: SocketAddr,
+    clock: Arc<dyn Clock>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Starts a server that uses the system time.
+    pub async fn start() -> STResult<Self> {
+        Self::start_with_clock(Arc::new(SystemClock)).await
+    }
+
+    /// Starts a server that reads the time from the given clock.
+    pub async fn start_with_clock(clock: Arc<dyn Clock>) -> STResult<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+
+        let state = Arc::new(Mutex::new(MockState::new(clock.clone())));
+        let make_service = make_service_fn(move |_| {
+            let state = state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+        });
+
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let server = Server::from_tcp(listener)
+            .map_err(std::io::Error::other)?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+        tokio::spawn(server);
+
+        Ok(Self {
+            address,
+            clock,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// The URL to pass to [set_base_url](SpaceTradersClient::set_base_url).
+    pub fn base_url(&self) -> String {
+        format!("http://{}/v2", self.address)
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Creates a client that sends its requests to this server.
+    ///
+    /// The client isn't rate limited, and its [Scheduler] uses the server's clock.
+    pub fn client(&self) -> SpaceTradersClient {
+        let mut client = SpaceTradersClient::new();
+        client.set_base_url(self.base_url());
+        client.set_rate_limiter(RateLimiter::new(1_000., 1_000));
+        client.set_scheduler(Scheduler::new(self.clock.clone()));
+
+        client
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<MockState>>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let (parts, body) = request.into_parts();
+    let token = parts
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(String::from);
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+
+    let result =
+        state
+            .lock()
+            .unwrap()
+            .route(&parts.method, parts.uri.path(), token.as_deref(), &body);
+    let (status, body) = match result {
+        Ok((status, data)) => (status, json!({ "data": data })),
+        Err(error) => (error.status, error.to_json()),
+    };
+
+    Ok(Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap())
+}
+
+/// An error response, in the same format as the `SpaceTraders API`.
+#[derive(Debug)]
+struct MockError {
+    status: StatusCode,
+    code: i32,
+    message: String,
+    data: Option<Value>,
+}
+
+impl MockError {
+    fn new(status: StatusCode, code: i32, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    fn bad_request(code: i32, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, 404, message)
+    }
+
+    fn insufficient_funds(credits: i32, cost: i64) -> Self {
+        Self::bad_request(
+            4600,
+            format!(
+                "Agent has insufficient funds. Available: {}, required: {}.",
+                credits, cost
+            ),
+        )
+    }
+
+    fn to_json(&self) -> Value {
+        let mut error = json!({ "code": self.code, "message": self.message });
+        if let Some(data) = &self.data {
+            error["data"] = data.clone();
+        }
+
+        json!({ "error": error })
+    }
+}
+
+type MockResult<T = (StatusCode, Value)> = Result<T, MockError>;
+
+/// The state of the whole mock universe.
+#[derive(Debug)]
+struct MockState {
+    clock: Arc<dyn Clock>,
+    ids: Ids,
+    system: System,
+    waypoints: Vec<Waypoint>,
+    markets: Vec<Market>,
+    shipyards: Vec<Shipyard>,
+    /// Registered agents, by token.
+    agents: HashMap<String, AgentState>,
+    /// Surveys that can be used for extraction, by signature.
+    surveys: HashMap<String, Survey>,
+    extractions: usize,
+    prices: PriceDynamics,
+}
+
+#[derive(Debug)]
+struct AgentState {
+    agent: Agent,
+    contracts: Vec<Contract>,
+    ships: Vec<Ship>,
+    cooldowns: HashMap<String, DateTime<Utc>>,
+}
+
+/// Generates deterministic IDs that look like the ones used by the API.
+#[derive(Debug, Default)]
+struct Ids(u64);
+
+impl Ids {
+    fn next(&mut self) -> String {
+        self.0 += 1;
+        format!("clmock{:019}", self.0)
+    }
+}
+
+impl MockState {
+    fn new(clock: Arc<dyn Clock>) -> Self {
+        let waypoints = waypoints();
+        let system = from_json(json!({
+            "symbol": SYSTEM,
+            "sectorSymbol": "X1",
+            "type": "RED_STAR",
+            "x": -5,
+            "y": 23,
+            "waypoints": waypoints
+                .iter()
+                .map(|waypoint| json!({
+                    "symbol": waypoint.symbol,
+                    "type": waypoint.waypoint_type,
+                    "x": waypoint.x,
+                    "y": waypoint.y,
+                }))
+                .collect::<Vec<_>>(),
+            "factions": [{ "symbol": "COSMIC" }],
+        }));
+
+        Self {
+            clock,
+            ids: Ids::default(),
+            system,
+            waypoints,
+            markets: MARKETS
+                .iter()
+                .map(|(symbol, trade_volume, goods)| market(symbol, *trade_volume, goods))
+                .collect(),
+            shipyards: SHIPYARDS
+                .iter()
+                .map(|(symbol, ship_types)| shipyard(symbol, ship_types))
+                .collect(),
+            agents: HashMap::new(),
+            surveys: HashMap::new(),
+            extractions: 0,
+            prices: PriceDynamics::default(),
+        }
+    }
+
+    fn route(
+        &mut self,
+        method: &Method,
+        path: &str,
+        token: Option<&str>,
+        body: &[u8],
+    ) -> MockResult {
+        let path = path.strip_prefix("/v2").unwrap_or(path);
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        match (method, segments.as_slice()) {
+            (&Method::POST, ["register"]) => self.register(parse(body)?),
+            (&Method::GET, ["systems", system]) => {
+                self.authenticate(token)?;
+                if *system != SYSTEM {
+                    return Err(MockError::not_found(format!(
+                        "System {} not found.",
+                        system
+                    )));
+                }
+
+                ok(&self.system)
+            }
+            (&Method::GET, ["systems", system, "waypoints", waypoint]) => {
+                self.authenticate(token)?;
+
+                ok(self.waypoint(system, waypoint)?)
+            }
+            (&Method::GET, ["systems", system, "waypoints", waypoint, "market"]) => {
+                self.view_market(token, system, waypoint)
+            }
+            (&Method::GET, ["systems", system, "waypoints", waypoint, "shipyard"]) => {
+                self.view_shipyard(token, system, waypoint)
+            }
+            (&Method::POST, ["my", "ships"]) => self.buy_ship(token, parse(body)?),
+            (&Method::POST, ["my", "ships", ship, action]) => match *action {
+                "dock" => self.set_status(token, ship, ShipStatus::Docked),
+                "orbit" => self.set_status(token, ship, ShipStatus::InOrbit),
+                "extract" => self.extract(token, ship, parse_optional(body)?),
+                "navigate" => self.navigate(token, ship, parse(body)?),
+                "survey" => self.survey(token, ship),
+                "refuel" => self.refuel(token, ship),
+                "jettison" => self.jettison(token, ship, parse(body)?),
+                "sell" => self.trade(token, ship, TransactionType::Sell, parse(body)?),
+                "purchase" => self.trade(token, ship, TransactionType::Purchase, parse(body)?),
+                _ => Err(MockError::not_found(format!("Route {} not found.", path))),
+            },
+            (&Method::POST, ["my", "contracts", id, action]) => match *action {
+                "accept" => self.accept_contract(token, id),
+                "deliver" => self.deliver_contract(token, id, parse(body)?),
+                "fulfill" => self.fulfill_contract(token, id),
+                _ => Err(MockError::not_found(format!("Route {} not found.", path))),
+            },
+            _ => Err(MockError::not_found(format!("Route {} not found.", path))),
+        }
+    }
+
+    fn register(&mut self, request: RegisterRequest) -> MockResult {
+        let symbol = request.symbol.to_uppercase();
+        if !(3..=14).contains(&symbol.len()) {
+            return Err(MockError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                422,
+                "Agent symbol must be between 3 and 14 characters.",
+            ));
+        }
+        if request.faction != "COSMIC" {
+            return Err(MockError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                422,
+                format!("Faction {} is not available.", request.faction),
+            ));
+        }
+        if self
+            .agents
+            .values()
+            .any(|state| state.agent.symbol == symbol)
+        {
+            return Err(MockError::new(
+                StatusCode::CONFLICT,
+                4111,
+                format!("Agent symbol {} has already been claimed.", symbol),
+            ));
+        }
+
+        let now = self.clock.now();
+        let agent: Agent = from_json(json!({
+            "accountId": self.ids.next(),
+            "symbol": symbol,
+            "headquarters": HEADQUARTERS,
+            "credits": STARTING_CREDITS,
+        }));
+        let contract: Contract = from_json(json!({
+            "id": self.ids.next(),
+            "factionSymbol": "COSMIC",
+            "type": "PROCUREMENT",
+            "terms": {
+                "deadline": now + Duration::days(7),
+                "payment": { "onAccepted": 100_280, "onFulfilled": 401_120 },
+                "deliver": [{
+                    "tradeSymbol": "IRON_ORE",
+                    "destinationSymbol": HEADQUARTERS,
+                    "unitsRequired": 10_900,
+                    "unitsFulfilled": 0,
+                }],
+            },
+            "accepted": false,
+            "fulfilled": false,
+            "expiration": now + Duration::days(3),
+        }));
+
+        let headquarters = self.waypoint(SYSTEM, HEADQUARTERS)?;
+        let mut ship = build_ship(
+            &ShipType::ShipCommandFrigate,
+            &format!("{}-1", symbol),
+            headquarters,
+            now,
+        );
+        add_cargo(&mut ship.cargo, "ANTIMATTER", 15)?;
+
+        let token = format!("mock-token-{}", self.ids.next());
+        let data = json!({
+            "token": token,
+            "agent": agent,
+            "contract": contract,
+            "faction": faction(),
+            "ship": ship,
+        });
+
+        self.agents.insert(
+            token,
+            AgentState {
+                agent,
+                contracts: vec![contract],
+                ships: vec![ship],
+                cooldowns: HashMap::new(),
+            },
+        );
+
+        Ok((StatusCode::CREATED, data))
+    }
+
+    fn view_market(&mut self, token: Option<&str>, system: &str, waypoint: &str) -> MockResult {
+        self.waypoint(system, waypoint)?;
+        let present = self.authenticate(token)?.has_ship_at(waypoint);
+
+        let market = find_market(&mut self.markets, waypoint)?;
+        self.prices.update(market, self.clock.now());
+        let mut market = market.clone();
+
+        // Prices are only visible to agents with a ship at the market
+        if !present {
+            market.trade_goods = None;
+            market.transactions = None;
+        }
+
+        ok(&market)
+    }
+
+    fn view_shipyard(&mut self, token: Option<&str>, system: &str, waypoint: &str) -> MockResult {
+        self.waypoint(system, waypoint)?;
+        let present = self.authenticate(token)?.has_ship_at(waypoint);
+
+        let mut shipyard = self
+            .shipyards
+            .iter()
+            .find(|shipyard| shipyard.symbol == waypoint)
+            .cloned()
+            .ok_or_else(|| shipyard_not_found(waypoint))?;
+
+        // Ships for sale are only visible to agents with a ship at the shipyard
+        if !present {
+            shipyard.ships = None;
+            shipyard.transactions = None;
+        }
+
+        ok(&shipyard)
+    }
+
+    fn buy_ship(&mut self, token: Option<&str>, request: BuyShipRequest) -> MockResult {
+        let now = self.clock.now();
+        let waypoint = find_waypoint(&self.waypoints, &request.waypoint_symbol)?;
+        let shipyard = self
+            .shipyards
+            .iter_mut()
+            .find(|shipyard| shipyard.symbol == request.waypoint_symbol)
+            .ok_or_else(|| shipyard_not_found(&request.waypoint_symbol))?;
+        let state = authenticate(&mut self.agents, token, now)?;
+
+        if !state.has_ship_at(&request.waypoint_symbol) {
+            return Err(MockError::bad_request(
+                4222,
+                format!(
+                    "Agent does not have a ship at {}, so ships can't be purchased there.",
+                    request.waypoint_symbol
+                ),
+            ));
+        }
+        let price = shipyard
+            .ships
+            .iter()
+            .flatten()
+            .find(|ship| ship.type_ == request.ship_type)
+            .map(|ship| ship.purchase_price)
+            .ok_or_else(|| {
+                MockError::bad_request(
+                    4241,
+                    format!(
+                        "Shipyard {} does not sell ships of type {:?}.",
+                        request.waypoint_symbol, request.ship_type
+                    ),
+                )
+            })?;
+        if state.agent.credits < price {
+            return Err(MockError::insufficient_funds(
+                state.agent.credits,
+                price.into(),
+            ));
+        }
+
+        let ship_symbol = format!("{}-{:X}", state.agent.symbol, state.ships.len() + 1);
+        let ship = build_ship(&request.ship_type, &ship_symbol, waypoint, now);
+        let transaction = ShipyardTransaction {
+            waypoint_symbol: waypoint.symbol.clone(),
+            ship_symbol: ship.symbol.clone(),
+            price: LowerBoundInt::new(price.into()).unwrap(),
+            agent_symbol: state.agent.symbol.clone(),
+            timestamp: now,
+        };
+
+        state.agent.credits -= price;
+        state.ships.push(ship.clone());
+        shipyard
+            .transactions
+            .get_or_insert_with(Vec::new)
+            .push(transaction.clone());
+
+        Ok((
+            StatusCode::CREATED,
+            json!({ "agent": state.agent, "ship": ship, "transaction": transaction }),
+        ))
+    }
+
+    fn set_status(&mut self, token: Option<&str>, ship: &str, status: ShipStatus) -> MockResult {
+        let now = self.clock.now();
+        let state = authenticate(&mut self.agents, token, now)?;
+        let ship = find_ship(&mut state.ships, ship)?;
+
+        if ship.nav.status == ShipStatus::InTransit {
+            return Err(in_transit(ship, now));
+        }
+        ship.nav.status = status;
+
+        ok(json!({ "nav": ship.nav }))
+    }
+
+    fn extract(
+        &mut self,
+        token: Option<&str>,
+        ship: &str,
+        request: Option<ExtractRequest>,
+    ) -> MockResult {
+        let now = self.clock.now();
+        let state = authenticate(&mut self.agents, token, now)?;
+        let ship = find_ship(&mut state.ships, ship)?;
+
+        require_status(ship, ShipStatus::InOrbit, now)?;
+        check_cooldown(&state.cooldowns, ship, now)?;
+        let waypoint = find_waypoint(&self.waypoints, &ship.nav.waypoint_symbol)?;
+        require_deposits(waypoint)?;
+
+        let strength: i64 = ship
+            .mounts
+            .iter()
+            .filter(|mount| {
+                matches!(
+                    mount.symbol,
+                    MountSymbol::MountMiningLaserI
+                        | MountSymbol::MountMiningLaserII
+                        | MountSymbol::MountMiningLaserIII
+                )
+            })
+            .map(|mount| *mount.strength)
+            .sum();
+        if strength == 0 {
+            return Err(MockError::bad_request(
+                4243,
+                format!("Ship {} does not have a mining laser mount.", ship.symbol),
+            ));
+        }
+
+        let deposits = match request.map(|request| request.survey) {
+            Some(survey) => {
+                let survey = self
+                    .surveys
+                    .get(&*survey.signature)
+                    .filter(|survey| survey.symbol == ship.nav.waypoint_symbol)
+                    .filter(|survey| survey.expiration > now)
+                    .ok_or_else(|| {
+                        MockError::bad_request(
+                            4221,
+                            format!(
+                                "Survey {} is no longer valid at {}.",
+                                survey.signature, ship.nav.waypoint_symbol
+                            ),
+                        )
+                    })?;
+
+                survey
+                    .deposits
+                    .iter()
+                    .map(|deposit| deposit.symbol)
+                    .collect()
+            }
+            None => DEPOSITS.to_vec(),
+        };
+
+        let free = *ship.cargo.capacity - *ship.cargo.units;
+        if free == 0 {
+            return Err(MockError::bad_request(
+                4228,
+                format!("Ship {} has no space left in its cargo hold.", ship.symbol),
+            ));
+        }
+
+        // Yields vary between half and all of the mount strength
+        let deposit = deposits[self.extractions % deposits.len()];
+        let units = (strength / 2 + (self.extractions as i64 % (strength / 2 + 1))).min(free);
+        self.extractions += 1;
+
+        let trade_symbol = json!(deposit).as_str().unwrap().to_string();
+        add_cargo(&mut ship.cargo, &trade_symbol, units)?;
+        let cooldown = start_cooldown(&mut state.cooldowns, ship, now);
+
+        ok(json!({
+            "cooldown": cooldown,
+            "extraction": {
+                "shipSymbol": ship.symbol,
+                "yield": { "symbol": trade_symbol, "units": units },
+            },
+            "cargo": ship.cargo,
+        }))
+    }
+
+    fn navigate(
+        &mut self,
+        token: Option<&str>,
+        ship: &str,
+        request: NavigateRequest,
+    ) -> MockResult {
+        let now = self.clock.now();
+        let state = authenticate(&mut self.agents, token, now)?;
+        let ship = find_ship(&mut state.ships, ship)?;
+
+        require_status(ship, ShipStatus::InOrbit, now)?;
+        let destination = find_waypoint(&self.waypoints, &request.waypoint_symbol)?;
+        if destination.system_symbol != ship.nav.system_symbol {
+            return Err(MockError::bad_request(
+                4202,
+                format!(
+                    "Waypoint {} is not in the ship's current system.",
+                    destination.symbol
+                ),
+            ));
+        }
+        if destination.symbol == ship.nav.waypoint_symbol {
+            return Err(MockError::bad_request(
+                4204,
+                format!("Ship {} is already at {}.", ship.symbol, destination.symbol),
+            ));
+        }
+
+        let departure = find_waypoint(&self.waypoints, &ship.nav.waypoint_symbol)?;
+        let distance = distance((departure.x, departure.y), (destination.x, destination.y));
+
+        // Ships without fuel tanks, like probes, travel for free
+        let fuel = if *ship.fuel.capacity == 0 {
+            0
+        } else {
+            fuel_cost(distance, ship.nav.flight_mode)
+        };
+        if fuel > *ship.fuel.current {
+            return Err(MockError::bad_request(
+                4203,
+                format!(
+                    "Ship {} needs {} fuel to reach {}, but only has {}.",
+                    ship.symbol, fuel, destination.symbol, *ship.fuel.current
+                ),
+            ));
+        }
+
+        let seconds = travel_time(distance, *ship.engine.speed, ship.nav.flight_mode);
+        ship.fuel.current = non_negative(*ship.fuel.current - fuel);
+        ship.fuel.consumed.amount = non_negative(fuel);
+        ship.fuel.consumed.timestamp = now;
+        ship.nav.waypoint_symbol = destination.symbol.clone();
+        ship.nav.status = ShipStatus::InTransit;
+        ship.nav.route = from_json(json!({
+            "departure": location(departure),
+            "destination": location(destination),
+            "departureTime": now,
+            "arrival": now + Duration::seconds(seconds),
+        }));
+
+        ok(json!({ "fuel": ship.fuel, "nav": ship.nav }))
+    }
+
+    fn survey(&mut self, token: Option<&str>, ship: &str) -> MockResult {
+        let now = self.clock.now();
+        let state = authenticate(&mut self.agents, token, now)?;
+        let ship = find_ship(&mut state.ships, ship)?;
+
+        require_status(ship, ShipStatus::InOrbit, now)?;
+        check_cooldown(&state.cooldowns, ship, now)?;
+        let waypoint = find_waypoint(&self.waypoints, &ship.nav.waypoint_symbol)?;
+        require_deposits(waypoint)?;
+
+        let surveyors: Vec<_> = ship
+            .mounts
+            .iter()
+            .filter(|mount| {
+                matches!(
+                    mount.symbol,
+                    MountSymbol::MountSurveyorI
+                        | MountSymbol::MountSurveyorII
+                        | MountSymbol::MountSurveyorIII
+                )
+            })
+            .collect();
+        if surveyors.is_empty() {
+            return Err(MockError::bad_request(
+                4223,
+                format!("Ship {} does not have a surveyor mount.", ship.symbol),
+            ));
+        }
+
+        // Each surveyor creates one survey per point of strength
+        let mut surveys = Vec::new();
+        for surveyor in surveyors {
+            let known = surveyor.deposits.clone().unwrap_or_default();
+            for _ in 0..*surveyor.strength {
+                let offset = self.surveys.len();
+                let deposits: Vec<_> = DEPOSITS
+                    .iter()
+                    .cycle()
+                    .skip(offset)
+                    .take(3 + offset % 3)
+                    .filter(|deposit| known.contains(deposit))
+                    .map(|&symbol| SurveyDeposit { symbol })
+                    .collect();
+                let survey = Survey {
+                    signature: symbol(&format!("{}-{}", waypoint.symbol, self.ids.next())),
+                    symbol: waypoint.symbol.clone(),
+                    deposits,
+                    expiration: now + Duration::minutes(SURVEY_MINUTES),
+                    size: [SurveySize::Small, SurveySize::Moderate, SurveySize::Large][offset % 3],
+                };
+
+                self.surveys
+                    .insert(survey.signature.to_string(), survey.clone());
+                surveys.push(survey);
+            }
+        }
+        let cooldown = start_cooldown(&mut state.cooldowns, ship, now);
+
+        ok(json!({ "cooldown": cooldown, "surveys": surveys }))
+    }
+
+    fn refuel(&mut self, token: Option<&str>, ship: &str) -> MockResult {
+        let now = self.clock.now();
+        let state = authenticate(&mut self.agents, token, now)?;
+        let ship = find_ship(&mut state.ships, ship)?;
+
+        require_status(ship, ShipStatus::Docked, now)?;
+        let market = find_market(&mut self.markets, &ship.nav.waypoint_symbol)?;
+        self.prices.update(market, now);
+        let price = trade_good_price(market, "FUEL", TransactionType::Purchase)?;
+
+        let units = *ship.fuel.capacity - *ship.fuel.current;
+        let cost = units * price;
+        if i64::from(state.agent.credits) < cost {
+            return Err(MockError::insufficient_funds(state.agent.credits, cost));
+        }
+
+        state.agent.credits -= cost as i32;
+        ship.fuel.current = ship.fuel.capacity;
+        let transaction = record_transaction(
+            market,
+            ship,
+            "FUEL",
+            TransactionType::Purchase,
+            units,
+            price,
+            now,
+        );
+        self.prices
+            .record(market, "FUEL", TransactionType::Purchase, units, now);
+
+        ok(json!({ "agent": state.agent, "fuel": ship.fuel, "transaction": transaction }))
+    }
+
+    fn jettison(&mut self, token: Option<&str>, ship: &str, request: CargoRequest) -> MockResult {
+        let now = self.clock.now();
+        let state = authenticate(&mut self.agents, token, now)?;
+        let ship = find_ship(&mut state.ships, ship)?;
+
+        if ship.nav.status == ShipStatus::InTransit {
+            return Err(in_transit(ship, now));
+        }
+        remove_cargo(&mut ship.cargo, &request.symbol, request.units)?;
+
+        ok(json!({ "cargo": ship.cargo }))
+    }
+
+    fn trade(
+        &mut self,
+        token: Option<&str>,
+        ship: &str,
+        transaction_type: TransactionType,
+        request: CargoRequest,
+    ) -> MockResult {
+        let now = self.clock.now();
+        let state = authenticate(&mut self.agents, token, now)?;
+        let ship = find_ship(&mut state.ships, ship)?;
+
+        require_status(ship, ShipStatus::Docked, now)?;
+        let market = find_market(&mut self.markets, &ship.nav.waypoint_symbol)?;
+        self.prices.update(market, now);
+        let price = trade_good_price(market, &request.symbol, transaction_type)?;
+
+        let trade_volume = market
+            .trade_goods
+            .iter()
+            .flatten()
+            .find(|good| good.symbol == request.symbol)
+            .map(|good| *good.trade_volume)
+            .unwrap_or_default();
+        if request.units > trade_volume {
+            return Err(MockError::bad_request(
+                4604,
+                format!(
+                    "Market {} only trades {} unit(s) of {} per transaction.",
+                    market.symbol, trade_volume, request.symbol
+                ),
+            ));
+        }
+
+        let total = request.units * price;
+        match transaction_type {
+            TransactionType::Purchase => {
+                if i64::from(state.agent.credits) < total {
+                    return Err(MockError::insufficient_funds(state.agent.credits, total));
+                }
+                add_cargo(&mut ship.cargo, &request.symbol, request.units)?;
+                state.agent.credits -= total as i32;
+            }
+            TransactionType::Sell => {
+                remove_cargo(&mut ship.cargo, &request.symbol, request.units)?;
+                state.agent.credits += total as i32;
+            }
+        }
+        let transaction = record_transaction(
+            market,
+            ship,
+            &request.symbol,
+            transaction_type,
+            request.units,
+            price,
+            now,
+        );
+        self.prices.record(
+            market,
+            &request.symbol,
+            transaction_type,
+            request.units,
+            now,
+        );
+
+        ok(json!({ "agent": state.agent, "cargo": ship.cargo, "transaction": transaction }))
+    }
+
+    fn accept_contract(&mut self, token: Option<&str>, id: &str) -> MockResult {
+        let now = self.clock.now();
+        let state = authenticate(&mut self.agents, token, now)?;
+        let contract = find_contract(&mut state.contracts, id)?;
+
+        if contract.accepted {
+            return Err(MockError::bad_request(
+                4501,
+                format!("Contract {} has already been accepted.", id),
+            ));
+        }
+        if contract.expiration < now {
+            return Err(MockError::bad_request(
+                4502,
+                format!("Contract {} has expired.", id),
+            ));
+        }
+
+        contract.accepted = true;
+        state.agent.credits += contract.terms.payment.on_accepted;
+
+        ok(json!({ "agent": state.agent, "contract": contract }))
+    }
+
+    fn deliver_contract(
+        &mut self,
+        token: Option<&str>,
+        id: &str,
+        request: DeliverRequest,
+    ) -> MockResult {
+        let now = self.clock.now();
+        let state = authenticate(&mut self.agents, token, now)?;
+        let contract = find_contract(&mut state.contracts, id)?;
+        let ship = find_ship(&mut state.ships, &request.ship_symbol)?;
+
+        check_open(contract, now)?;
+        require_status(ship, ShipStatus::Docked, now)?;
+        let deliver = contract
+            .terms
+            .deliver
+            .iter_mut()
+            .find(|deliver| deliver.trade_symbol == request.trade_symbol)
+            .ok_or_else(|| {
+                MockError::bad_request(
+                    4508,
+                    format!("Contract {} does not require {}.", id, request.trade_symbol),
+                )
+            })?;
+        if deliver.destination_symbol != ship.nav.waypoint_symbol {
+            return Err(MockError::bad_request(
+                4510,
+                format!(
+                    "{} must be delivered to {}.",
+                    request.trade_symbol, deliver.destination_symbol
+                ),
+            ));
+        }
+        if i64::from(deliver.units_fulfilled) + request.units > i64::from(deliver.units_required) {
+            return Err(MockError::bad_request(
+                4509,
+                format!(
+                    "Contract {} only requires {} more unit(s) of {}.",
+                    id,
+                    deliver.units_required - deliver.units_fulfilled,
+                    request.trade_symbol
+                ),
+            ));
+        }
+
+        remove_cargo(&mut ship.cargo, &request.trade_symbol, request.units)?;
+        deliver.units_fulfilled += request.units as i32;
+
+        ok(json!({ "contract": contract, "cargo": ship.cargo }))
+    }
+
+    fn fulfill_contract(&mut self, token: Option<&str>, id: &str) -> MockResult {
+        let now = self.clock.now();
+        let state = authenticate(&mut self.agents, token, now)?;
+        let contract = find_contract(&mut state.contracts, id)?;
+
+        check_open(contract, now)?;
+        if contract
+            .terms
+            .deliver
+            .iter()
+            .any(|deliver| deliver.units_fulfilled < deliver.units_required)
+        {
+            return Err(MockError::bad_request(
+                4503,
+                format!("The terms of contract {} have not been met.", id),
+            ));
+        }
+
+        contract.fulfilled = true;
+        state.agent.credits += contract.terms.payment.on_fulfilled;
+
+        ok(json!({ "agent": state.agent, "contract": contract }))
+    }
+
+    fn authenticate(&mut self, token: Option<&str>) -> MockResult<&mut AgentState> {
+        authenticate(&mut self.agents, token, self.clock.now())
+    }
+
+    fn waypoint(&self, system: &str, waypoint: &str) -> MockResult<&Waypoint> {
+        find_waypoint(&self.waypoints, waypoint)
+            .ok()
+            .filter(|found| found.system_symbol == system)
+            .ok_or_else(|| MockError::not_found(format!("Waypoint {} not found.", waypoint)))
+    }
+}
+
+impl AgentState {
+    fn has_ship_at(&self, waypoint: &str) -> bool {
+        self.ships.iter().any(|ship| {
+            ship.nav.waypoint_symbol == waypoint && ship.nav.status != ShipStatus::InTransit
+        })
+    }
+}
+
+/// Finds the agent with the token, and moves its ships that have arrived into orbit.
+fn authenticate<'a>(
+    agents: &'a mut HashMap<String, AgentState>,
+    token: Option<&str>,
+    now: DateTime<Utc>,
+) -> MockResult<&'a mut AgentState> {
+    let state = token
+        .and_then(|token| agents.get_mut(token))
+        .ok_or_else(|| {
+            MockError::new(
+                StatusCode::UNAUTHORIZED,
+                401,
+                "Missing or invalid bearer token.",
+            )
+        })?;
+
+    for ship in &mut state.ships {
+        if ship.nav.status == ShipStatus::InTransit && ship.nav.route.arrival <= now {
+            ship.nav.status = ShipStatus::InOrbit;
+        }
+    }
+
+    Ok(state)
+}
+
+fn find_ship<'a>(ships: &'a mut [Ship], symbol: &str) -> MockResult<&'a mut Ship> {
+    ships
+        .iter_mut()
+        .find(|ship| ship.symbol == symbol)
+        .ok_or_else(|| MockError::not_found(format!("Ship {} not found.", symbol)))
+}
+
+fn find_contract<'a>(contracts: &'a mut [Contract], id: &str) -> MockResult<&'a mut Contract> {
+    contracts
+        .iter_mut()
+        .find(|contract| contract.id == id)
+        .ok_or_else(|| MockError::not_found(format!("Contract {} not found.", id)))
+}
+
+fn find_waypoint<'a>(waypoints: &'a [Waypoint], symbol: &str) -> MockResult<&'a Waypoint> {
+    waypoints
+        .iter()
+        .find(|waypoint| waypoint.symbol == symbol)
+        .ok_or_else(|| MockError::not_found(format!("Waypoint {} not found.", symbol)))
+}
+
+fn find_market<'a>(markets: &'a mut [Market], waypoint: &str) -> MockResult<&'a mut Market> {
+    markets
+        .iter_mut()
+        .find(|market| market.symbol == waypoint)
+        .ok_or_else(|| market_not_found(waypoint))
+}
+
+fn market_not_found(waypoint: &str) -> MockError {
+    MockError::not_found(format!("Market not found at waypoint {}.", waypoint))
+}
+
+fn shipyard_not_found(waypoint: &str) -> MockError {
+    MockError::not_found(format!("Shipyard not found at waypoint {}.", waypoint))
+}
+
+fn in_transit(ship: &Ship, now: DateTime<Utc>) -> MockError {
+    MockError::bad_request(
+        4214,
+        format!(
+            "Ship {} is currently in transit to {} and arrives in {} second(s).",
+            ship.symbol,
+            ship.nav.route.destination.symbol,
+            (ship.nav.route.arrival - now).num_seconds()
+        ),
+    )
+}
+
+fn require_status(ship: &Ship, status: ShipStatus, now: DateTime<Utc>) -> MockResult<()> {
+    match (ship.nav.status, status) {
+        (ShipStatus::InTransit, _) => Err(in_transit(ship, now)),
+        (ShipStatus::InOrbit, ShipStatus::Docked) => Err(MockError::bad_request(
+            4244,
+            format!("Ship {} must be docked for this action.", ship.symbol),
+        )),
+        (ShipStatus::Docked, ShipStatus::InOrbit) => Err(MockError::bad_request(
+            4236,
+            format!("Ship {} must be in orbit for this action.", ship.symbol),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn require_deposits(waypoint: &Waypoint) -> MockResult<()> {
+    let has_deposits = waypoint.traits.iter().any(|waypoint_trait| {
+        matches!(
+            waypoint_trait.symbol,
+            WaypointTraitSymbols::MineralDeposits | WaypointTraitSymbols::CommonMetalDeposits
+        )
+    });
+    if !has_deposits {
+        return Err(MockError::bad_request(
+            4205,
+            format!("Waypoint {} has no resources to extract.", waypoint.symbol),
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_cooldown(
+    cooldowns: &HashMap<String, DateTime<Utc>>,
+    ship: &Ship,
+    now: DateTime<Utc>,
+) -> MockResult<()> {
+    match cooldowns.get(&*ship.symbol) {
+        Some(&expiration) if expiration > now => {
+            let mut error = MockError::new(
+                StatusCode::CONFLICT,
+                4000,
+                format!(
+                    "Ship {} is still on cooldown until {}.",
+                    ship.symbol, expiration
+                ),
+            );
+            error.data = Some(json!({ "cooldown": cooldown(ship, expiration, now) }));
+
+            Err(error)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn start_cooldown(
+    cooldowns: &mut HashMap<String, DateTime<Utc>>,
+    ship: &Ship,
+    now: DateTime<Utc>,
+) -> Cooldown {
+    let expiration = now + Duration::seconds(COOLDOWN_SECONDS);
+    cooldowns.insert(ship.symbol.to_string(), expiration);
+
+    cooldown(ship, expiration, now)
+}
+
+fn cooldown(ship: &Ship, expiration: DateTime<Utc>, now: DateTime<Utc>) -> Cooldown {
+    Cooldown {
+        ship_symbol: ship.symbol.clone(),
+        total_seconds: non_negative(COOLDOWN_SECONDS),
+        remaining_seconds: non_negative((expiration - now).num_seconds().max(0)),
+        expiration,
+    }
+}
+
+fn check_open(contract: &Contract, now: DateTime<Utc>) -> MockResult<()> {
+    if !contract.accepted {
+        return Err(MockError::bad_request(
+            4501,
+            format!("Contract {} has not been accepted.", contract.id),
+        ));
+    }
+    if contract.fulfilled {
+        return Err(MockError::bad_request(
+            4504,
+            format!("Contract {} has already been fulfilled.", contract.id),
+        ));
+    }
+    if contract.terms.deadline < now {
+        return Err(MockError::bad_request(
+            4502,
+            format!("The deadline of contract {} has passed.", contract.id),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Tracks how far trading has pushed each price away from its base price.
+///
+/// Selling lowers a good's prices and purchasing raises them, in proportion to the units traded.
+/// Prices then recover towards their base price over time.
+#[derive(Debug, Default)]
+struct PriceDynamics {
+    /// The relative deviation from the base price, and when it was last updated, by market and
+    /// trade good.
+    deviations: HashMap<(String, String), (f64, DateTime<Utc>)>,
+}
+
+impl PriceDynamics {
+    /// Gets the current deviation of a price, after recovering since its last update.
+    fn deviation(&mut self, market: &str, trade_symbol: &str, now: DateTime<Utc>) -> f64 {
+        let key = (market.to_string(), trade_symbol.to_string());
+        let (deviation, updated) = self.deviations.entry(key).or_insert((0., now));
+
+        let minutes = (now - *updated).num_milliseconds().max(0) as f64 / 60_000.;
+        *deviation *= 0.5_f64.powf(minutes / PRICE_HALF_LIFE_MINUTES);
+        *updated = now;
+
+        *deviation
+    }
+
+    /// Sets the market's prices to their current values.
+    fn update(&mut self, market: &mut Market, now: DateTime<Utc>) {
+        let market_symbol = market.symbol.to_string();
+        for good in market.trade_goods.iter_mut().flatten() {
+            let Some((purchase_price, sell_price)) = base_prices(&market_symbol, &good.symbol)
+            else {
+                continue;
+            };
+
+            let factor = 1. + self.deviation(&market_symbol, &good.symbol, now);
+            let price = |base: i64| non_negative(((base as f64 * factor).round() as i64).max(1));
+            good.purchase_price = price(purchase_price);
+            good.sell_price = price(sell_price);
+        }
+    }
+
+    /// Moves the price of a trade good after a transaction.
+    fn record(
+        &mut self,
+        market: &mut Market,
+        trade_symbol: &str,
+        transaction_type: TransactionType,
+        units: i64,
+        now: DateTime<Utc>,
+    ) {
+        let impact = match transaction_type {
+            TransactionType::Purchase => units as f64 * PRICE_IMPACT,
+            TransactionType::Sell => -(units as f64) * PRICE_IMPACT,
+        };
+        let deviation = self.deviation(&market.symbol, trade_symbol, now);
+        self.deviations.insert(
+            (market.symbol.to_string(), trade_symbol.to_string()),
+            ((deviation + impact).clamp(-0.9, 3.), now),
+        );
+
+        self.update(market, now);
+    }
+}
+
+fn base_prices(market: &str, trade_symbol: &str) -> Option<(i64, i64)> {
+    MARKETS
+        .iter()
+        .find(|(symbol, ..)| *symbol == market)?
+        .2
+        .iter()
+        .find(|(symbol, ..)| *symbol == trade_symbol)
+        .map(|&(_, _, purchase_price, sell_price)| (purchase_price, sell_price))
+}
+
+fn trade_good_price(
+    market: &Market,
+    trade_symbol: &str,
+    transaction_type: TransactionType,
+) -> MockResult<i64> {
+    market
+        .trade_goods
+        .iter()
+        .flatten()
+        .find(|good| good.symbol == trade_symbol)
+        .map(|good| match transaction_type {
+            TransactionType::Purchase => *good.purchase_price,
+            TransactionType::Sell => *good.sell_price,
+        })
+        .ok_or_else(|| {
+            MockError::bad_request(
+                4601,
+                format!("Market {} does not trade {}.", market.symbol, trade_symbol),
+            )
+        })
+}
+
+fn record_transaction(
+    market: &mut Market,
+    ship: &Ship,
+    trade_symbol: &str,
+    transaction_type: TransactionType,
+    units: i64,
+    price_per_unit: i64,
+    now: DateTime<Utc>,
+) -> MarketTransaction {
+    let transaction = MarketTransaction {
+        waypoint_symbol: market.symbol.clone(),
+        ship_symbol: ship.symbol.clone(),
+        trade_symbol: symbol(trade_symbol),
+        transaction_type,
+        units: non_negative(units),
+        price_per_unit: non_negative(price_per_unit),
+        total_price: non_negative(units * price_per_unit),
+        timestamp: now,
+    };
+    market
+        .transactions
+        .get_or_insert_with(Vec::new)
+        .push(transaction.clone());
+
+    transaction
+}
+
+fn add_cargo(cargo: &mut Cargo, trade_symbol: &str, units: i64) -> MockResult<()> {
+    if units < 1 {
+        return Err(invalid_units(units));
+    }
+    if *cargo.units + units > *cargo.capacity {
+        return Err(MockError::bad_request(
+            4228,
+            format!(
+                "Cannot add {} unit(s) of {} to the cargo hold, which has a capacity of {}.",
+                units, trade_symbol, *cargo.capacity
+            ),
+        ));
+    }
+
+    match cargo
+        .inventory
+        .iter_mut()
+        .find(|item| item.symbol == trade_symbol)
+    {
+        Some(item) => item.units = LowerBoundInt::new(*item.units + units).unwrap(),
+        None => {
+            let (name, description) = trade_good(trade_symbol);
+            cargo.inventory.push(InventoryItem {
+                symbol: symbol(trade_symbol),
+                name: symbol(name),
+                description: symbol(description),
+                units: LowerBoundInt::new(units).unwrap(),
+            });
+        }
+    }
+    cargo.units = non_negative(*cargo.units + units);
+
+    Ok(())
+}
+
+fn remove_cargo(cargo: &mut Cargo, trade_symbol: &str, units: i64) -> MockResult<()> {
+    if units < 1 {
+        return Err(invalid_units(units));
+    }
+
+    let position = cargo
+        .inventory
+        .iter()
+        .position(|item| item.symbol == trade_symbol && *item.units >= units)
+        .ok_or_else(|| {
+            MockError::bad_request(
+                4219,
+                format!(
+                    "The cargo hold does not contain {} unit(s) of {}.",
+                    units, trade_symbol
+                ),
+            )
+        })?;
+
+    let remaining = *cargo.inventory[position].units - units;
+    if remaining == 0 {
+        cargo.inventory.remove(position);
+    } else {
+        cargo.inventory[position].units = LowerBoundInt::new(remaining).unwrap();
+    }
+    cargo.units = non_negative(*cargo.units - units);
+
+    Ok(())
+}
+
+fn invalid_units(units: i64) -> MockError {
+    MockError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        422,
+        format!("Units must be at least 1, but was {}.", units),
+    )
+}
+
+fn trade_good(trade_symbol: &str) -> (&'static str, &'static str) {
+    TRADE_GOODS
+        .iter()
+        .find(|(symbol, ..)| *symbol == trade_symbol)
+        .map(|&(_, name, description)| (name, description))
+        .unwrap_or(("Unknown", "An unknown trade good."))
+}
+
+#[derive(Debug, Deserialize)]
+struct RegisterRequest {
+    symbol: String,
+    faction: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BuyShipRequest {
+    ship_type: ShipType,
+    waypoint_symbol: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtractRequest {
+    survey: Survey,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NavigateRequest {
+    waypoint_symbol: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoRequest {
+    symbol: String,
+    units: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeliverRequest {
+    ship_symbol: String,
+    trade_symbol: String,
+    units: i64,
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> MockResult<T> {
+    serde_json::from_slice(body).map_err(|error| {
+        MockError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            422,
+            format!("Invalid request body: {}", error),
+        )
+    })
+}
+
+fn parse_optional<T: DeserializeOwned>(body: &[u8]) -> MockResult<Option<T>> {
+    if body.is_empty() {
+        return Ok(None);
+    }
+
+    parse(body).map(Some)
+}
+
+fn ok(data: impl Serialize) -> MockResult {
+    Ok((StatusCode::OK, json!(data)))
+}
+
+fn from_json<T: DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).unwrap()
+}
+
+fn symbol(value: &str) -> Symbol {
+    Symbol::new(value).unwrap()
+}
+
+fn non_negative(value: i64) -> NonNegative {
+    NonNegative::new(value).unwrap()
+}
+
+fn location(waypoint: &Waypoint) -> Value {
+    json!({
+        "symbol": waypoint.symbol,
+        "type": waypoint.waypoint_type,
+        "systemSymbol": waypoint.system_symbol,
+        "x": waypoint.x,
+        "y": waypoint.y,
+    })
+}
+
+fn faction() -> Value {
+    json!({
+        "symbol": "COSMIC",
+        "name": "Cosmic Engineers",
+        "description": "The Cosmic Engineers are a group of highly advanced scientists and engineers who seek to terraform and colonize new worlds, pushing the boundaries of technology and exploration.",
+        "headquarters": HEADQUARTERS,
+        "traits": [
+            {
+                "symbol": "INNOVATIVE",
+                "name": "Innovative",
+                "description": "Willing to try new and untested ideas. Sometimes able to come up with creative and original solutions to problems, and may be able to think outside the box. Sometimes at the forefront of technological or social change, and may be willing to take risks in order to advance the boundaries of human knowledge and understanding.",
+            },
+            {
+                "symbol": "BOLD",
+                "name": "Bold",
+                "description": "Unafraid to take risks and challenge the status quo. Sometimes willing to do things that others would not dare, and may be able to overcome obstacles and challenges that would be insurmountable for others. Sometimes able to inspire and motivate others to take bold action as well.",
+            },
+            {
+                "symbol": "VISIONARY",
+                "name": "Visionary",
+                "description": "Possessing a clear and compelling vision for the future. Sometimes able to see beyond the present and anticipate the needs and challenges of tomorrow. Sometimes able to inspire and guide others towards a better and brighter future, and may be willing to take bold and decisive action to make their vision a reality.",
+            },
+            {
+                "symbol": "CURIOUS",
+                "name": "Curious",
+                "description": "Possessing a strong desire to learn and explore. Sometimes interested in a wide range of topics and may be willing to take risks in order to satisfy their curiosity. Sometimes able to think outside the box and come up with creative solutions to challenges.",
+            },
+        ],
+    })
+}
+
+fn waypoint_trait(symbol: &str) -> Value {
+    let (name, description) = match symbol {
+        "OVERCROWDED" => ("Overcrowded", "A waypoint teeming with inhabitants, leading to cramped living conditions and a high demand for resources."),
+        "HIGH_TECH" => ("High-Tech", "A center of innovation and cutting-edge technology, driving progress and attracting skilled individuals from around the galaxy."),
+        "BUREAUCRATIC" => ("Bureaucratic", "A waypoint governed by complex regulations, red tape, and layers of administration, often leading to inefficiencies and frustration."),
+        "TEMPERATE" => ("Temperate", "A world with a mild climate and balanced ecosystem, providing a comfortable environment for a variety of life forms and supporting diverse industries."),
+        "MARKETPLACE" => ("Marketplace", "A thriving center of commerce where traders from across the galaxy gather to buy, sell, and exchange goods."),
+        "SHIPYARD" => ("Shipyard", "A bustling hub for the construction, repair, and sale of various spacecraft, from humble shuttles to mighty warships."),
+        "INDUSTRIAL" => ("Industrial", "A waypoint dominated by factories and refineries, producing goods for the rest of the system."),
+        "BARREN" => ("Barren", "A desolate world with little to no vegetation or water, and a harsh, inhospitable environment."),
+        "FROZEN" => ("Frozen", "An ice-covered world with frigid temperatures, providing unique opportunities for harvesting ice resources."),
+        "MINERAL_DEPOSITS" => ("Mineral Deposits", "Abundant mineral resources, attracting mining operations and providing valuable materials such as silicon and quartz crystals."),
+        "COMMON_METAL_DEPOSITS" => ("Common Metal Deposits", "A waypoint rich in common metals like iron, copper, and aluminum, essential for construction and manufacturing."),
+        _ => unreachable!("unknown waypoint trait {}", symbol),
+    };
+
+    json!({ "symbol": symbol, "name": name, "description": description })
+}
+
+fn waypoints() -> Vec<Waypoint> {
+    let waypoint = |symbol: &str,
+                    waypoint_type: &str,
+                    (x, y): (i32, i32),
+                    orbitals: &[&str],
+                    traits: &[&str]| {
+        from_json(json!({
+            "symbol": symbol,
+            "type": waypoint_type,
+            "systemSymbol": SYSTEM,
+            "x": x,
+            "y": y,
+            "orbitals": orbitals
+                .iter()
+                .map(|orbital| json!({ "symbol": orbital }))
+                .collect::<Vec<_>>(),
+            "faction": { "symbol": "COSMIC" },
+            "traits": traits.iter().map(|symbol| waypoint_trait(symbol)).collect::<Vec<_>>(),
+            "chart": { "submittedBy": "COSMIC", "submittedOn": CHARTED_ON },
+        }))
+    };
+
+    vec![
+        waypoint(
+            HEADQUARTERS,
+            "PLANET",
+            (10, 0),
+            &["X1-ZA40-69371X", "X1-ZA40-97262C", "X1-ZA40-11513D"],
+            &[
+                "OVERCROWDED",
+                "HIGH_TECH",
+                "BUREAUCRATIC",
+                "TEMPERATE",
+                "MARKETPLACE",
+            ],
+        ),
+        waypoint("X1-ZA40-69371X", "MOON", (10, 0), &[], &["BARREN"]),
+        waypoint(
+            "X1-ZA40-97262C",
+            "ORBITAL_STATION",
+            (10, 0),
+            &[],
+            &["MARKETPLACE"],
+        ),
+        waypoint("X1-ZA40-11513D", "MOON", (10, 0), &[], &["FROZEN"]),
+        waypoint(
+            "X1-ZA40-68707C",
+            "PLANET",
+            (-20, 40),
+            &[],
+            &["SHIPYARD", "MARKETPLACE", "INDUSTRIAL"],
+        ),
+        waypoint(
+            "X1-ZA40-99095A",
+            "ASTEROID_FIELD",
+            (-30, -50),
+            &[],
+            &["MINERAL_DEPOSITS", "COMMON_METAL_DEPOSITS"],
+        ),
+    ]
+}
+
+fn market(waypoint: &str, trade_volume: i64, goods: &[TradeGood]) -> Market {
+    let trade_goods = |trade: fn(Trade) -> bool| {
+        goods
+            .iter()
+            .filter(|good| trade(good.1))
+            .map(|good| {
+                let (name, description) = trade_good(good.0);
+                json!({ "symbol": good.0, "name": name, "description": description })
+            })
+            .collect::<Vec<_>>()
+    };
+
+    from_json(json!({
+        "symbol": waypoint,
+        "imports": trade_goods(|trade| matches!(trade, Trade::Import)),
+        "exports": trade_goods(|trade| matches!(trade, Trade::Export)),
+        "exchange": trade_goods(|trade| matches!(trade, Trade::Exchange)),
+        "transactions": [],
+        "tradeGoods": goods
+            .iter()
+            .map(|&(symbol, trade, purchase_price, sell_price)| json!({
+                "symbol": symbol,
+                "tradeVolume": trade_volume,
+                "supply": match trade {
+                    Trade::Import => "LIMITED",
+                    Trade::Export => "ABUNDANT",
+                    Trade::Exchange => "MODERATE",
+                },
+                "purchasePrice": purchase_price,
+                "sellPrice": sell_price,
+            }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+fn shipyard(waypoint: &str, ship_types: &[ShipType]) -> Shipyard {
+    from_json(json!({
+        "symbol": waypoint,
+        "shipTypes": ship_types
+            .iter()
+            .map(|ship_type| json!({ "type": ship_type }))
+            .collect::<Vec<_>>(),
+        "transactions": [],
+        "ships": ship_types.iter().map(blueprint).collect::<Vec<_>>(),
+    }))
+}
+
+/// The components of every ship type used in the universe.
+fn blueprint(ship_type: &ShipType) -> ShipyardShip {
+    let (name, description, price, frame, reactor, engine, modules, mounts): (
+        &str,
+        &str,
+        i32,
+        &str,
+        &str,
+        &str,
+        &[&str],
+        &[&str],
+    ) = match ship_type {
+        ShipType::ShipCommandFrigate => (
+            "Command Frigate",
+            "A versatile frigate used to command a fleet, with enough cargo space and equipment to mine and trade on its own.",
+            0,
+            "FRAME_FRIGATE",
+            "REACTOR_FISSION_I",
+            "ENGINE_ION_DRIVE_II",
+            &[
+                "MODULE_CARGO_HOLD_I",
+                "MODULE_CARGO_HOLD_I",
+                "MODULE_CREW_QUARTERS_I",
+                "MODULE_CREW_QUARTERS_I",
+                "MODULE_MINERAL_PROCESSOR_I",
+                "MODULE_JUMP_DRIVE_I",
+                "MODULE_WARP_DRIVE_I",
+            ],
+            &[
+                "MOUNT_SENSOR_ARRAY_I",
+                "MOUNT_MINING_LASER_I",
+                "MOUNT_SURVEYOR_I",
+            ],
+        ),
+        ShipType::ShipProbe => (
+            "Probe",
+            "A small, unmanned spacecraft used for exploration and reconnaissance.",
+            24_000,
+            "FRAME_PROBE",
+            "REACTOR_SOLAR_I",
+            "ENGINE_IMPULSE_DRIVE_I",
+            &[],
+            &[],
+        ),
+        ShipType::ShipMiningDrone => (
+            "Mining Drone",
+            "A small, unmanned spacecraft used for mining asteroids.",
+            80_000,
+            "FRAME_DRONE",
+            "REACTOR_CHEMICAL_I",
+            "ENGINE_IMPULSE_DRIVE_I",
+            &["MODULE_CARGO_HOLD_I", "MODULE_MINERAL_PROCESSOR_I"],
+            &["MOUNT_MINING_LASER_I"],
+        ),
+        ShipType::ShipOreHound => (
+            "Ore Hound",
+            "A rugged mining ship, equipped to find and extract valuable deposits.",
+            160_000,
+            "FRAME_MINER",
+            "REACTOR_FISSION_I",
+            "ENGINE_ION_DRIVE_I",
+            &[
+                "MODULE_CARGO_HOLD_I",
+                "MODULE_CARGO_HOLD_I",
+                "MODULE_CREW_QUARTERS_I",
+                "MODULE_MINERAL_PROCESSOR_I",
+            ],
+            &["MOUNT_MINING_LASER_II", "MOUNT_SURVEYOR_I"],
+        ),
+        ShipType::ShipRefiningFreighter => (
+            "Refining Freighter",
+            "A large freighter that refines ores into metals while hauling them to market.",
+            1_600_000,
+            "FRAME_HEAVY_FREIGHTER",
+            "REACTOR_FUSION_I",
+            "ENGINE_ION_DRIVE_I",
+            &[
+                "MODULE_CARGO_HOLD_I",
+                "MODULE_CARGO_HOLD_I",
+                "MODULE_CARGO_HOLD_I",
+                "MODULE_CREW_QUARTERS_I",
+                "MODULE_CREW_QUARTERS_I",
+                "MODULE_ORE_REFINERY_I",
+            ],
+            &["MOUNT_SENSOR_ARRAY_I", "MOUNT_MINING_LASER_I"],
+        ),
+        _ => unreachable!("the mock universe has no {:?}", ship_type),
+    };
+
+    from_json(json!({
+        "type": ship_type,
+        "name": name,
+        "description": description,
+        "purchasePrice": price,
+        "frame": component(frame),
+        "reactor": component(reactor),
+        "engine": component(engine),
+        "modules": modules.iter().map(|symbol| component(symbol)).collect::<Vec<_>>(),
+        "mounts": mounts.iter().map(|symbol| component(symbol)).collect::<Vec<_>>(),
+    }))
+}
+
+fn component(symbol: &str) -> Value {
+    let requirements = |power: i32, crew: i32| json!({ "power": power, "crew": crew });
+    let frame = |name: &str, description: &str, slots: i32, points: i32, fuel: i32, power, crew| {
+        json!({
+            "symbol": symbol, "name": name, "description": description, "condition": 100,
+            "moduleSlots": slots, "mountingPoints": points, "fuelCapacity": fuel,
+            "requirements": requirements(power, crew),
+        })
+    };
+    let reactor = |name: &str, description: &str, output: i32, crew: i32| {
+        json!({
+            "symbol": symbol, "name": name, "description": description, "condition": 100,
+            "powerOutput": output, "requirements": { "crew": crew },
+        })
+    };
+    let engine = |name: &str, description: &str, speed: i32, power, crew| {
+        json!({
+            "symbol": symbol, "name": name, "description": description, "condition": 100,
+            "speed": speed, "requirements": requirements(power, crew),
+        })
+    };
+    let module = |name: &str, description: &str, power: i32, crew: i32, slots: i32| {
+        json!({
+            "symbol": symbol, "name": name, "description": description,
+            "requirements": { "power": power, "crew": crew, "slots": slots },
+        })
+    };
+    let mount = |name: &str, description: &str, strength: i32, power, crew| {
+        json!({
+            "symbol": symbol, "name": name, "description": description, "strength": strength,
+            "requirements": requirements(power, crew),
+        })
+    };
+
+    match symbol {
+        "FRAME_FRIGATE" => frame("Frame Frigate", "A medium-sized, multi-purpose spacecraft, often used for combat, transport, or support operations.", 8, 5, 1200, 8, 25),
+        "FRAME_PROBE" => frame("Frame Probe", "A small, unmanned spacecraft used for exploration, reconnaissance, and scientific research.", 0, 0, 0, 1, 0),
+        "FRAME_DRONE" => frame("Frame Drone", "A small, unmanned spacecraft that can be used for a variety of tasks, such as surveillance, transportation, or mining.", 3, 2, 100, 1, 0),
+        "FRAME_MINER" => frame("Frame Miner", "A medium-sized spacecraft designed for mining operations, equipped with tools for extracting and processing valuable minerals.", 7, 4, 400, 3, 5),
+        "FRAME_HEAVY_FREIGHTER" => frame("Frame Heavy Freighter", "A large, heavily-armored spacecraft designed for the transportation of goods and materials.", 8, 4, 1200, 8, 20),
+        "REACTOR_FISSION_I" => reactor("Fission Reactor I", "A basic fission power reactor, used to generate electricity from nuclear fission reactions.", 31, 8),
+        "REACTOR_SOLAR_I" => reactor("Solar Reactor I", "A basic solar power reactor, used to generate electricity from solar energy.", 3, 0),
+        "REACTOR_CHEMICAL_I" => reactor("Chemical Reactor I", "A basic chemical power reactor, used to generate electricity from chemical reactions.", 15, 0),
+        "REACTOR_FUSION_I" => reactor("Fusion Reactor I", "A basic fusion power reactor, used to generate electricity from nuclear fusion reactions.", 40, 12),
+        "ENGINE_ION_DRIVE_II" => engine("Ion Drive II", "An advanced propulsion system that uses ionized particles to generate high-speed, low-thrust acceleration, with improved efficiency and performance.", 30, 6, 8),
+        "ENGINE_ION_DRIVE_I" => engine("Ion Drive I", "An advanced propulsion system that uses ionized particles to generate high-speed, low-thrust acceleration.", 15, 3, 4),
+        "ENGINE_IMPULSE_DRIVE_I" => engine("Impulse Drive I", "A basic low-energy propulsion system that generates thrust for interplanetary travel.", 10, 1, 0),
+        "MODULE_CARGO_HOLD_I" => {
+            let mut module = module("Cargo Hold", "A module that increases a ship's cargo capacity.", 1, 0, 1);
+            module["capacity"] = json!(30);
+            module
+        }
+        "MODULE_CREW_QUARTERS_I" => {
+            let mut module = module("Crew Quarters", "A module that provides living space and amenities for the crew.", 1, 2, 1);
+            module["capacity"] = json!(40);
+            module
+        }
+        "MODULE_MINERAL_PROCESSOR_I" => module("Mineral Processor", "Crushes and processes extracted minerals and ores into their component parts, filters out impurities, and containerizes them into raw storage units.", 1, 0, 2),
+        "MODULE_ORE_REFINERY_I" => module("Ore Refinery", "A specialized module that can refine raw ores into usable metals.", 1, 0, 1),
+        "MODULE_JUMP_DRIVE_I" => {
+            let mut module = module("Jump Drive I", "A basic antimatter jump drive that allows for instantaneous short-range interdimensional travel.", 4, 10, 1);
+            module["range"] = json!(500);
+            module
+        }
+        "MODULE_WARP_DRIVE_I" => {
+            let mut module = module("Warp Drive I", "A basic warp drive that allows for short-range interstellar travel.", 3, 2, 1);
+            module["range"] = json!(2000);
+            module
+        }
+        "MOUNT_SENSOR_ARRAY_I" => mount("Sensor Array I", "A basic sensor array that improves a ship's ability to detect and track other objects in space.", 1, 1, 0),
+        "MOUNT_MINING_LASER_I" => mount("Mining Laser I", "A basic mining laser that can be used to extract valuable minerals from asteroids and other space objects.", 10, 1, 0),
+        "MOUNT_MINING_LASER_II" => mount("Mining Laser II", "An advanced mining laser that is more efficient and effective at extracting valuable minerals from asteroids and other space objects.", 25, 2, 0),
+        "MOUNT_SURVEYOR_I" => {
+            let mut mount = mount("Surveyor I", "A basic survey probe that can be used to gather information about a mineral deposit.", 1, 1, 2);
+            mount["deposits"] = json!([
+                "QUARTZ_SAND", "SILICON_CRYSTALS", "PRECIOUS_STONES", "ICE_WATER", "AMMONIA_ICE",
+                "IRON_ORE", "COPPER_ORE", "SILVER_ORE", "ALUMINUM_ORE", "GOLD_ORE", "PLATINUM_ORE",
+            ]);
+            mount
+        }
+        _ => unreachable!("unknown ship component {}", symbol),
+    }
+}
+
+/// Builds a new ship of the given type, docked at the waypoint.
+fn build_ship(
+    ship_type: &ShipType,
+    ship_symbol: &str,
+    waypoint: &Waypoint,
+    now: DateTime<Utc>,
+) -> Ship {
+    let blueprint = blueprint(ship_type);
+    let role = match ship_type {
+        ShipType::ShipCommandFrigate => "COMMAND",
+        ShipType::ShipProbe => "SATELLITE",
+        ShipType::ShipMiningDrone | ShipType::ShipOreHound => "EXCAVATOR",
+        ShipType::ShipRefiningFreighter => "REFINERY",
+        _ => "HAULER",
+    };
+
+    let capacity = |symbol: ModuleSymbol| -> i64 {
+        blueprint
+            .modules
+            .iter()
+            .filter(|module| module.symbol == symbol)
+            .filter_map(|module| module.capacity.as_deref().copied())
+            .sum()
+    };
+    let required_crew: i32 = [
+        &blueprint.frame.requirements,
+        &blueprint.reactor.requirements,
+        &blueprint.engine.requirements,
+    ]
+    .into_iter()
+    .chain(blueprint.modules.iter().map(|module| &module.requirements))
+    .chain(blueprint.mounts.iter().map(|mount| &mount.requirements))
+    .filter_map(|requirements| requirements.crew)
+    .sum();
+
+    from_json(json!({
+        "symbol": ship_symbol,
+        "registration": { "name": ship_symbol, "factionSymbol": "COSMIC", "role": role },
+        "nav": {
+            "systemSymbol": waypoint.system_symbol,
+            "waypointSymbol": waypoint.symbol,
+            "route": {
+                "departure": location(waypoint),
+                "destination": location(waypoint),
+                "departureTime": now,
+                "arrival": now,
+            },
+            "status": "DOCKED",
+            "flightMode": "CRUISE",
+        },
+        "crew": {
+            "current": 0,
+            "required": required_crew,
+            "capacity": capacity(ModuleSymbol::ModuleCrewQuartersI),
+            "rotation": "STRICT",
+            "morale": 100,
+            "wages": 0,
+        },
+        "frame": blueprint.frame,
+        "reactor": blueprint.reactor,
+        "engine": blueprint.engine,
+        "modules": blueprint.modules,
+        "mounts": blueprint.mounts,
+        "cargo": {
+            "capacity": capacity(ModuleSymbol::ModuleCargoHoldI),
+            "units": 0,
+            "inventory": [],
+        },
+        "fuel": {
+            "current": blueprint.frame.fuel_capacity,
+            "capacity": blueprint.frame.fuel_capacity,
+            "consumed": { "amount": 0, "timestamp": now },
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scheduler::ManualClock, SpaceTradersError};
+
+    fn start() -> DateTime<Utc> {
+        "2023-05-17T04:18:05.930Z".parse().unwrap()
+    }
+
+    fn error_code(result: STResult<impl std::fmt::Debug>) -> i32 {
+        match result {
+            Err(SpaceTradersError::ResponseError(error)) => error.code,
+            result => panic!("expected an error response: {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_taken_callsigns() -> STResult<()> {
+        let server = MockServer::start().await?;
+
+        server.client().register_callsign("MOCK-1", None).await?;
+        let result = server.client().register_callsign("MOCK-1", None).await;
+        assert_eq!(error_code(result), 4111);
+
+        // Requests without a valid token are rejected
+        let client = server.client();
+        *client.token.write().unwrap() = Some("invalid".into());
+        let result = client.view_system(&symbol(SYSTEM)).await;
+        assert_eq!(error_code(result), 401);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mines_and_sells_in_virtual_time() -> STResult<()> {
+        let clock = Arc::new(ManualClock::new(start()));
+        let server = MockServer::start_with_clock(clock.clone()).await?;
+        let client = server.client();
+        client.register_callsign("MOCK-MINER", None).await?;
+        let ship = symbol("MOCK-MINER-1");
+        let field = symbol("X1-ZA40-99095A");
+
+        client.orbit_ship(&ship).await?;
+        let nav = client.navigate_ship(&ship, &field).await?;
+        assert_eq!(nav.status, ShipStatus::InTransit);
+        assert_eq!(
+            client.get_ship(&ship)?.fuel.current,
+            non_negative(1200 - 64)
+        );
+
+        // The ship can't act until it arrives
+        let result = client.extract_resources(&ship, None).await;
+        assert_eq!(error_code(result), 4214);
+
+        clock.advance_to(nav.route.arrival);
+        let (cooldown, extraction) = client.extract_resources(&ship, None).await?;
+        assert_eq!(extraction.yield_.symbol, "IRON_ORE");
+        assert_eq!(extraction.yield_.units, 5);
+        assert_eq!(*cooldown.remaining_seconds, COOLDOWN_SECONDS);
+
+        let result = client.extract_resources(&ship, None).await;
+        assert_eq!(error_code(result), 4000);
+
+        clock.advance(Duration::seconds(COOLDOWN_SECONDS));
+        let (_, surveys) = client.create_survey(&ship).await?;
+        clock.advance(Duration::seconds(COOLDOWN_SECONDS));
+        let (_, extraction) = client
+            .extract_resources(&ship, Some(surveys[0].clone()))
+            .await?;
+        assert!(surveys[0]
+            .deposits
+            .iter()
+            .any(|deposit| json!(deposit.symbol) == json!(extraction.yield_.symbol)));
+
+        // Sell the iron ore back at headquarters
+        let nav = client.navigate_ship(&ship, &symbol(HEADQUARTERS)).await?;
+        clock.advance_to(nav.route.arrival);
+        client.dock_ship(&ship).await?;
+        let transaction = client.sell_cargo(&ship, &symbol("IRON_ORE"), 5).await?;
+        assert_eq!(*transaction.total_price, 200);
+        assert_eq!(client.agent()?.credits, STARTING_CREDITS + 200);
+
+        let result = client.sell_cargo(&ship, &symbol("IRON_ORE"), 5).await;
+        assert_eq!(error_code(result), 4219);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn prices_move_with_trades_and_recover() -> STResult<()> {
+        let clock = Arc::new(ManualClock::new(start()));
+        let server = MockServer::start_with_clock(clock.clone()).await?;
+        let client = server.client();
+        client.register_callsign("MOCK-MARKET", None).await?;
+        let ship = symbol("MOCK-MARKET-1");
+        let iron_ore = symbol("IRON_ORE");
+
+        let transaction = client.purchase_cargo(&ship, &iron_ore, 40).await?;
+        assert_eq!(*transaction.price_per_unit, 48);
+
+        // Buying 40 units raises the price by 8%, and half of that recovers each hour
+        let transaction = client.purchase_cargo(&ship, &iron_ore, 1).await?;
+        assert_eq!(*transaction.price_per_unit, 52);
+        clock.advance(Duration::minutes(60));
+        let transaction = client.sell_cargo(&ship, &iron_ore, 1).await?;
+        assert_eq!(*transaction.price_per_unit, 42);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn buys_ships_and_delivers_contracts() -> STResult<()> {
+        let clock = Arc::new(ManualClock::new(start()));
+        let server = MockServer::start_with_clock(clock.clone()).await?;
+        let client = server.client();
+        client.register_callsign("MOCK-TRADER", None).await?;
+        let ship = symbol("MOCK-TRADER-1");
+        let shipyard = symbol("X1-ZA40-68707C");
+
+        // Ships are only for sale to agents with a ship at the shipyard
+        let result = client.buy_ship(ShipType::ShipProbe, shipyard.clone()).await;
+        assert_eq!(error_code(result), 4222);
+
+        client.orbit_ship(&ship).await?;
+        let nav = client.navigate_ship(&ship, &shipyard).await?;
+        clock.advance_to(nav.route.arrival);
+        let view = client.view_shipyard(&symbol(SYSTEM), &shipyard).await?;
+        assert_eq!(view.ships.unwrap().len(), 4);
+
+        client
+            .buy_ship(ShipType::ShipProbe, shipyard.clone())
+            .await?;
+        assert_eq!(client.agent()?.credits, STARTING_CREDITS - 24_000);
+        let probe = client.get_ship(&symbol("MOCK-TRADER-2"))?;
+        assert_eq!(probe.nav.waypoint_symbol, shipyard);
+        assert_eq!(probe.nav.status, ShipStatus::Docked);
+
+        // Deliver part of the contract's iron ore
+        let contract = client.contracts()?[0].clone();
+        client.accept_contract(contract.id.clone()).await?;
+        client.dock_ship(&ship).await?;
+        client.refuel_ship(&ship).await?;
+        let result = client.purchase_cargo(&ship, &symbol("IRON_ORE"), 40).await;
+        assert_eq!(error_code(result), 4604);
+        for _ in 0..4 {
+            client
+                .purchase_cargo(&ship, &symbol("IRON_ORE"), 10)
+                .await?;
+        }
+        let result = client
+            .deliver_contract(&contract.id, &ship, &symbol("IRON_ORE"), 40)
+            .await;
+        assert_eq!(error_code(result), 4510);
+
+        client.orbit_ship(&ship).await?;
+        let nav = client.navigate_ship(&ship, &symbol(HEADQUARTERS)).await?;
+        clock.advance_to(nav.route.arrival);
+        client.dock_ship(&ship).await?;
+        client
+            .deliver_contract(&contract.id, &ship, &symbol("IRON_ORE"), 40)
+            .await?;
+        assert_eq!(client.contracts()?[0].terms.deliver[0].units_fulfilled, 40);
+
+        let result = client.fulfill_contract(&contract.id).await;
+        assert_eq!(error_code(result), 4503);
+
+        Ok(())
+    }
+}
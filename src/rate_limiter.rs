@@ -0,0 +1,135 @@
+//! Keeps requests to the `SpaceTraders API` within its rate limit.
+//!
+//! The API allows 2 requests per second, with short bursts of up to 10 requests. Every request
+//! made by a [SpaceTradersClient](crate::space_traders_client::SpaceTradersClient) first waits
+//! for the client's [RateLimiter], so ships driven from multiple tasks share the same limit.
+//!
+//! # Example
+//! ```
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let limiter = RateLimiter::new(2., 10);
+//!
+//! // The first 10 requests can be made right away
+//! for _ in 0..10 {
+//!     limiter.acquire().await;
+//! }
+//! # })
+//! ```
+
+use crate::space_traders_client::SpaceTradersClient;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// The sustained number of requests per second allowed by the API.
+const REQUESTS_PER_SECOND: f64 = 2.;
+
+/// The number of requests allowed in a burst by the API.
+const BURST: u32 = 10;
+
+/// A token bucket rate limiter.
+///
+/// Waiting requests are let through in the order they called [acquire](RateLimiter::acquire).
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: u32,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Default for RateLimiter {
+    /// Creates a limiter matching the limits of the `SpaceTraders API`.
+    fn default() -> Self {
+        Self::new(REQUESTS_PER_SECOND, BURST)
+    }
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `requests_per_second` requests on average, with bursts of up to
+    /// `burst` requests.
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        Self {
+            requests_per_second,
+            burst: burst.max(1),
+            bucket: Mutex::new(Bucket {
+                tokens: burst.max(1) as f64,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn requests_per_second(&self) -> f64 {
+        self.requests_per_second
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Waits until a request can be made.
+    pub async fn acquire(&self) {
+        let mut bucket = self.bucket.lock().await;
+        self.refill(&mut bucket);
+
+        if bucket.tokens < 1. {
+            let wait = (1. - bucket.tokens) / self.requests_per_second;
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+            self.refill(&mut bucket);
+        }
+
+        bucket.tokens = (bucket.tokens - 1.).max(0.);
+    }
+
+    fn refill(&self, bucket: &mut Bucket) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+
+        bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second).min(self.burst as f64);
+        bucket.updated = now;
+    }
+}
+
+impl SpaceTradersClient {
+    /// Replaces the [RateLimiter] used for every request made by the client.
+    ///
+    /// This is only needed if the limits of the API change; by default the client already uses
+    /// the limits of the `SpaceTraders API`.
+    pub fn set_rate_limiter(&mut self, limiter: RateLimiter) {
+        self.rate_limiter = Arc::new(limiter);
+    }
+
+    /// Get a reference to the [RateLimiter] used by the client.
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn limits_requests_after_burst() {
+        let limiter = RateLimiter::new(20., 2);
+        let start = Instant::now();
+
+        // The burst goes through immediately
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(40));
+
+        // The next two have to wait for the bucket to refill
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+}
@@ -0,0 +1,322 @@
+//! Provides travel time and fuel estimates, and plans routes between waypoints in a system.
+//!
+//! The estimates follow the formulas used by the `SpaceTraders API`:
+//!
+//! - Travel time (in seconds) is `round(distance) * (multiplier / engine speed) + 15`, where the
+//!   multiplier depends on the [FlightMode].
+//! - Fuel usage is `round(distance)` when cruising or in stealth mode, twice that when burning,
+//!   and a single unit when drifting.
+//!
+//! # Example
+//! ```
+//! # use space_traders::prelude::*;
+//! let planner = RoutePlanner::new(
+//!     vec![
+//!         RouteNode::new("X1-ZA40-15970B".try_into().unwrap(), 10, 0, true),
+//!         RouteNode::new("X1-ZA40-68707C".try_into().unwrap(), -20, 40, true),
+//!     ],
+//!     30,
+//!     1200,
+//! );
+//!
+//! let route = planner
+//!     .plan(&"X1-ZA40-15970B".try_into().unwrap(), &"X1-ZA40-68707C".try_into().unwrap(), 1200)
+//!     .unwrap();
+//! assert_eq!(route.legs.len(), 1);
+//! assert_eq!(route.fuel, 50);
+//! ```
+
+use crate::conditional_types::Symbol;
+use std::collections::{BinaryHeap, HashMap};
+
+pub use crate::ship::FlightMode;
+
+impl FlightMode {
+    fn travel_multiplier(&self) -> f64 {
+        match self {
+            FlightMode::Cruise => 15.,
+            FlightMode::Drift => 150.,
+            FlightMode::Burn => 7.5,
+            FlightMode::Stealth => 30.,
+        }
+    }
+}
+
+/// The straight line distance between two points.
+pub fn distance(from: (i32, i32), to: (i32, i32)) -> f64 {
+    let dx = (to.0 - from.0) as f64;
+    let dy = (to.1 - from.1) as f64;
+
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// The time (in seconds) it takes to travel the given distance.
+pub fn travel_time(distance: f64, engine_speed: i64, flight_mode: FlightMode) -> i64 {
+    let distance = distance.round().max(1.);
+
+    (distance * (flight_mode.travel_multiplier() / engine_speed.max(1) as f64)).round() as i64 + 15
+}
+
+/// The amount of fuel used to travel the given distance.
+pub fn fuel_cost(distance: f64, flight_mode: FlightMode) -> i64 {
+    let distance = distance.round() as i64;
+    if distance == 0 {
+        return 0;
+    }
+
+    match flight_mode {
+        FlightMode::Cruise | FlightMode::Stealth => distance,
+        FlightMode::Burn => 2 * distance,
+        FlightMode::Drift => 1,
+    }
+}
+
+/// A waypoint that can be visited by the [RoutePlanner].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteNode {
+    pub symbol: Symbol,
+    pub x: i32,
+    pub y: i32,
+    /// Whether fuel can be bought at the waypoint.
+    pub refuel: bool,
+}
+
+impl RouteNode {
+    pub fn new(symbol: Symbol, x: i32, y: i32, refuel: bool) -> Self {
+        Self {
+            symbol,
+            x,
+            y,
+            refuel,
+        }
+    }
+}
+
+/// A single flight between two waypoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlightLeg {
+    pub from: Symbol,
+    pub to: Symbol,
+    pub flight_mode: FlightMode,
+    /// The travel time in seconds.
+    pub travel_time: i64,
+    pub fuel: i64,
+}
+
+/// A route made up of one or more [FlightLeg]s.
+///
+/// Every waypoint in between the legs has fuel for sale, and the ship is expected to refuel there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedRoute {
+    pub legs: Vec<FlightLeg>,
+    /// The total travel time in seconds.
+    pub travel_time: i64,
+    /// The total fuel used over all the legs.
+    pub fuel: i64,
+}
+
+/// Plans the fastest route between waypoints for a ship with the given engine speed and fuel
+/// capacity.
+#[derive(Debug, Clone)]
+pub struct RoutePlanner {
+    nodes: Vec<RouteNode>,
+    engine_speed: i64,
+    fuel_capacity: i64,
+    flight_mode: FlightMode,
+}
+
+impl RoutePlanner {
+    /// Creates a planner over the given waypoints that flies in [FlightMode::Cruise].
+    pub fn new(nodes: Vec<RouteNode>, engine_speed: i64, fuel_capacity: i64) -> Self {
+        Self {
+            nodes,
+            engine_speed,
+            fuel_capacity,
+            flight_mode: FlightMode::Cruise,
+        }
+    }
+
+    /// Sets the flight mode used for every leg.
+    pub fn set_flight_mode(&mut self, flight_mode: FlightMode) {
+        self.flight_mode = flight_mode;
+    }
+
+    pub fn flight_mode(&self) -> FlightMode {
+        self.flight_mode
+    }
+
+    pub fn fuel_capacity(&self) -> i64 {
+        self.fuel_capacity
+    }
+
+    pub fn nodes(&self) -> &[RouteNode] {
+        &self.nodes
+    }
+
+    /// Get the waypoint with the given symbol.
+    pub fn node(&self, symbol: &str) -> Option<&RouteNode> {
+        self.nodes.iter().find(|node| node.symbol == symbol)
+    }
+
+    /// Estimates a direct flight between two waypoints, ignoring fuel capacity.
+    ///
+    /// Returns `None` if either waypoint is unknown to the planner.
+    pub fn leg(&self, from: &str, to: &str) -> Option<FlightLeg> {
+        let from = self.node(from)?;
+        let to = self.node(to)?;
+
+        Some(self.leg_between(from, to))
+    }
+
+    fn leg_between(&self, from: &RouteNode, to: &RouteNode) -> FlightLeg {
+        let distance = distance((from.x, from.y), (to.x, to.y));
+
+        FlightLeg {
+            from: from.symbol.clone(),
+            to: to.symbol.clone(),
+            flight_mode: self.flight_mode,
+            travel_time: travel_time(distance, self.engine_speed, self.flight_mode),
+            fuel: fuel_cost(distance, self.flight_mode),
+        }
+    }
+
+    /// Plans the fastest route from `from` to `to`, starting with `current_fuel` in the tank.
+    ///
+    /// If the destination is out of range, the route will stop at waypoints that sell fuel along
+    /// the way. Returns `None` if either waypoint is unknown, or the destination can't be reached.
+    pub fn plan(&self, from: &Symbol, to: &Symbol, current_fuel: i64) -> Option<PlannedRoute> {
+        let start = self.nodes.iter().position(|node| node.symbol == *from)?;
+        let end = self.nodes.iter().position(|node| node.symbol == *to)?;
+
+        if start == end {
+            return Some(PlannedRoute {
+                legs: vec![],
+                travel_time: 0,
+                fuel: 0,
+            });
+        }
+
+        // Dijkstra's algorithm over the travel time, where the ship can only continue from the
+        // start and from waypoints where it can refuel
+        let mut best: HashMap<usize, i64> = HashMap::from([(start, 0)]);
+        let mut previous: HashMap<usize, usize> = HashMap::new();
+        let mut queue = BinaryHeap::from([(std::cmp::Reverse(0), start)]);
+
+        while let Some((std::cmp::Reverse(time), current)) = queue.pop() {
+            if current == end {
+                break;
+            }
+            if best.get(&current).is_some_and(|&best| time > best) {
+                continue;
+            }
+
+            let fuel = if current == start {
+                current_fuel.min(self.fuel_capacity)
+            } else {
+                self.fuel_capacity
+            };
+
+            for (next, node) in self.nodes.iter().enumerate() {
+                if next == current || (next != end && !node.refuel) {
+                    continue;
+                }
+
+                let leg = self.leg_between(&self.nodes[current], node);
+                if leg.fuel > fuel {
+                    continue;
+                }
+
+                let arrival = time + leg.travel_time;
+                if best.get(&next).is_none_or(|&best| arrival < best) {
+                    best.insert(next, arrival);
+                    previous.insert(next, current);
+                    queue.push((std::cmp::Reverse(arrival), next));
+                }
+            }
+        }
+
+        best.get(&end)?;
+
+        let mut path = vec![end];
+        while let Some(&prev) = previous.get(path.last().unwrap()) {
+            path.push(prev);
+        }
+        path.reverse();
+
+        let legs: Vec<FlightLeg> = path
+            .windows(2)
+            .map(|pair| self.leg_between(&self.nodes[pair[0]], &self.nodes[pair[1]]))
+            .collect();
+
+        Some(PlannedRoute {
+            travel_time: legs.iter().map(|leg| leg.travel_time).sum(),
+            fuel: legs.iter().map(|leg| leg.fuel).sum(),
+            legs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(symbol: &str) -> Symbol {
+        Symbol::new(symbol).unwrap()
+    }
+
+    #[test]
+    fn can_estimate_flights() {
+        assert_eq!(distance((0, 0), (3, 4)), 5.);
+
+        assert_eq!(travel_time(100., 30, FlightMode::Cruise), 65);
+        assert_eq!(travel_time(100., 30, FlightMode::Burn), 40);
+        assert_eq!(travel_time(100., 30, FlightMode::Drift), 515);
+        assert_eq!(travel_time(0., 30, FlightMode::Cruise), 16);
+
+        assert_eq!(fuel_cost(100., FlightMode::Cruise), 100);
+        assert_eq!(fuel_cost(100., FlightMode::Burn), 200);
+        assert_eq!(fuel_cost(100., FlightMode::Drift), 1);
+        assert_eq!(fuel_cost(0., FlightMode::Burn), 0);
+    }
+
+    #[test]
+    fn can_plan_routes_with_refuel_stops() {
+        let planner = RoutePlanner::new(
+            vec![
+                RouteNode::new(symbol("X1-A"), 0, 0, true),
+                RouteNode::new(symbol("X1-B"), 60, 0, true),
+                RouteNode::new(symbol("X1-C"), 120, 0, false),
+                RouteNode::new(symbol("X1-D"), 60, 10, false),
+            ],
+            30,
+            100,
+        );
+
+        // Direct flight is within range
+        let route = planner.plan(&symbol("X1-A"), &symbol("X1-B"), 100).unwrap();
+        assert_eq!(route.legs.len(), 1);
+        assert_eq!(route.fuel, 60);
+
+        // Needs to stop at X1-B to refuel (X1-D doesn't sell fuel)
+        let route = planner.plan(&symbol("X1-A"), &symbol("X1-C"), 100).unwrap();
+        assert_eq!(route.legs.len(), 2);
+        assert_eq!(route.legs[0].to, "X1-B");
+        assert_eq!(route.legs[1].to, "X1-C");
+        assert_eq!(route.fuel, 120);
+        assert_eq!(
+            route.travel_time,
+            route.legs.iter().map(|leg| leg.travel_time).sum::<i64>()
+        );
+
+        // Not enough fuel to leave
+        assert!(planner.plan(&symbol("X1-A"), &symbol("X1-B"), 10).is_none());
+
+        // Unknown waypoint
+        assert!(planner
+            .plan(&symbol("X1-A"), &symbol("X1-Z"), 100)
+            .is_none());
+
+        let route = planner.plan(&symbol("X1-A"), &symbol("X1-A"), 0).unwrap();
+        assert!(route.legs.is_empty());
+    }
+}
@@ -0,0 +1,440 @@
+//! Tracks when each ship will next be ready to act.
+//!
+//! Ships can't act while they are in transit or while their cooldown is active. The client records
+//! the arrival time and cooldown expiration from every response that contains them in its
+//! [Scheduler], which can then be used to wait for a ship to be ready, or to be notified as ships
+//! become ready.
+//!
+//! The scheduler reads the time from a [Clock]. By default this is the [SystemClock], but a
+//! [ManualClock] can be used instead so code that waits on ships can be tested without sleeping.
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let client = SpaceTradersClient::load_saved().unwrap();
+//! let ship = Symbol::new("TST-RS-04-1").unwrap();
+//!
+//! client.extract_resources(&ship, None).await.unwrap();
+//!
+//! // Waits until the ship's cooldown expires
+//! client.scheduler().wait_until_ready(&ship).await;
+//! client.extract_resources(&ship, None).await.unwrap();
+//! # })
+//! ```
+
+use crate::{
+    conditional_types::strings::Symbol,
+    ship::{Nav, ShipStatus},
+    space_traders_client::SpaceTradersClient,
+    SpaceTradersError,
+};
+use chrono::{DateTime, Duration, Utc};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, RwLock},
+};
+use tokio::sync::watch;
+
+/// How many seconds to wait before retrying a step that failed with a retryable error.
+const RETRY_DELAY_SECONDS: i64 = 5;
+
+/// A future returned by [Clock::sleep_until].
+pub type Sleep<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// A source of the current time.
+pub trait Clock: Debug + Send + Sync {
+    /// Get the current time.
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Waits until the clock reaches `deadline`.
+    fn sleep_until(&self, deadline: DateTime<Utc>) -> Sleep<'_>;
+}
+
+/// A [Clock] that follows the system time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep_until(&self, deadline: DateTime<Utc>) -> Sleep<'_> {
+        let duration = (deadline - Utc::now()).to_std().unwrap_or_default();
+
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A [Clock] that only moves when it is advanced.
+///
+/// Sleeping on the clock finishes as soon as it is advanced past the deadline, without waiting
+/// in real time.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: watch::Sender<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    /// Creates a clock starting at `now`.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: watch::channel(now).0,
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.now.send_modify(|now| *now += duration);
+    }
+
+    /// Moves the clock to `time`, if it is later than the current time.
+    pub fn advance_to(&self, time: DateTime<Utc>) {
+        self.now.send_modify(|now| *now = (*now).max(time));
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.borrow()
+    }
+
+    fn sleep_until(&self, deadline: DateTime<Utc>) -> Sleep<'_> {
+        let mut now = self.now.subscribe();
+
+        Box::pin(async move {
+            // The sender is owned by the clock, which outlives the future
+            while *now.borrow_and_update() < deadline {
+                let _ = now.changed().await;
+            }
+        })
+    }
+}
+
+/// A ship becoming ready to act.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShipReady {
+    pub ship_symbol: Symbol,
+    /// The time the ship's arrival and cooldown were both over.
+    pub ready_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Readiness {
+    arrival: Option<DateTime<Utc>>,
+    cooldown: Option<DateTime<Utc>>,
+}
+
+impl Readiness {
+    fn ready_at(&self) -> Option<DateTime<Utc>> {
+        self.arrival.max(self.cooldown)
+    }
+}
+
+/// Tracks the arrival and cooldown of every ship.
+#[derive(Debug)]
+pub struct Scheduler {
+    clock: Arc<dyn Clock>,
+    ships: RwLock<HashMap<Symbol, Readiness>>,
+    // Bumped whenever a ship's readiness changes, to wake up waiting tasks
+    updates: watch::Sender<u64>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemClock))
+    }
+}
+
+impl Scheduler {
+    /// Creates a scheduler reading the time from `clock`.
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            ships: Default::default(),
+            updates: watch::channel(0).0,
+        }
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Records the time the ship's cooldown expires.
+    pub fn record_cooldown(&self, ship_symbol: &Symbol, expiration: DateTime<Utc>) {
+        self.update(ship_symbol, |readiness| {
+            readiness.cooldown = Some(expiration)
+        });
+    }
+
+    /// Records the time the ship arrives at its destination.
+    pub fn record_arrival(&self, ship_symbol: &Symbol, arrival: DateTime<Utc>) {
+        self.update(ship_symbol, |readiness| readiness.arrival = Some(arrival));
+    }
+
+    /// Records the ship's arrival from its navigation info, if it is in transit.
+    pub fn record_nav(&self, ship_symbol: &Symbol, nav: &Nav) {
+        if nav.status == ShipStatus::InTransit {
+            self.record_arrival(ship_symbol, nav.route.arrival);
+        }
+    }
+
+    fn update(&self, ship_symbol: &Symbol, f: impl FnOnce(&mut Readiness)) {
+        f(self
+            .ships
+            .write()
+            .unwrap()
+            .entry(ship_symbol.clone())
+            .or_default());
+
+        self.updates.send_modify(|version| *version += 1);
+    }
+
+    /// Get the time the ship's cooldown expires, if one has been recorded.
+    pub fn cooldown_expiration(&self, ship_symbol: &Symbol) -> Option<DateTime<Utc>> {
+        self.readiness(ship_symbol).cooldown
+    }
+
+    /// Get the time the ship arrives at its destination, if one has been recorded.
+    pub fn arrival(&self, ship_symbol: &Symbol) -> Option<DateTime<Utc>> {
+        self.readiness(ship_symbol).arrival
+    }
+
+    /// Get the time the ship will be ready to act, if an arrival or cooldown has been recorded.
+    ///
+    /// This may be in the past if the ship is already ready.
+    pub fn ready_at(&self, ship_symbol: &Symbol) -> Option<DateTime<Utc>> {
+        self.readiness(ship_symbol).ready_at()
+    }
+
+    fn readiness(&self, ship_symbol: &Symbol) -> Readiness {
+        self.ships
+            .read()
+            .unwrap()
+            .get(ship_symbol)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Checks whether the ship is neither in transit nor on cooldown.
+    pub fn is_ready(&self, ship_symbol: &Symbol) -> bool {
+        self.ready_at(ship_symbol)
+            .is_none_or(|ready_at| ready_at <= self.clock.now())
+    }
+
+    /// Waits until the ship is neither in transit nor on cooldown.
+    ///
+    /// Returns immediately if nothing has been recorded for the ship. Arrivals and cooldowns
+    /// recorded while waiting are taken into account.
+    pub async fn wait_until_ready(&self, ship_symbol: &Symbol) {
+        let mut updates = self.updates.subscribe();
+
+        loop {
+            updates.borrow_and_update();
+
+            let ready_at = match self.ready_at(ship_symbol) {
+                Some(ready_at) if ready_at > self.clock.now() => ready_at,
+                _ => return,
+            };
+
+            tokio::select! {
+                _ = self.clock.sleep_until(ready_at) => {}
+                _ = updates.changed() => {}
+            }
+        }
+    }
+
+    /// Get a stream of [ShipReady] events, in the order the ships become ready.
+    ///
+    /// An event is produced each time a recorded arrival or cooldown passes, including ones
+    /// recorded after the stream was created. Ships that were already ready when the stream was
+    /// created are reported immediately.
+    pub fn ready_events(self: &Arc<Self>) -> ReadyEvents {
+        ReadyEvents {
+            scheduler: self.clone(),
+            updates: self.updates.subscribe(),
+            reported: HashMap::new(),
+        }
+    }
+}
+
+/// A stream of [ShipReady] events, created by [Scheduler::ready_events].
+#[derive(Debug)]
+pub struct ReadyEvents {
+    scheduler: Arc<Scheduler>,
+    updates: watch::Receiver<u64>,
+    reported: HashMap<Symbol, DateTime<Utc>>,
+}
+
+impl ReadyEvents {
+    /// Waits for the next ship to become ready.
+    pub async fn next(&mut self) -> ShipReady {
+        loop {
+            self.updates.borrow_and_update();
+
+            // Find the ship that becomes ready first, out of the ones not reported yet
+            let next = self
+                .scheduler
+                .ships
+                .read()
+                .unwrap()
+                .iter()
+                .filter_map(|(ship_symbol, readiness)| {
+                    let ready_at = readiness.ready_at()?;
+                    (self.reported.get(ship_symbol) != Some(&ready_at))
+                        .then(|| (ship_symbol.clone(), ready_at))
+                })
+                .min_by_key(|(_, ready_at)| *ready_at);
+
+            let Some((ship_symbol, ready_at)) = next else {
+                let _ = self.updates.changed().await;
+                continue;
+            };
+
+            tokio::select! {
+                _ = self.scheduler.clock.sleep_until(ready_at) => {
+                    self.reported.insert(ship_symbol.clone(), ready_at);
+
+                    return ShipReady { ship_symbol, ready_at };
+                }
+                _ = self.updates.changed() => {}
+            }
+        }
+    }
+}
+
+impl SpaceTradersClient {
+    /// Get the [Scheduler] tracking the readiness of the client's ships.
+    pub fn scheduler(&self) -> &Arc<Scheduler> {
+        &self.scheduler
+    }
+
+    /// Replaces the client's [Scheduler], for example to use a different [Clock].
+    pub fn set_scheduler(&mut self, scheduler: Scheduler) {
+        self.scheduler = Arc::new(scheduler);
+    }
+
+    /// Get the time a step that failed with the error should be retried at, or `None` if the
+    /// error isn't [retryable](SpaceTradersError::is_retryable).
+    ///
+    /// With the `metrics` feature, the retry is recorded in the client's metrics.
+    pub(crate) fn retry_at(&self, err: &SpaceTradersError) -> Option<DateTime<Utc>> {
+        if !err.is_retryable() {
+            return None;
+        }
+
+        #[cfg(feature = "metrics")]
+        self.record_retry(err);
+
+        Some(self.scheduler.clock.now() + Duration::seconds(RETRY_DELAY_SECONDS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler() -> (Arc<ManualClock>, Arc<Scheduler>) {
+        let clock = Arc::new(ManualClock::new(
+            "2023-05-17T04:00:00Z".parse::<DateTime<Utc>>().unwrap(),
+        ));
+
+        (clock.clone(), Arc::new(Scheduler::new(clock)))
+    }
+
+    #[tokio::test]
+    async fn waits_for_arrival_and_cooldown() {
+        let (clock, scheduler) = scheduler();
+        let ship = Symbol::new("TST-RS-04-1").unwrap();
+        let start = clock.now();
+
+        // Nothing recorded, so the ship is ready
+        scheduler.wait_until_ready(&ship).await;
+
+        scheduler.record_arrival(&ship, start + Duration::seconds(30));
+        scheduler.record_cooldown(&ship, start + Duration::seconds(70));
+        assert_eq!(
+            scheduler.ready_at(&ship),
+            Some(start + Duration::seconds(70))
+        );
+        assert!(!scheduler.is_ready(&ship));
+
+        let waiting = tokio::spawn({
+            let scheduler = scheduler.clone();
+            let ship = ship.clone();
+            async move { scheduler.wait_until_ready(&ship).await }
+        });
+
+        // Arriving isn't enough while the cooldown is active
+        clock.advance(Duration::seconds(30));
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        clock.advance(Duration::seconds(40));
+        waiting.await.unwrap();
+        assert!(scheduler.is_ready(&ship));
+    }
+
+    #[tokio::test]
+    async fn emits_ready_events_in_order() {
+        let (clock, scheduler) = scheduler();
+        let first = Symbol::new("TST-RS-04-1").unwrap();
+        let second = Symbol::new("TST-RS-04-2").unwrap();
+        let start = clock.now();
+
+        let mut events = scheduler.ready_events();
+        scheduler.record_cooldown(&second, start + Duration::seconds(70));
+        scheduler.record_arrival(&first, start + Duration::seconds(30));
+
+        clock.advance(Duration::seconds(100));
+        assert_eq!(
+            events.next().await,
+            ShipReady {
+                ship_symbol: first,
+                ready_at: start + Duration::seconds(30),
+            }
+        );
+        assert_eq!(
+            events.next().await,
+            ShipReady {
+                ship_symbol: second.clone(),
+                ready_at: start + Duration::seconds(70),
+            }
+        );
+
+        // New cooldowns are reported once they expire
+        scheduler.record_cooldown(&second, start + Duration::seconds(170));
+        let next = tokio::spawn(async move { events.next().await });
+        tokio::task::yield_now().await;
+        assert!(!next.is_finished());
+
+        clock.advance_to(start + Duration::seconds(170));
+        assert_eq!(next.await.unwrap().ship_symbol, second);
+    }
+
+    #[test]
+    fn retries_retryable_errors_after_a_delay() {
+        let (clock, scheduler) = scheduler();
+        let mut client = SpaceTradersClient::new();
+        client.set_scheduler(Arc::into_inner(scheduler).unwrap());
+
+        let rate_limited = SpaceTradersError::ResponseError(
+            serde_json::from_value(serde_json::json!({ "code": 429, "message": "Slow down." }))
+                .unwrap(),
+        );
+        assert_eq!(
+            client.retry_at(&rate_limited),
+            Some(clock.now() + Duration::seconds(RETRY_DELAY_SECONDS))
+        );
+
+        let not_found = SpaceTradersError::InvalidContractId {
+            contract_id: "clhr6zx0r07s2s60daxqce7b1".into(),
+            operation: None,
+        };
+        assert_eq!(client.retry_at(&not_found), None);
+    }
+}
@@ -0,0 +1,573 @@
+//! Runs ship behaviours written as [Rhai](https://rhai.rs) scripts.
+//!
+//! A [ScriptRunner] calls the `step` function of a script file once per action, like the
+//! [MiningLoop](crate::mining::MiningLoop) does with its own logic. Between steps, the runner waits
+//! until the ship has arrived and its cooldown has expired, so each step can simply perform the
+//! next action. The file is read again before every step, and reloaded when it changed, so a
+//! behaviour can be tweaked while the ship is running. If the new version doesn't compile, the
+//! previous one keeps running.
+//!
+//! Scripts keep state between steps in `this`, which starts out as an empty object map, and call
+//! `finish()` once their work is done. Printed messages are reported as [ScriptEvent]s.
+//!
+//! The following functions act on the runner's ship. Values from the API are passed to the script
+//! as object maps with the API's field names, e.g. `market(waypoint).tradeGoods`.
+//!
+//! | Function | Description |
+//! | --- | --- |
+//! | `ship_symbol()` | The ship's symbol |
+//! | `ship()` | The ship, as cached by the client |
+//! | `status()` | `"DOCKED"`, `"IN_ORBIT"` or `"IN_TRANSIT"` |
+//! | `location()` | The ship's waypoint, or its destination while in transit |
+//! | `fuel()`, `cargo_units()`, `cargo_capacity()` | The ship's fuel and cargo |
+//! | `cargo()` | The units of each good in the ship's cargo hold |
+//! | `dock()`, `orbit()`, `navigate(waypoint)` | Moves the ship, and returns its navigation info |
+//! | `extract()` | Extracts resources, and returns the `symbol` and `units` extracted and the `cooldown` |
+//! | `survey()` | Surveys the ship's waypoint |
+//! | `sell(good, units)`, `purchase(good, units)`, `refuel()` | Trades at the ship's market |
+//! | `jettison(good, units)` | Jettisons cargo |
+//! | `market(waypoint)` | A market in the ship's system |
+//! | `credits()`, `contracts()` | The agent's credits and contracts |
+//! | `accept_contract(id)`, `deliver(id, good, units)`, `fulfill_contract(id)` | Works on contracts |
+//! | `finish()` | Ends the behaviour after this step |
+//!
+//! Scripts can be run per ship by a [Fleet](crate::fleet::Fleet) with
+//! [Assignment::Script](crate::fleet::Assignment::Script).
+//!
+//! Scripting is only available with the `scripting` feature.
+//!
+//! # Example
+//! A script that mines until the cargo hold is full:
+//! ```text
+//! fn step() {
+//!     if status() == "DOCKED" {
+//!         orbit();
+//!     } else if location() != "X1-ZA40-99095A" {
+//!         navigate("X1-ZA40-99095A");
+//!     } else if cargo_units() < cargo_capacity() {
+//!         let extraction = extract();
+//!         print(`Extracted ${extraction.units} ${extraction.symbol}`);
+//!     } else {
+//!         finish();
+//!     }
+//! }
+//! ```
+//!
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let client = SpaceTradersClient::load_saved().unwrap();
+//! let mut runner = ScriptRunner::new(Symbol::new("TST-RS-04-1").unwrap(), "mine.rhai");
+//! runner.run(&client).await.unwrap();
+//! # })
+//! ```
+
+use crate::{
+    conditional_types::Symbol, ship::ShipStatus, space_traders_client::SpaceTradersClient,
+    STResult, SpaceTradersError,
+};
+use chrono::{DateTime, Utc};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, ImmutableString, Map, AST};
+use serde::Serialize;
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::{runtime::Handle, sync::mpsc};
+
+/// The name of the function called for every step.
+const STEP_FN: &str = "step";
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Shared with the engine, so scripts print to the most recent subscriber.
+type EventSender = Arc<std::sync::Mutex<Option<mpsc::UnboundedSender<ScriptEvent>>>>;
+
+/// Progress reported by a [ScriptRunner].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptEvent {
+    /// The script file changed, and the new version was loaded.
+    Reloaded,
+    /// The script file changed, but the new version failed to compile. The previous version keeps
+    /// running.
+    ReloadFailed(String),
+    /// The script printed a message.
+    Printed(String),
+}
+
+/// The outcome of a single [ScriptRunner::step].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptStep {
+    /// An action was performed; the next step can be taken immediately.
+    Continue,
+    /// The ship is busy (travelling or cooling down) until the given time.
+    WaitUntil(DateTime<Utc>),
+    /// The script called `finish()`.
+    Finished,
+}
+
+/// The compiled script, along with the engine bound to a client.
+struct Compiled {
+    engine: Arc<Engine>,
+    source: String,
+    ast: AST,
+}
+
+/// Runs the behaviour of a single ship from a script file.
+pub struct ScriptRunner {
+    ship_symbol: Symbol,
+    path: PathBuf,
+    compiled: Option<Compiled>,
+    state: Dynamic,
+    finished: Arc<AtomicBool>,
+    events: EventSender,
+}
+
+impl std::fmt::Debug for ScriptRunner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptRunner")
+            .field("ship_symbol", &self.ship_symbol)
+            .field("path", &self.path)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScriptRunner {
+    /// Creates a runner for the script at `path`, which is loaded on the first step.
+    pub fn new(ship_symbol: Symbol, path: impl AsRef<Path>) -> Self {
+        Self {
+            ship_symbol,
+            path: path.as_ref().to_path_buf(),
+            compiled: None,
+            state: Map::new().into(),
+            finished: Arc::new(AtomicBool::new(false)),
+            events: Default::default(),
+        }
+    }
+
+    pub fn ship_symbol(&self) -> &Symbol {
+        &self.ship_symbol
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get a receiver for the runner's events.
+    ///
+    /// Only the most recent receiver gets the events.
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<ScriptEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.events.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    fn emit(&self, event: ScriptEvent) {
+        emit(&self.events, event);
+    }
+
+    /// Runs steps until the script finishes, waiting between them while the ship is busy.
+    pub async fn run(&mut self, client: &SpaceTradersClient) -> STResult<()> {
+        loop {
+            match self.step(client).await? {
+                ScriptStep::Continue => {}
+                ScriptStep::WaitUntil(until) => client.scheduler().clock().sleep_until(until).await,
+                ScriptStep::Finished => return Ok(()),
+            }
+        }
+    }
+
+    /// Reloads the script if it changed, and calls its `step` function once.
+    ///
+    /// The script runs on a blocking thread, so this must be called from a `tokio` runtime.
+    pub async fn step(&mut self, client: &SpaceTradersClient) -> STResult<ScriptStep> {
+        if self.finished.load(Ordering::SeqCst) {
+            return Ok(ScriptStep::Finished);
+        }
+        self.reload(client)?;
+
+        // The cached status isn't updated when a ship arrives
+        let now = client.scheduler().clock().now();
+        let ship = client.get_ship(&self.ship_symbol)?;
+        if ship.nav.status == ShipStatus::InTransit && ship.nav.route.arrival <= now {
+            client.update_ship(&self.ship_symbol, |ship| {
+                ship.nav.status = ShipStatus::InOrbit
+            })?;
+        }
+
+        let compiled = self
+            .compiled
+            .as_ref()
+            .expect("the script is compiled by `reload`");
+        let (engine, ast) = (compiled.engine.clone(), compiled.ast.clone());
+        let mut state = std::mem::take(&mut self.state);
+
+        let (result, state) = tokio::task::spawn_blocking(move || {
+            let options = CallFnOptions::new().bind_this_ptr(&mut state);
+            // The return value of `step` is ignored
+            let result = engine
+                .call_fn_with_options::<Dynamic>(
+                    options,
+                    &mut Default::default(),
+                    &ast,
+                    STEP_FN,
+                    (),
+                )
+                .map(drop);
+            (result, state)
+        })
+        .await
+        .map_err(|err| SpaceTradersError::ScriptError(err.to_string()))?;
+        self.state = state;
+        result.map_err(|err| SpaceTradersError::ScriptError(err.to_string()))?;
+
+        if self.finished.load(Ordering::SeqCst) {
+            return Ok(ScriptStep::Finished);
+        }
+
+        Ok(match client.scheduler().ready_at(&self.ship_symbol) {
+            Some(ready_at) if ready_at > client.scheduler().clock().now() => {
+                ScriptStep::WaitUntil(ready_at)
+            }
+            _ => ScriptStep::Continue,
+        })
+    }
+
+    /// Compiles the script if it hasn't been compiled yet, or if the file changed since.
+    fn reload(&mut self, client: &SpaceTradersClient) -> STResult<()> {
+        let source = std::fs::read_to_string(&self.path)?;
+
+        match &self.compiled {
+            Some(compiled) if compiled.source == source => {}
+            Some(compiled) => match compiled.engine.compile(&source) {
+                Ok(ast) => {
+                    self.compiled = Some(Compiled {
+                        engine: compiled.engine.clone(),
+                        source,
+                        ast,
+                    });
+                    self.emit(ScriptEvent::Reloaded);
+                }
+                Err(err) => {
+                    // Keep running the previous version, but don't report the error again
+                    let engine = compiled.engine.clone();
+                    let ast = compiled.ast.clone();
+                    self.compiled = Some(Compiled {
+                        engine,
+                        source,
+                        ast,
+                    });
+                    self.emit(ScriptEvent::ReloadFailed(err.to_string()));
+                }
+            },
+            None => {
+                let engine = self.engine(client);
+                let ast = engine
+                    .compile(&source)
+                    .map_err(|err| SpaceTradersError::ScriptError(err.to_string()))?;
+                self.compiled = Some(Compiled {
+                    engine: Arc::new(engine),
+                    source,
+                    ast,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates an engine exposing the client's operations for the runner's ship.
+    fn engine(&self, client: &SpaceTradersClient) -> Engine {
+        let mut engine = Engine::new();
+        let context = Context {
+            client: client.clone(),
+            ship: self.ship_symbol.clone(),
+            runtime: Handle::current(),
+        };
+
+        let events = self.events.clone();
+        engine.on_print(move |message| emit(&events, ScriptEvent::Printed(message.into())));
+
+        let finished = self.finished.clone();
+        engine.register_fn("finish", move || finished.store(true, Ordering::SeqCst));
+
+        let ctx = context.clone();
+        engine.register_fn("ship_symbol", move || ctx.ship.to_string());
+        let ctx = context.clone();
+        engine.register_fn("ship", move || to_dynamic(ctx.client.get_ship(&ctx.ship)));
+        let ctx = context.clone();
+        engine.register_fn("status", move || -> ScriptResult<Dynamic> {
+            to_dynamic(ctx.client.get_ship(&ctx.ship).map(|ship| ship.nav.status))
+        });
+        let ctx = context.clone();
+        engine.register_fn("location", move || -> ScriptResult<String> {
+            let ship = ctx.client.get_ship(&ctx.ship).map_err(script_error)?;
+            Ok(ship.nav.waypoint_symbol.to_string())
+        });
+        let ctx = context.clone();
+        engine.register_fn("fuel", move || -> ScriptResult<i64> {
+            let ship = ctx.client.get_ship(&ctx.ship).map_err(script_error)?;
+            Ok(*ship.fuel.current)
+        });
+        let ctx = context.clone();
+        engine.register_fn("cargo_units", move || -> ScriptResult<i64> {
+            let ship = ctx.client.get_ship(&ctx.ship).map_err(script_error)?;
+            Ok(*ship.cargo.units)
+        });
+        let ctx = context.clone();
+        engine.register_fn("cargo_capacity", move || -> ScriptResult<i64> {
+            let ship = ctx.client.get_ship(&ctx.ship).map_err(script_error)?;
+            Ok(*ship.cargo.capacity)
+        });
+        let ctx = context.clone();
+        engine.register_fn("cargo", move || -> ScriptResult<Map> {
+            let ship = ctx.client.get_ship(&ctx.ship).map_err(script_error)?;
+            Ok(ship
+                .cargo
+                .inventory
+                .iter()
+                .map(|item| (item.symbol.as_str().into(), Dynamic::from_int(*item.units)))
+                .collect())
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("dock", move || {
+            ctx.block_on(ctx.client.dock_ship(&ctx.ship))
+        });
+        let ctx = context.clone();
+        engine.register_fn("orbit", move || {
+            ctx.block_on(ctx.client.orbit_ship(&ctx.ship))
+        });
+        let ctx = context.clone();
+        engine.register_fn("navigate", move |waypoint: ImmutableString| {
+            let waypoint = symbol(&waypoint)?;
+            ctx.block_on(ctx.client.navigate_ship(&ctx.ship, &waypoint))
+        });
+        let ctx = context.clone();
+        engine.register_fn("extract", move || {
+            ctx.block_on(async {
+                let (cooldown, extraction) = ctx.client.extract_resources(&ctx.ship, None).await?;
+                // `yield` is a keyword in Rhai, so the yield is flattened into the result
+                Ok(serde_json::json!({
+                    "symbol": extraction.yield_.symbol,
+                    "units": extraction.yield_.units,
+                    "cooldown": cooldown,
+                }))
+            })
+        });
+        let ctx = context.clone();
+        engine.register_fn("survey", move || {
+            ctx.block_on(async {
+                let (_, surveys) = ctx.client.create_survey(&ctx.ship).await?;
+                Ok(surveys)
+            })
+        });
+        let ctx = context.clone();
+        engine.register_fn("sell", move |good: ImmutableString, units: i64| {
+            let good = symbol(&good)?;
+            ctx.block_on(ctx.client.sell_cargo(&ctx.ship, &good, units as i32))
+        });
+        let ctx = context.clone();
+        engine.register_fn("purchase", move |good: ImmutableString, units: i64| {
+            let good = symbol(&good)?;
+            ctx.block_on(ctx.client.purchase_cargo(&ctx.ship, &good, units as i32))
+        });
+        let ctx = context.clone();
+        engine.register_fn("refuel", move || {
+            ctx.block_on(ctx.client.refuel_ship(&ctx.ship))
+        });
+        let ctx = context.clone();
+        engine.register_fn("jettison", move |good: ImmutableString, units: i64| {
+            let good = symbol(&good)?;
+            ctx.block_on(ctx.client.jettison_cargo(&ctx.ship, &good, units as i32))
+        });
+        let ctx = context.clone();
+        engine.register_fn("market", move |waypoint: ImmutableString| {
+            let waypoint = symbol(&waypoint)?;
+            let system = ctx
+                .client
+                .get_ship(&ctx.ship)
+                .map_err(script_error)?
+                .nav
+                .system_symbol;
+            ctx.block_on(ctx.client.view_market(&system, &waypoint))
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("credits", move || -> ScriptResult<i64> {
+            let agent = ctx.client.agent().map_err(script_error)?;
+            Ok(agent.credits.into())
+        });
+        let ctx = context.clone();
+        engine.register_fn("contracts", move || to_dynamic(ctx.client.contracts()));
+        let ctx = context.clone();
+        engine.register_fn("accept_contract", move |id: ImmutableString| {
+            let id = symbol(&id)?;
+            ctx.block_on(ctx.client.accept_contract(id))
+        });
+        let ctx = context.clone();
+        engine.register_fn(
+            "deliver",
+            move |id: ImmutableString, good: ImmutableString, units: i64| {
+                let (id, good) = (symbol(&id)?, symbol(&good)?);
+                ctx.block_on(
+                    ctx.client
+                        .deliver_contract(&id, &ctx.ship, &good, units as i32),
+                )
+            },
+        );
+        let ctx = context;
+        engine.register_fn("fulfill_contract", move |id: ImmutableString| {
+            let id = symbol(&id)?;
+            ctx.block_on(ctx.client.fulfill_contract(&id))
+        });
+
+        engine
+    }
+}
+
+/// What the functions exposed to a script need to call the client.
+#[derive(Clone)]
+struct Context {
+    client: SpaceTradersClient,
+    ship: Symbol,
+    runtime: Handle,
+}
+
+impl Context {
+    /// Runs an API call to completion from the script's blocking thread.
+    fn block_on<T: Serialize>(
+        &self,
+        future: impl Future<Output = STResult<T>>,
+    ) -> ScriptResult<Dynamic> {
+        to_dynamic(self.runtime.block_on(future))
+    }
+}
+
+fn emit(events: &EventSender, event: ScriptEvent) {
+    if let Some(events) = events.lock().unwrap().as_ref() {
+        // The receiver may have been dropped, which just means nobody is listening
+        let _ = events.send(event);
+    }
+}
+
+fn to_dynamic<T: Serialize>(value: STResult<T>) -> ScriptResult<Dynamic> {
+    rhai::serde::to_dynamic(value.map_err(script_error)?)
+}
+
+fn script_error(err: SpaceTradersError) -> Box<EvalAltResult> {
+    err.to_string().into()
+}
+
+fn symbol(symbol: &str) -> ScriptResult<Symbol> {
+    Symbol::new(symbol).map_err(|err| err.to_string().into())
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+    use crate::simulator::Simulator;
+    use chrono::Duration;
+
+    const MINE: &str = r#"
+        fn step() {
+            if this.steps == () { this.steps = 0; }
+            this.steps += 1;
+
+            if status() == "DOCKED" {
+                orbit();
+            } else if location() != "X1-ZA40-99095A" {
+                navigate("X1-ZA40-99095A");
+            } else if cargo_units() < cargo_capacity() {
+                let extraction = extract();
+                print(`Extracted ${extraction.units} ${extraction.symbol}`);
+            } else {
+                print(`Full after ${this.steps} steps`);
+                finish();
+            }
+        }
+    "#;
+
+    fn script(name: &str, source: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.rhai", name, uuid::Uuid::new_v4()));
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn mines_until_the_cargo_hold_is_full() -> STResult<()> {
+        let simulator = Simulator::start("2023-05-17T04:18:05.930Z".parse().unwrap()).await?;
+        let client = simulator.client();
+        client.register_callsign("SCRIPT", None).await?;
+
+        let path = script("mine", MINE);
+        let ship = Symbol::new("SCRIPT-1").unwrap();
+        let mut runner = ScriptRunner::new(ship.clone(), &path);
+        let mut events = runner.subscribe();
+
+        simulator
+            .run_for(Duration::hours(6), runner.run(&client))
+            .await
+            .expect("the script should finish")?;
+
+        let ship = client.get_ship(&ship)?;
+        assert_eq!(ship.nav.waypoint_symbol, "X1-ZA40-99095A");
+        assert_eq!(*ship.cargo.units, *ship.cargo.capacity);
+
+        let mut printed = vec![];
+        while let Ok(event) = events.try_recv() {
+            if let ScriptEvent::Printed(message) = event {
+                printed.push(message);
+            }
+        }
+        assert!(printed[0].starts_with("Extracted"));
+        assert!(printed.last().unwrap().starts_with("Full after"));
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reloads_changed_scripts() -> STResult<()> {
+        let simulator = Simulator::start("2023-05-17T04:18:05.930Z".parse().unwrap()).await?;
+        let client = simulator.client();
+        client.register_callsign("RELOAD", None).await?;
+
+        let path = script("reload", r#"fn step() { print("v1"); }"#);
+        let mut runner = ScriptRunner::new(Symbol::new("RELOAD-1").unwrap(), &path);
+        let mut events = runner.subscribe();
+
+        assert_eq!(runner.step(&client).await?, ScriptStep::Continue);
+        assert_eq!(events.try_recv(), Ok(ScriptEvent::Printed("v1".into())));
+
+        std::fs::write(&path, r#"fn step() { print("v2"); finish(); }"#)?;
+        assert_eq!(runner.step(&client).await?, ScriptStep::Finished);
+        assert_eq!(events.try_recv(), Ok(ScriptEvent::Reloaded));
+        assert_eq!(events.try_recv(), Ok(ScriptEvent::Printed("v2".into())));
+
+        // Broken versions are reported once, and the previous version keeps running
+        let mut runner = ScriptRunner::new(Symbol::new("RELOAD-1").unwrap(), &path);
+        let mut events = runner.subscribe();
+        std::fs::write(&path, r#"fn step() { print("v3"); }"#)?;
+        runner.step(&client).await?;
+        std::fs::write(&path, r#"fn step() { print("v4" }"#)?;
+        runner.step(&client).await?;
+        runner.step(&client).await?;
+
+        assert_eq!(events.try_recv(), Ok(ScriptEvent::Printed("v3".into())));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(ScriptEvent::ReloadFailed(_))
+        ));
+        assert_eq!(events.try_recv(), Ok(ScriptEvent::Printed("v3".into())));
+        assert_eq!(events.try_recv(), Ok(ScriptEvent::Printed("v3".into())));
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,394 @@
+//! Keeps the agent's token out of plaintext save files.
+//!
+//! By default, [save_client](SpaceTradersClient::save_client) writes the bearer token on the first
+//! line of the save file. Instead, the token can be kept in a [SecretStore], such as an
+//! environment variable ([EnvSecretStore]) or a file only readable by its owner
+//! ([FileSecretStore]), while the cached data is saved without it using
+//! [save_with_store](SpaceTradersClient::save_with_store).
+//!
+//! With the `encryption` feature, the whole save can also be encrypted with a passphrase or a key
+//! file (see [SaveKey]).
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! // The token is read from `SPACE_TRADERS_TOKEN`, and only the cache is kept in the save file.
+//! let store = EnvSecretStore::default();
+//! let client = SpaceTradersClient::load_with_store("spacetraders.save", &store).unwrap();
+//!
+//! client.save_with_store("spacetraders.save", &store).unwrap();
+//! ```
+
+use crate::{space_traders_client::SpaceTradersClient, STResult, SpaceTradersError};
+use std::{
+    env,
+    fmt::Debug,
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+/// The first line of encrypted save files, in place of the token.
+pub(crate) const ENCRYPTED_SAVE_HEADER: &str = "space-traders encrypted save v1";
+
+/// The environment variable read by the default [EnvSecretStore].
+pub const TOKEN_VAR: &str = "SPACE_TRADERS_TOKEN";
+
+/// Somewhere the agent's token is kept, apart from the save file.
+pub trait SecretStore: Debug + Send + Sync {
+    /// Reads the token, if there is one.
+    fn load_token(&self) -> STResult<Option<String>>;
+
+    /// Keeps the token, replacing any token stored before.
+    fn store_token(&self, token: &str) -> STResult<()>;
+}
+
+/// Reads the token from an environment variable.
+///
+/// Environment variables can't be set for later runs, so storing a token only succeeds if the
+/// variable already holds it.
+#[derive(Debug, Clone)]
+pub struct EnvSecretStore {
+    var: String,
+}
+
+impl EnvSecretStore {
+    pub fn new(var: impl Into<String>) -> Self {
+        Self { var: var.into() }
+    }
+
+    pub fn var(&self) -> &str {
+        &self.var
+    }
+}
+
+impl Default for EnvSecretStore {
+    /// Reads the token from [TOKEN_VAR].
+    fn default() -> Self {
+        Self::new(TOKEN_VAR)
+    }
+}
+
+impl SecretStore for EnvSecretStore {
+    fn load_token(&self) -> STResult<Option<String>> {
+        Ok(env::var(&self.var).ok().filter(|token| !token.is_empty()))
+    }
+
+    fn store_token(&self, token: &str) -> STResult<()> {
+        if self.load_token()?.as_deref() == Some(token) {
+            return Ok(());
+        }
+
+        Err(SpaceTradersError::SecretStoreError(format!(
+            "The environment variable `{}` must be set to the agent's token",
+            self.var
+        )))
+    }
+}
+
+/// Keeps the token in its own file.
+///
+/// On Unix, the file is created so that only its owner can read it.
+#[derive(Debug, Clone)]
+pub struct FileSecretStore {
+    path: PathBuf,
+}
+
+impl FileSecretStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl SecretStore for FileSecretStore {
+    fn load_token(&self) -> STResult<Option<String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(token) => Ok(Some(token.trim().to_string()).filter(|token| !token.is_empty())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn store_token(&self, token: &str) -> STResult<()> {
+        Ok(write_private(&self.path, token.as_bytes())?)
+    }
+}
+
+/// Writes the file, which is only readable and writable by its owner on Unix.
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        options.mode(0o600);
+        // The mode only applies to new files
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    options.open(path)?.write_all(contents)
+}
+
+impl SpaceTradersClient {
+    /// Keeps the token in the store, and saves the cached data to the file at the given path
+    /// without it.
+    pub fn save_with_store(&self, path: impl AsRef<Path>, store: &dyn SecretStore) -> STResult<()> {
+        store.store_token(&self.token()?)?;
+        self.save_cache_to(path)
+    }
+
+    /// Loads a client from the save file at the given path, with the token from the store.
+    ///
+    /// The token in the save file is used instead, if it has one. Without either, the client can
+    /// only read its cached data.
+    pub fn load_with_store(path: impl AsRef<Path>, store: &dyn SecretStore) -> STResult<Self> {
+        let client = Self::load_saved_from(path)?;
+        if !client.token_set() {
+            *client.token.write().unwrap() = store.load_token()?;
+        }
+
+        Ok(client)
+    }
+}
+
+#[cfg(feature = "encryption")]
+pub use encryption::SaveKey;
+
+#[cfg(feature = "encryption")]
+mod encryption {
+    use super::{write_private, ENCRYPTED_SAVE_HEADER};
+    use crate::{space_traders_client::SpaceTradersClient, STResult, SpaceTradersError};
+    use chacha20poly1305::{
+        aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+        ChaCha20Poly1305, Nonce,
+    };
+    use std::{fmt, path::Path};
+
+    /// The length of the random salt the encryption key is derived with.
+    const SALT_LEN: usize = 16;
+
+    /// The secret an encrypted save is encrypted with: a passphrase, or the contents of a key
+    /// file.
+    ///
+    /// The encryption key is derived from the secret with Argon2, and the save is encrypted with
+    /// ChaCha20-Poly1305.
+    #[derive(Clone)]
+    pub struct SaveKey {
+        secret: Vec<u8>,
+    }
+
+    impl SaveKey {
+        pub fn passphrase(passphrase: impl Into<String>) -> Self {
+            Self {
+                secret: passphrase.into().into_bytes(),
+            }
+        }
+
+        /// Uses the contents of the file as the secret, e.g. 32 random bytes.
+        pub fn from_file(path: impl AsRef<Path>) -> STResult<Self> {
+            let secret = std::fs::read(path)?;
+            if secret.is_empty() {
+                return Err(SpaceTradersError::EncryptionError(
+                    "The key file is empty".into(),
+                ));
+            }
+
+            Ok(Self { secret })
+        }
+
+        fn cipher(&self, salt: &[u8]) -> STResult<ChaCha20Poly1305> {
+            let mut key = [0; 32];
+            argon2::Argon2::default()
+                .hash_password_into(&self.secret, salt, &mut key)
+                .map_err(|err| SpaceTradersError::EncryptionError(err.to_string()))?;
+
+            Ok(ChaCha20Poly1305::new(&key.into()))
+        }
+    }
+
+    impl fmt::Debug for SaveKey {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("SaveKey").finish_non_exhaustive()
+        }
+    }
+
+    impl SpaceTradersClient {
+        /// Saves the `SpaceTradersClient` to the file at the given path, encrypted with the key.
+        ///
+        /// This data can be retrieved using [load_encrypted_from](Self::load_encrypted_from).
+        pub fn save_encrypted_to(&self, path: impl AsRef<Path>, key: &SaveKey) -> STResult<()> {
+            let contents = self.save_contents(Some(&self.token()?))?;
+
+            let mut salt = [0; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+            let ciphertext = key
+                .cipher(&salt)?
+                .encrypt(&nonce, contents.as_bytes())
+                .map_err(|err| SpaceTradersError::EncryptionError(err.to_string()))?;
+
+            let save = format!(
+                "{}\n{}\n{}\n{}",
+                ENCRYPTED_SAVE_HEADER,
+                hex(&salt),
+                hex(&nonce),
+                hex(&ciphertext)
+            );
+            write_private(path.as_ref(), save.as_bytes())?;
+
+            Ok(())
+        }
+
+        /// Load a `SpaceTradersClient` from a save file encrypted with the key.
+        pub fn load_encrypted_from(path: impl AsRef<Path>, key: &SaveKey) -> STResult<Self> {
+            let path = path.as_ref();
+            let invalid_save = || SpaceTradersError::InvalidSave(path.display().to_string());
+
+            let save = std::fs::read_to_string(path)?;
+            let mut lines = save.lines();
+            if lines.next() != Some(ENCRYPTED_SAVE_HEADER) {
+                return Err(invalid_save());
+            }
+            let mut next = || lines.next().and_then(unhex).ok_or_else(invalid_save);
+            let (salt, nonce, ciphertext) = (next()?, next()?, next()?);
+            if nonce.len() != 12 {
+                return Err(invalid_save());
+            }
+
+            let contents = key
+                .cipher(&salt)?
+                .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+                .map_err(|_| {
+                    SpaceTradersError::EncryptionError(format!(
+                        "{} couldn't be decrypted: the key is wrong or the save is corrupted",
+                        path.display()
+                    ))
+                })?;
+
+            Self::from_save(&String::from_utf8_lossy(&contents), path)
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn unhex(hex: &str) -> Option<Vec<u8>> {
+        if hex.len() % 2 != 0 {
+            return None;
+        }
+
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "space-traders-secrets-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    async fn registered_client(
+        server: &MockServer,
+        callsign: &str,
+    ) -> STResult<SpaceTradersClient> {
+        let client = server.client();
+        client.register_callsign(callsign, None).await?;
+
+        Ok(client)
+    }
+
+    #[tokio::test]
+    async fn keeps_the_token_in_a_store() -> STResult<()> {
+        let server = MockServer::start().await?;
+        let client = registered_client(&server, "SECRETS").await?;
+        let token = client.token()?;
+
+        let save = temp_path("save");
+        let store = FileSecretStore::new(temp_path("token"));
+        client.save_with_store(&save, &store)?;
+
+        assert!(!fs::read_to_string(&save)?.contains(&token));
+        assert_eq!(store.load_token()?.as_deref(), Some(token.as_str()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(store.path())?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Without the store, the client only has its cache
+        let loaded = SpaceTradersClient::load_saved_from(&save)?;
+        assert!(!loaded.token_set());
+        assert_eq!(loaded.agent()?.symbol, "SECRETS");
+
+        let loaded = SpaceTradersClient::load_with_store(&save, &store)?;
+        assert_eq!(loaded.token()?, token);
+
+        // The environment variable has to be set by the user
+        let env_store = EnvSecretStore::new("SPACE_TRADERS_TEST_TOKEN_UNSET");
+        assert_eq!(env_store.load_token()?, None);
+        assert!(matches!(
+            client.save_with_store(&save, &env_store),
+            Err(SpaceTradersError::SecretStoreError(_))
+        ));
+
+        fs::remove_file(save)?;
+        fs::remove_file(store.path())?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn encrypts_saves() -> STResult<()> {
+        let server = MockServer::start().await?;
+        let client = registered_client(&server, "ENCRYPTED").await?;
+        let token = client.token()?;
+
+        let save = temp_path("encrypted");
+        let key_file = temp_path("key");
+        fs::write(&key_file, [7; 32])?;
+        client.save_encrypted_to(&save, &SaveKey::from_file(&key_file)?)?;
+
+        let contents = fs::read_to_string(&save)?;
+        assert!(!contents.contains(&token));
+        assert!(!contents.contains("ENCRYPTED"));
+
+        let loaded =
+            SpaceTradersClient::load_encrypted_from(&save, &SaveKey::from_file(&key_file)?)?;
+        assert_eq!(loaded.token()?, token);
+        assert_eq!(loaded.agent()?.symbol, "ENCRYPTED");
+
+        assert!(matches!(
+            SpaceTradersClient::load_encrypted_from(&save, &SaveKey::passphrase("wrong")),
+            Err(SpaceTradersError::EncryptionError(_))
+        ));
+        assert!(matches!(
+            SpaceTradersClient::load_saved_from(&save),
+            Err(SpaceTradersError::InvalidSave(_))
+        ));
+
+        fs::remove_file(save)?;
+        fs::remove_file(key_file)?;
+
+        Ok(())
+    }
+}
@@ -1,10 +1,14 @@
 use crate::{
+    auto_prepare::Preparation,
     conditional_types::ints::{BoundedInt, LowerBoundInt, NonNegative},
     conditional_types::strings::{Description, Name, Symbol},
+    events::ClientEvent,
     faction::FactionSymbol,
+    ledger::{Activity, LedgerEntry},
+    market::MarketTransaction,
     prelude::Agent,
     space_traders_client::SpaceTradersClient,
-    waypoint::{Waypoint, WaypointTraitSymbols, WaypointType},
+    waypoint::WaypointType,
     ResponseData, STResult, SpaceTradersError,
 };
 use chrono::{DateTime, Utc};
@@ -12,6 +16,7 @@ use reqwest::header::{
     HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE,
 };
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -29,7 +34,7 @@ pub(crate) struct Ship {
     pub(crate) fuel: Fuel,
 }
 
-#[derive(Deserialize, Debug, Serialize, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Nav {
     pub(crate) system_symbol: Symbol,
@@ -49,23 +54,23 @@ pub(crate) enum ShipStatus {
 
 #[derive(Deserialize, Debug, Serialize, PartialEq, Eq, Clone, Copy)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
-pub(crate) enum FlightMode {
+pub enum FlightMode {
     Drift,
     Stealth,
     Cruise,
     Burn,
 }
 
-#[derive(Deserialize, Debug, Serialize, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Route {
     pub(crate) destination: Location,
     pub(crate) departure: Location,
-    departure_time: chrono::DateTime<chrono::Utc>,
-    arrival: chrono::DateTime<chrono::Utc>,
+    pub(crate) departure_time: chrono::DateTime<chrono::Utc>,
+    pub(crate) arrival: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(Deserialize, Debug, Serialize, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Location {
     pub(crate) symbol: Symbol,
@@ -94,19 +99,19 @@ pub(crate) enum Rotation {
     Relaxed,
 }
 
-#[derive(Deserialize, Debug, Serialize, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct Fuel {
+pub struct Fuel {
     pub(crate) current: NonNegative,
     pub(crate) capacity: NonNegative,
     pub(crate) consumed: Consumed,
 }
 
-#[derive(Deserialize, Debug, Serialize, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Consumed {
     pub(crate) amount: NonNegative,
-    timestamp: chrono::DateTime<chrono::Utc>,
+    pub(crate) timestamp: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
@@ -295,7 +300,7 @@ pub(crate) enum MountSymbol {
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Registration {
-    name: Name,
+    pub(crate) name: Name,
     pub(crate) faction_symbol: FactionSymbol,
     pub(crate) role: Role,
 }
@@ -319,15 +324,15 @@ pub(crate) enum Role {
     Refinery,
 }
 
-#[derive(Deserialize, Debug, Serialize, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct Cargo {
+pub struct Cargo {
     pub(crate) capacity: NonNegative,
     pub(crate) units: NonNegative,
     pub(crate) inventory: Vec<InventoryItem>,
 }
 
-#[derive(Deserialize, Debug, Serialize, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct InventoryItem {
     pub(crate) symbol: Symbol,
@@ -354,52 +359,52 @@ pub enum ShipType {
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
-struct InnerShipType {
+pub(crate) struct InnerShipType {
     #[serde(rename = "type")]
-    type_: ShipType,
+    pub(crate) type_: ShipType,
 }
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ShipyardTransaction {
-    waypoint_symbol: Symbol,
-    ship_symbol: Symbol,
-    price: LowerBoundInt<1>,
-    agent_symbol: Symbol,
-    timestamp: DateTime<Utc>,
+    pub(crate) waypoint_symbol: Symbol,
+    pub(crate) ship_symbol: Symbol,
+    pub(crate) price: LowerBoundInt<1>,
+    pub(crate) agent_symbol: Symbol,
+    pub(crate) timestamp: DateTime<Utc>,
 }
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
-struct ShipyardShip {
+pub(crate) struct ShipyardShip {
     #[serde(rename = "type")]
-    type_: ShipType,
-    name: Name,
-    description: Description,
-    purchase_price: i32,
-    frame: Frame,
-    reactor: Reactor,
-    engine: Engine,
-    modules: Vec<Module>,
-    mounts: Vec<Mount>,
+    pub(crate) type_: ShipType,
+    pub(crate) name: Name,
+    pub(crate) description: Description,
+    pub(crate) purchase_price: i32,
+    pub(crate) frame: Frame,
+    pub(crate) reactor: Reactor,
+    pub(crate) engine: Engine,
+    pub(crate) modules: Vec<Module>,
+    pub(crate) mounts: Vec<Mount>,
 }
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Shipyard {
-    symbol: Symbol,
-    ship_types: Vec<InnerShipType>,
-    transactions: Option<Vec<ShipyardTransaction>>,
-    ships: Option<Vec<ShipyardShip>>,
+    pub(crate) symbol: Symbol,
+    pub(crate) ship_types: Vec<InnerShipType>,
+    pub(crate) transactions: Option<Vec<ShipyardTransaction>>,
+    pub(crate) ships: Option<Vec<ShipyardShip>>,
 }
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SurveyDeposit {
-    symbol: Deposit,
+    pub(crate) symbol: Deposit,
 }
 
-#[derive(Deserialize, Debug, Serialize, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum SurveySize {
     Small,
@@ -410,35 +415,35 @@ pub enum SurveySize {
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Survey {
-    signature: Symbol,
-    symbol: Symbol,
-    deposits: Vec<SurveyDeposit>,
-    expiration: DateTime<Utc>,
-    size: SurveySize,
+    pub(crate) signature: Symbol,
+    pub(crate) symbol: Symbol,
+    pub(crate) deposits: Vec<SurveyDeposit>,
+    pub(crate) expiration: DateTime<Utc>,
+    pub(crate) size: SurveySize,
 }
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Cooldown {
-    ship_symbol: Symbol,
-    total_seconds: NonNegative,
-    remaining_seconds: NonNegative,
-    expiration: DateTime<Utc>,
+    pub(crate) ship_symbol: Symbol,
+    pub(crate) total_seconds: NonNegative,
+    pub(crate) remaining_seconds: NonNegative,
+    pub(crate) expiration: DateTime<Utc>,
 }
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
-struct ExtractionYield {
-    symbol: Symbol,
-    units: i32,
+pub(crate) struct ExtractionYield {
+    pub(crate) symbol: Symbol,
+    pub(crate) units: i32,
 }
 
 #[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Extraction {
-    ship_symbol: Symbol,
+    pub(crate) ship_symbol: Symbol,
     #[serde(rename = "yield")]
-    yield_: ExtractionYield,
+    pub(crate) yield_: ExtractionYield,
 }
 
 impl SpaceTradersClient {
@@ -448,59 +453,67 @@ impl SpaceTradersClient {
         system_symbol: &Symbol,
         waypoint_symbol: &Symbol,
     ) -> STResult<Shipyard> {
+        #[cfg(feature = "sqlite")]
+        if let Some(shipyard) = self.cached_shipyard(waypoint_symbol)? {
+            return Ok(shipyard);
+        }
+
         let url = format!(
-            "https://api.spacetraders.io/v2/systems/{}/waypoints/{}/shipyard",
-            system_symbol, waypoint_symbol
+            "{}/systems/{}/waypoints/{}/shipyard",
+            self.base_url, system_symbol, waypoint_symbol
         );
 
         let mut headers = HeaderMap::with_capacity(2);
         headers.insert(
             AUTHORIZATION,
-            HeaderValue::from_str(&format!(
-                "Bearer {}",
-                self.token.as_ref().ok_or(SpaceTradersError::TokenNotSet)?
-            ))?,
+            HeaderValue::from_str(&format!("Bearer {}", self.token()?))?,
         );
         headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
 
         // Send request
-        let res = self.client.get(url).headers(headers).send().await?;
+        self.rate_limiter.acquire().await;
+        let res = self.get(url).headers(headers).send().await?;
+
+        match res.json::<ResponseData<Shipyard>>()? {
+            ResponseData::Data { data } => {
+                #[cfg(feature = "sqlite")]
+                if let Some(cache) = &self.galaxy_cache {
+                    cache.store_shipyard(&data)?;
+                }
 
-        match res.json::<ResponseData<Shipyard>>().await? {
-            ResponseData::Data { data } => Ok(data),
+                Ok(data)
+            }
             ResponseData::PaginatedData { .. } => unreachable!(),
             ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
         }
     }
 
     pub async fn buy_ship(
-        &mut self,
+        &self,
         ship_type: ShipType,
         waypoint_symbol: Symbol,
     ) -> STResult<ShipyardTransaction> {
-        const URL: &str = "https://api.spacetraders.io/v2/my/ships";
+        let url = format!("{}/my/ships", self.base_url);
 
         let mut headers = HeaderMap::with_capacity(2);
         headers.insert(
             AUTHORIZATION,
-            HeaderValue::from_str(&format!(
-                "Bearer {}",
-                self.token.as_ref().ok_or(SpaceTradersError::TokenNotSet)?
-            ))?,
+            HeaderValue::from_str(&format!("Bearer {}", self.token()?))?,
         );
         headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
         #[derive(Debug, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
         struct BuyShipData {
             ship_type: ShipType,
             waypoint_symbol: Symbol,
         }
 
         // Send request
+        self.rate_limiter.acquire().await;
         let res = self
-            .client
-            .post(URL)
+            .post(url)
             .headers(headers)
             .json(&BuyShipData {
                 ship_type,
@@ -513,23 +526,43 @@ impl SpaceTradersClient {
         struct BuyShipResponse {
             agent: Agent,
             ship: Ship,
-            transactions: ShipyardTransaction,
+            transaction: ShipyardTransaction,
         }
 
-        match res.json::<ResponseData<BuyShipResponse>>().await? {
+        match res.json::<ResponseData<BuyShipResponse>>()? {
             ResponseData::Data { data } => {
-                if let Some(cache) = &mut self.cache {
-                    cache.agent = data.agent;
-                    cache.ships.push(data.ship)
+                if let Ok(cache) = self.shared_cache() {
+                    let ship_symbol = data.ship.symbol.clone();
+                    cache
+                        .ships
+                        .write()
+                        .unwrap()
+                        .push(Arc::new(RwLock::new(data.ship)));
+
+                    self.emit(ClientEvent::ShipPurchased {
+                        ship_symbol: ship_symbol.clone(),
+                    });
+                    self.set_agent(data.agent)?;
+                    self.record_ledger(LedgerEntry {
+                        timestamp: data.transaction.timestamp,
+                        activity: Activity::ShipPurchase,
+                        ship_symbol: Some(ship_symbol),
+                        counterpart: data.transaction.waypoint_symbol.to_string(),
+                        trade_symbol: None,
+                        units: None,
+                        amount: -*data.transaction.price,
+                        balance: self.balance()?,
+                    });
                 } else {
                     // The ship is already purchased at this point, so the transaction is returned
                     // as a part of the error
-                    return Err(SpaceTradersError::EmptyCache(Some(
-                        serde_json::json!(data.transactions).to_string(),
-                    )));
+                    return Err(SpaceTradersError::EmptyCache {
+                        operation: Some("buy_ship"),
+                        data: Some(serde_json::json!(data.transaction).to_string()),
+                    });
                 }
 
-                Ok(data.transactions)
+                Ok(data.transaction)
             }
             ResponseData::PaginatedData { .. } => unreachable!(),
             ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
@@ -537,31 +570,31 @@ impl SpaceTradersClient {
     }
 
     pub async fn dock_ship(&self, ship_symbol: &Symbol) -> STResult<Nav> {
+        // Ships in transit can't dock or orbit, so wait for them to arrive if auto-prepare is on
+        self.await_arrival(ship_symbol).await?;
+
         //  If the ship is already docked, dont make API call
-        let ship = self.get_ship(ship_symbol)?;
+        let ship = self
+            .get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("dock_ship"))?;
         if ship.nav.status == ShipStatus::Docked {
-            return Ok(ship.nav.clone());
+            return Ok(ship.nav);
         }
 
-        let url = format!(
-            "https://api.spacetraders.io/v2/my/ships/{}/dock",
-            ship_symbol
-        );
+        let url = format!("{}/my/ships/{}/dock", self.base_url, ship_symbol);
 
         let mut headers = HeaderMap::with_capacity(2);
         headers.insert(
             AUTHORIZATION,
-            HeaderValue::from_str(&format!(
-                "Bearer {}",
-                self.token.as_ref().ok_or(SpaceTradersError::TokenNotSet)?
-            ))?,
+            HeaderValue::from_str(&format!("Bearer {}", self.token()?))?,
         );
         headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         headers.insert(CONTENT_LENGTH, HeaderValue::from_static("0"));
 
         // Send request
-        let res = self.client.post(url).headers(headers).send().await?;
+        self.rate_limiter.acquire().await;
+        let res = self.post(url).headers(headers).send().await?;
 
         // dbg!(res.json::<serde_json::Value>().await?);
         // todo!();
@@ -571,89 +604,95 @@ impl SpaceTradersClient {
             nav: Nav,
         }
 
-        match res.json::<ResponseData<DockShipResponse>>().await? {
-            ResponseData::Data { data } => Ok(data.nav),
+        match res.json::<ResponseData<DockShipResponse>>()? {
+            ResponseData::Data { data } => {
+                self.update_ship(ship_symbol, |ship| ship.nav = data.nav.clone())?;
+
+                Ok(data.nav)
+            }
             ResponseData::PaginatedData { .. } => unreachable!(),
             ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
         }
     }
 
     pub async fn orbit_ship(&self, ship_symbol: &Symbol) -> STResult<Nav> {
+        // Ships in transit can't dock or orbit, so wait for them to arrive if auto-prepare is on
+        self.await_arrival(ship_symbol).await?;
+
         //  If the ship is already docked, dont make API call
-        let ship = self.get_ship(ship_symbol)?;
+        let ship = self
+            .get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("orbit_ship"))?;
         if ship.nav.status == ShipStatus::InOrbit {
-            return Ok(ship.nav.clone());
+            return Ok(ship.nav);
         }
 
-        let url = format!(
-            "https://api.spacetraders.io/v2/my/ships/{}/orbit",
-            ship_symbol
-        );
+        let url = format!("{}/my/ships/{}/orbit", self.base_url, ship_symbol);
 
         let mut headers = HeaderMap::with_capacity(2);
         headers.insert(
             AUTHORIZATION,
-            HeaderValue::from_str(&format!(
-                "Bearer {}",
-                self.token.as_ref().ok_or(SpaceTradersError::TokenNotSet)?
-            ))?,
+            HeaderValue::from_str(&format!("Bearer {}", self.token()?))?,
         );
         headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         headers.insert(CONTENT_LENGTH, HeaderValue::from_static("0"));
 
         // Send request
-        let res = self.client.post(url).headers(headers).send().await?;
+        self.rate_limiter.acquire().await;
+        let res = self.post(url).headers(headers).send().await?;
 
         #[derive(Debug, Deserialize, Serialize)]
         struct DockShipResponse {
             nav: Nav,
         }
 
-        match res.json::<ResponseData<DockShipResponse>>().await? {
-            ResponseData::Data { data } => Ok(data.nav),
+        match res.json::<ResponseData<DockShipResponse>>()? {
+            ResponseData::Data { data } => {
+                self.update_ship(ship_symbol, |ship| ship.nav = data.nav.clone())?;
+
+                Ok(data.nav)
+            }
             ResponseData::PaginatedData { .. } => unreachable!(),
             ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
         }
     }
 
     pub async fn extract_resources(
-        &mut self,
+        &self,
         ship_symbol: &Symbol,
         survey: Option<Survey>,
     ) -> STResult<(Cooldown, Extraction)> {
         // Check if ship_symbol exists first
-        self.get_ship(ship_symbol)?;
+        self.get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("extract_resources"))?;
+        self.prepare_ship(ship_symbol, Preparation::Orbit).await?;
+        #[cfg(feature = "metrics")]
+        let waypoint_symbol = self.get_ship(ship_symbol)?.nav.waypoint_symbol;
 
-        let url = format!(
-            "https://api.spacetraders.io/v2/my/ships/{}/extract",
-            ship_symbol
-        );
+        let url = format!("{}/my/ships/{}/extract", self.base_url, ship_symbol);
 
         let mut headers = HeaderMap::with_capacity(2);
         headers.insert(
             AUTHORIZATION,
-            HeaderValue::from_str(&format!(
-                "Bearer {}",
-                self.token.as_ref().ok_or(SpaceTradersError::TokenNotSet)?
-            ))?,
+            HeaderValue::from_str(&format!("Bearer {}", self.token()?))?,
         );
         headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         headers.insert(CONTENT_LENGTH, HeaderValue::from_static("0"));
 
         // Send request
+        self.rate_limiter.acquire().await;
         let res = match survey {
             // Send survey as body if there is one
             Some(survey) => {
-                self.client
-                    .post(url)
+                self.post(url)
                     .headers(headers)
-                    .json(&survey)
+                    .json(&serde_json::json!({ "survey": survey }))
                     .send()
                     .await?
             }
-            None => self.client.post(url).headers(headers).send().await?,
+            None => self.post(url).headers(headers).send().await?,
         };
 
         #[derive(Debug, Deserialize, Serialize)]
@@ -663,11 +702,17 @@ impl SpaceTradersClient {
             cargo: Cargo,
         }
 
-        match res.json::<ResponseData<ExtractResourceResponse>>().await? {
+        match res.json::<ResponseData<ExtractResourceResponse>>()? {
             ResponseData::Data { data } => {
                 // Update the ship's cargo with the new cargo
-                let ship = self.get_ship_mut(ship_symbol)?;
-                ship.cargo = data.cargo;
+                self.update_ship(ship_symbol, |ship| ship.cargo = data.cargo)?;
+                self.scheduler
+                    .record_cooldown(ship_symbol, data.cooldown.expiration);
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = &self.metrics {
+                    let extracted = &data.extraction.yield_;
+                    metrics.record_extraction(&waypoint_symbol, &extracted.symbol, extracted.units);
+                }
 
                 Ok((data.cooldown, data.extraction))
             }
@@ -676,50 +721,253 @@ impl SpaceTradersClient {
         }
     }
 
-    fn get_ship_mut(&mut self, ship_symbol: &Symbol) -> STResult<&mut Ship> {
-        match &mut self.cache {
-            Some(cache) => {
-                for ship in &mut cache.ships {
-                    if ship.symbol == *ship_symbol {
-                        return Ok(ship);
-                    }
-                }
+    /// Navigate the ship to a waypoint in its current system.
+    ///
+    /// The ship must be in orbit. The returned [Nav] contains the arrival time of the ship.
+    pub async fn navigate_ship(
+        &self,
+        ship_symbol: &Symbol,
+        waypoint_symbol: &Symbol,
+    ) -> STResult<Nav> {
+        // Check if ship_symbol exists first
+        self.get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("navigate_ship"))?;
+        self.prepare_ship(ship_symbol, Preparation::Orbit).await?;
+
+        let url = format!("{}/my/ships/{}/navigate", self.base_url, ship_symbol);
+
+        // Send request
+        self.rate_limiter.acquire().await;
+        let res = self
+            .post(url)
+            .headers(self.auth_headers()?)
+            .json(&serde_json::json!({ "waypointSymbol": waypoint_symbol }))
+            .send()
+            .await?;
+
+        #[derive(Debug, Deserialize, Serialize)]
+        struct NavigateShipResponse {
+            fuel: Fuel,
+            nav: Nav,
+        }
+
+        match res.json::<ResponseData<NavigateShipResponse>>()? {
+            ResponseData::Data { data } => {
+                self.update_ship(ship_symbol, |ship| {
+                    ship.fuel = data.fuel;
+                    ship.nav = data.nav.clone();
+                })?;
+                self.scheduler.record_nav(ship_symbol, &data.nav);
+
+                Ok(data.nav)
+            }
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
+        }
+    }
 
-                Err(SpaceTradersError::InvalidShipSymbol(format!(
-                    "ShipNotFound: {}",
-                    ship_symbol
-                )))
+    /// Survey the waypoint the ship is orbiting for resource deposits.
+    ///
+    /// The ship must have a surveyor mount. The surveys can be passed to
+    /// [extract_resources](Self::extract_resources) to target specific deposits.
+    pub async fn create_survey(&self, ship_symbol: &Symbol) -> STResult<(Cooldown, Vec<Survey>)> {
+        // Check if ship_symbol exists first
+        self.get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("create_survey"))?;
+        self.prepare_ship(ship_symbol, Preparation::Orbit).await?;
+
+        let url = format!("{}/my/ships/{}/survey", self.base_url, ship_symbol);
+
+        let mut headers = self.auth_headers()?;
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("0"));
+
+        // Send request
+        self.rate_limiter.acquire().await;
+        let res = self.post(url).headers(headers).send().await?;
+
+        #[derive(Debug, Deserialize, Serialize)]
+        struct CreateSurveyResponse {
+            cooldown: Cooldown,
+            surveys: Vec<Survey>,
+        }
+
+        match res.json::<ResponseData<CreateSurveyResponse>>()? {
+            ResponseData::Data { data } => {
+                self.scheduler
+                    .record_cooldown(ship_symbol, data.cooldown.expiration);
+
+                Ok((data.cooldown, data.surveys))
             }
-            None => Err(SpaceTradersError::EmptyCache(None)),
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
+        }
+    }
+
+    /// Refuel the ship at its current waypoint.
+    ///
+    /// The ship must be docked at a waypoint that sells fuel.
+    pub async fn refuel_ship(&self, ship_symbol: &Symbol) -> STResult<MarketTransaction> {
+        // Check if ship_symbol exists first
+        self.get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("refuel_ship"))?;
+        self.prepare_ship(ship_symbol, Preparation::Dock).await?;
+
+        let url = format!("{}/my/ships/{}/refuel", self.base_url, ship_symbol);
+
+        let mut headers = self.auth_headers()?;
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("0"));
+
+        // Send request
+        self.rate_limiter.acquire().await;
+        let res = self.post(url).headers(headers).send().await?;
+
+        #[derive(Debug, Deserialize, Serialize)]
+        struct RefuelShipResponse {
+            agent: Agent,
+            fuel: Fuel,
+            transaction: MarketTransaction,
+        }
+
+        match res.json::<ResponseData<RefuelShipResponse>>()? {
+            ResponseData::Data { data } => {
+                self.set_agent(data.agent)?;
+                self.update_ship(ship_symbol, |ship| ship.fuel = data.fuel)?;
+                self.record_ledger(LedgerEntry::from_market_transaction(
+                    Activity::Refuel,
+                    &data.transaction,
+                    self.balance()?,
+                ));
+
+                Ok(data.transaction)
+            }
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
         }
     }
 
-    fn get_ship(&self, ship_symbol: &Symbol) -> STResult<&Ship> {
-        match &self.cache {
-            Some(cache) => {
-                for ship in &cache.ships {
-                    if ship.symbol == *ship_symbol {
-                        return Ok(ship);
-                    }
+    /// Jettison cargo from the ship.
+    pub async fn jettison_cargo(
+        &self,
+        ship_symbol: &Symbol,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<()> {
+        // Check if ship_symbol exists first
+        self.get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("jettison_cargo"))?;
+        self.prepare_ship(ship_symbol, Preparation::Arrive).await?;
+
+        let url = format!("{}/my/ships/{}/jettison", self.base_url, ship_symbol);
+
+        // Send request
+        self.rate_limiter.acquire().await;
+        let res = self
+            .post(url)
+            .headers(self.auth_headers()?)
+            .json(&serde_json::json!({ "symbol": trade_symbol, "units": units }))
+            .send()
+            .await?;
+
+        #[derive(Debug, Deserialize, Serialize)]
+        struct JettisonCargoResponse {
+            cargo: Cargo,
+        }
+
+        match res.json::<ResponseData<JettisonCargoResponse>>()? {
+            ResponseData::Data { data } => {
+                self.update_ship(ship_symbol, |ship| ship.cargo = data.cargo)?;
+
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_jettison(ship_symbol, trade_symbol, units);
                 }
 
-                Err(SpaceTradersError::InvalidShipSymbol(format!(
-                    "ShipNotFound: {}",
-                    ship_symbol
-                )))
+                Ok(())
             }
-            None => Err(SpaceTradersError::EmptyCache(None)),
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
+        }
+    }
+
+    /// Get the lock guarding the cached ship with the given symbol.
+    fn ship_lock(&self, ship_symbol: &Symbol) -> STResult<Arc<RwLock<Ship>>> {
+        let cache = self.shared_cache()?;
+        let ships = cache.ships.read().unwrap();
+
+        ships
+            .iter()
+            .find(|ship| ship.read().unwrap().symbol == *ship_symbol)
+            .cloned()
+            .ok_or_else(|| SpaceTradersError::InvalidShipSymbol {
+                ship_symbol: ship_symbol.to_string(),
+                operation: None,
+            })
+    }
+
+    /// Get a copy of the cached ship with the given symbol.
+    pub(crate) fn get_ship(&self, ship_symbol: &Symbol) -> STResult<Ship> {
+        Ok(self.ship_lock(ship_symbol)?.read().unwrap().clone())
+    }
+
+    /// Updates the cached ship with the given symbol.
+    ///
+    /// Only the ship itself is locked while `update` runs, so other ships can still be used.
+    pub(crate) fn update_ship<R>(
+        &self,
+        ship_symbol: &Symbol,
+        update: impl FnOnce(&mut Ship) -> R,
+    ) -> STResult<R> {
+        let lock = self.ship_lock(ship_symbol)?;
+
+        // Report everything that changed, after releasing the lock
+        let (before, result, after) = {
+            let mut ship = lock.write().unwrap();
+            let before = (ship.nav.clone(), ship.cargo.clone(), ship.fuel.clone());
+            let result = update(&mut ship);
+
+            (
+                before,
+                result,
+                (ship.nav.clone(), ship.cargo.clone(), ship.fuel.clone()),
+            )
+        };
+
+        if before.0 != after.0 {
+            self.emit(ClientEvent::NavChanged {
+                ship_symbol: ship_symbol.clone(),
+                before: Box::new(before.0),
+                after: Box::new(after.0),
+            });
+        }
+        if before.1 != after.1 {
+            self.emit(ClientEvent::CargoChanged {
+                ship_symbol: ship_symbol.clone(),
+                before: before.1,
+                after: after.1,
+            });
         }
+        if before.2 != after.2 {
+            self.emit(ClientEvent::FuelChanged {
+                ship_symbol: ship_symbol.clone(),
+                before: before.2,
+                after: after.2,
+            });
+        }
+
+        Ok(result)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "mock"))]
 mod tests {
     use super::*;
+    use crate::mock::MockServer;
 
     #[tokio::test]
     async fn can_view_shipyard() -> STResult<()> {
-        let client = SpaceTradersClient::load_saved()?;
+        let server = MockServer::start().await?;
+        let client = server.client();
+        client.register_callsign("TST-RS-04", None).await?;
 
         let system_symbol = Symbol::new("X1-ZA40").unwrap();
         let waypoint_symbol = Symbol::new("X1-ZA40-68707C").unwrap();
@@ -747,9 +995,11 @@ mod tests {
 
     #[tokio::test]
     async fn can_dock_and_orbit_ship() -> STResult<()> {
-        let client = SpaceTradersClient::load_saved()?;
+        let server = MockServer::start().await?;
+        let client = server.client();
+        client.register_callsign("TST-RS-04", None).await?;
 
-        let ship = &client.cache.as_ref().unwrap().ships[0];
+        let ship = &client.ships()?[0];
 
         let nav = client.dock_ship(&ship.symbol).await?;
         assert_eq!(nav.status, ShipStatus::Docked);
@@ -0,0 +1,284 @@
+//! A typestate API for controlling a single ship.
+//!
+//! Many ship actions are only valid in a specific state: extracting and navigating require the
+//! ship to be in orbit, trading and refuelling require it to be docked, and nothing can be done
+//! while it is in transit. A [ShipHandle] tracks the state of the ship in its type, so only the
+//! actions valid in that state are available, and invalid sequences fail to compile.
+//!
+//! Transitions consume the handle and return one in the new state. If a transition fails, the
+//! handle is lost; a new one can be created from the client's cache with
+//! [ship_handle](SpaceTradersClient::ship_handle).
+//!
+//! # Example
+//! ```no_run
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let client = SpaceTradersClient::load_saved().unwrap();
+//! let ship = Symbol::new("TST-RS-04-1").unwrap();
+//!
+//! let docked = match client.ship_handle(&ship).unwrap() {
+//!     AnyShipHandle::Docked(ship) => ship,
+//!     AnyShipHandle::InOrbit(ship) => ship.dock().await.unwrap(),
+//!     AnyShipHandle::InTransit(ship) => ship.await_arrival().await.unwrap().dock().await.unwrap(),
+//! };
+//! docked.refuel().await.unwrap();
+//!
+//! let in_orbit = docked.orbit().await.unwrap();
+//! let in_transit = in_orbit
+//!     .navigate(&Symbol::new("X1-ZA40-99095A").unwrap())
+//!     .await
+//!     .unwrap();
+//!
+//! let in_orbit = in_transit.await_arrival().await.unwrap();
+//! in_orbit.extract(None).await.unwrap();
+//! # })
+//! ```
+//!
+//! Actions that require a different state don't exist on the handle:
+//! ```compile_fail
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! # let client = SpaceTradersClient::load_saved().unwrap();
+//! # let ship = Symbol::new("TST-RS-04-1").unwrap();
+//! if let AnyShipHandle::Docked(docked) = client.ship_handle(&ship).unwrap() {
+//!     // Extracting requires the ship to be in orbit
+//!     docked.extract(None).await.unwrap();
+//! }
+//! # })
+//! ```
+
+use crate::{
+    conditional_types::{Id, Symbol},
+    market::MarketTransaction,
+    ship::{Cooldown, Extraction, Nav, ShipStatus, Survey},
+    space_traders_client::SpaceTradersClient,
+    STResult,
+};
+use chrono::{DateTime, Utc};
+use std::marker::PhantomData;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A state a ship can be in.
+///
+/// This trait is sealed, and is only implemented by [Docked], [InOrbit] and [InTransit].
+pub trait ShipState: sealed::Sealed {}
+
+/// The ship is docked at a waypoint.
+#[derive(Debug, Clone, Copy)]
+pub struct Docked;
+
+/// The ship is orbiting a waypoint.
+#[derive(Debug, Clone, Copy)]
+pub struct InOrbit;
+
+/// The ship is travelling to a waypoint.
+#[derive(Debug, Clone, Copy)]
+pub struct InTransit;
+
+impl sealed::Sealed for Docked {}
+impl sealed::Sealed for InOrbit {}
+impl sealed::Sealed for InTransit {}
+impl ShipState for Docked {}
+impl ShipState for InOrbit {}
+impl ShipState for InTransit {}
+
+/// A ship whose current state is tracked by its type.
+#[derive(Debug, Clone)]
+pub struct ShipHandle<S: ShipState> {
+    client: SpaceTradersClient,
+    ship_symbol: Symbol,
+    state: PhantomData<S>,
+}
+
+/// A [ShipHandle] in whichever state the ship is currently in.
+#[derive(Debug, Clone)]
+pub enum AnyShipHandle {
+    Docked(ShipHandle<Docked>),
+    InOrbit(ShipHandle<InOrbit>),
+    InTransit(ShipHandle<InTransit>),
+}
+
+impl SpaceTradersClient {
+    /// Creates a [ShipHandle] in the state the ship is in, according to the client's cache.
+    pub fn ship_handle(&self, ship_symbol: &Symbol) -> STResult<AnyShipHandle> {
+        let ship = self
+            .get_ship(ship_symbol)
+            .map_err(|err| err.in_operation("ship_handle"))?;
+
+        Ok(match ship.nav.status {
+            ShipStatus::Docked => AnyShipHandle::Docked(ShipHandle::new(self, ship_symbol)),
+            ShipStatus::InOrbit => AnyShipHandle::InOrbit(ShipHandle::new(self, ship_symbol)),
+            ShipStatus::InTransit => AnyShipHandle::InTransit(ShipHandle::new(self, ship_symbol)),
+        })
+    }
+}
+
+impl<S: ShipState> ShipHandle<S> {
+    fn new(client: &SpaceTradersClient, ship_symbol: &Symbol) -> Self {
+        Self {
+            client: client.clone(),
+            ship_symbol: ship_symbol.clone(),
+            state: PhantomData,
+        }
+    }
+
+    fn transition<T: ShipState>(self) -> ShipHandle<T> {
+        ShipHandle {
+            client: self.client,
+            ship_symbol: self.ship_symbol,
+            state: PhantomData,
+        }
+    }
+
+    pub fn ship_symbol(&self) -> &Symbol {
+        &self.ship_symbol
+    }
+
+    pub fn client(&self) -> &SpaceTradersClient {
+        &self.client
+    }
+
+    /// Get the ship's current navigation info.
+    pub fn nav(&self) -> STResult<Nav> {
+        Ok(self.client.get_ship(&self.ship_symbol)?.nav)
+    }
+}
+
+impl ShipHandle<Docked> {
+    /// Moves the ship into orbit.
+    pub async fn orbit(self) -> STResult<ShipHandle<InOrbit>> {
+        self.client.orbit_ship(&self.ship_symbol).await?;
+
+        Ok(self.transition())
+    }
+
+    pub async fn sell_cargo(
+        &self,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<MarketTransaction> {
+        self.client
+            .sell_cargo(&self.ship_symbol, trade_symbol, units)
+            .await
+    }
+
+    pub async fn purchase_cargo(
+        &self,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<MarketTransaction> {
+        self.client
+            .purchase_cargo(&self.ship_symbol, trade_symbol, units)
+            .await
+    }
+
+    pub async fn refuel(&self) -> STResult<MarketTransaction> {
+        self.client.refuel_ship(&self.ship_symbol).await
+    }
+
+    pub async fn deliver_contract(
+        &self,
+        contract_id: &Id,
+        trade_symbol: &Symbol,
+        units: i32,
+    ) -> STResult<()> {
+        self.client
+            .deliver_contract(contract_id, &self.ship_symbol, trade_symbol, units)
+            .await
+    }
+
+    pub async fn jettison_cargo(&self, trade_symbol: &Symbol, units: i32) -> STResult<()> {
+        self.client
+            .jettison_cargo(&self.ship_symbol, trade_symbol, units)
+            .await
+    }
+}
+
+impl ShipHandle<InOrbit> {
+    /// Docks the ship at the waypoint it is orbiting.
+    pub async fn dock(self) -> STResult<ShipHandle<Docked>> {
+        self.client.dock_ship(&self.ship_symbol).await?;
+
+        Ok(self.transition())
+    }
+
+    /// Navigates the ship to a waypoint in its current system.
+    pub async fn navigate(self, waypoint_symbol: &Symbol) -> STResult<ShipHandle<InTransit>> {
+        self.client
+            .navigate_ship(&self.ship_symbol, waypoint_symbol)
+            .await?;
+
+        Ok(self.transition())
+    }
+
+    pub async fn extract(&self, survey: Option<Survey>) -> STResult<(Cooldown, Extraction)> {
+        self.client
+            .extract_resources(&self.ship_symbol, survey)
+            .await
+    }
+
+    pub async fn create_survey(&self) -> STResult<(Cooldown, Vec<Survey>)> {
+        self.client.create_survey(&self.ship_symbol).await
+    }
+
+    pub async fn jettison_cargo(&self, trade_symbol: &Symbol, units: i32) -> STResult<()> {
+        self.client
+            .jettison_cargo(&self.ship_symbol, trade_symbol, units)
+            .await
+    }
+}
+
+impl ShipHandle<InTransit> {
+    /// The time the ship will arrive at its destination.
+    pub fn arrival(&self) -> STResult<DateTime<Utc>> {
+        Ok(self.nav()?.route.arrival)
+    }
+
+    /// Waits until the ship arrives at its destination, where it will be in orbit.
+    pub async fn await_arrival(self) -> STResult<ShipHandle<InOrbit>> {
+        let arrival = self.arrival()?;
+        self.client.scheduler().clock().sleep_until(arrival).await;
+
+        self.client.update_ship(&self.ship_symbol, |ship| {
+            if ship.nav.status == ShipStatus::InTransit {
+                ship.nav.status = ShipStatus::InOrbit;
+            }
+        })?;
+
+        Ok(self.transition())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[tokio::test]
+    async fn arrives_in_orbit() -> STResult<()> {
+        let client = fixtures::client(vec![fixtures::ship_in_transit()], vec![]);
+        let ship_symbol = Symbol::new("TST-RS-04-1").unwrap();
+
+        let in_transit = match client.ship_handle(&ship_symbol)? {
+            AnyShipHandle::InTransit(ship) => ship,
+            handle => panic!("expected the ship to be in transit: {:?}", handle),
+        };
+        assert_eq!(
+            in_transit.arrival()?.to_rfc3339(),
+            "2023-05-17T04:18:35.930+00:00"
+        );
+
+        // The arrival time has already passed, so this doesn't wait
+        let in_orbit = in_transit.await_arrival().await?;
+        assert_eq!(in_orbit.nav()?.status, ShipStatus::InOrbit);
+        assert!(matches!(
+            client.ship_handle(&ship_symbol)?,
+            AnyShipHandle::InOrbit(_)
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+//! Runs strategies against a local, simulated game in virtual time.
+//!
+//! A [Simulator] serves the same universe as the [MockServer], including travel times based on
+//! each ship's engine speed and flight mode, fuel use, extraction yields, cooldowns, and market
+//! prices that move with every trade and recover over time. The difference is its [VirtualClock]:
+//! instead of waiting in real time, a task that sleeps on the clock moves it straight to the time
+//! it is waiting for. Strategies that wait through the client's
+//! [Scheduler](crate::scheduler::Scheduler), like the [MiningLoop](crate::mining::MiningLoop), can
+//! therefore play a whole day in a few seconds.
+//!
+//! Simulated requests don't take any virtual time. A single task waiting on the clock gets the
+//! same results on every run. When several tasks run at once, the clock only moves to the
+//! earliest time any of them is waiting for, but a task that is busy with a request while the
+//! others sleep may find that time has passed when its request completes, so their results can
+//! vary from run to run with the order the runtime schedules them in.
+//!
+//! The simulator is only available with the `simulator` feature.
+//!
+//! # Example
+//! ```
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! let simulator = Simulator::start("2023-05-17T00:00:00Z".parse().unwrap())
+//!     .await
+//!     .unwrap();
+//! let client = simulator.client();
+//! client.register_callsign("BACKTEST", None).await.unwrap();
+//!
+//! let config = MiningConfig::new(
+//!     Symbol::new("X1-ZA40-99095A").unwrap(),
+//!     Symbol::new("X1-ZA40-15970B").unwrap(),
+//! );
+//! let mut mining = MiningLoop::new(Symbol::new("BACKTEST-1").unwrap(), config);
+//!
+//! // Mine for two hours of game time
+//! simulator
+//!     .run_for(chrono::Duration::hours(2), mining.run(&client))
+//!     .await;
+//! assert!(simulator.elapsed() >= chrono::Duration::hours(2));
+//! # })
+//! ```
+
+use crate::{
+    mock::MockServer,
+    scheduler::{Clock, Sleep},
+    space_traders_client::SpaceTradersClient,
+    STResult,
+};
+use chrono::{DateTime, Duration, Utc};
+use std::{future::Future, sync::Arc};
+use tokio::sync::watch;
+
+#[derive(Debug, Clone)]
+struct VirtualTime {
+    now: DateTime<Utc>,
+    /// The deadlines of every task currently sleeping on the clock.
+    sleepers: Vec<DateTime<Utc>>,
+}
+
+/// A [Clock] that skips ahead to the earliest time any task is sleeping until.
+///
+/// The clock can also be moved forward manually, like a
+/// [ManualClock](crate::scheduler::ManualClock).
+#[derive(Debug)]
+pub struct VirtualClock {
+    time: watch::Sender<VirtualTime>,
+}
+
+impl VirtualClock {
+    /// Creates a clock starting at `now`.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            time: watch::channel(VirtualTime {
+                now,
+                sleepers: vec![],
+            })
+            .0,
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.time.send_modify(|time| time.now += duration);
+    }
+
+    /// Moves the clock to `time`, if it is later than the current time.
+    pub fn advance_to(&self, time: DateTime<Utc>) {
+        self.time.send_if_modified(|current| {
+            let modified = time > current.now;
+            current.now = current.now.max(time);
+            modified
+        });
+    }
+
+    /// Waits until the clock reaches `deadline`, without moving it.
+    pub async fn reached(&self, deadline: DateTime<Utc>) {
+        let mut time = self.time.subscribe();
+
+        // The sender is owned by the clock, which outlives the future
+        while time.borrow_and_update().now < deadline {
+            let _ = time.changed().await;
+        }
+    }
+}
+
+/// Removes a sleeper's deadline when it wakes up or is cancelled.
+struct Sleeper<'a> {
+    clock: &'a VirtualClock,
+    deadline: DateTime<Utc>,
+}
+
+impl Drop for Sleeper<'_> {
+    fn drop(&mut self) {
+        self.clock.time.send_modify(|time| {
+            if let Some(i) = time.sleepers.iter().position(|&d| d == self.deadline) {
+                time.sleepers.swap_remove(i);
+            }
+        });
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.time.borrow().now
+    }
+
+    fn sleep_until(&self, deadline: DateTime<Utc>) -> Sleep<'_> {
+        Box::pin(async move {
+            let mut time = self.time.subscribe();
+            self.time.send_modify(|time| time.sleepers.push(deadline));
+            let _sleeper = Sleeper {
+                clock: self,
+                deadline,
+            };
+
+            loop {
+                if time.borrow_and_update().now >= deadline {
+                    return;
+                }
+
+                // Give other tasks that are about to sleep a chance to register their deadlines
+                tokio::task::yield_now().await;
+                self.time.send_if_modified(|time| {
+                    let earliest = time.sleepers.iter().min().copied().unwrap_or(deadline);
+                    let modified = earliest > time.now;
+                    time.now = time.now.max(earliest);
+                    modified
+                });
+
+                if time.borrow_and_update().now >= deadline {
+                    return;
+                }
+                let _ = time.changed().await;
+            }
+        })
+    }
+}
+
+/// A simulated game, running on a [VirtualClock].
+#[derive(Debug)]
+pub struct Simulator {
+    server: MockServer,
+    clock: Arc<VirtualClock>,
+    start: DateTime<Utc>,
+}
+
+impl Simulator {
+    /// Starts a simulation at the given game time.
+    pub async fn start(start: DateTime<Utc>) -> STResult<Self> {
+        let clock = Arc::new(VirtualClock::new(start));
+        let server = MockServer::start_with_clock(clock.clone()).await?;
+
+        Ok(Self {
+            server,
+            clock,
+            start,
+        })
+    }
+
+    /// Creates a client that plays the simulated game.
+    ///
+    /// The client's [Scheduler](crate::scheduler::Scheduler) uses the simulator's clock.
+    pub fn client(&self) -> SpaceTradersClient {
+        self.server.client()
+    }
+
+    pub fn clock(&self) -> &Arc<VirtualClock> {
+        &self.clock
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// The game time that has passed since the simulation started.
+    pub fn elapsed(&self) -> Duration {
+        self.now() - self.start
+    }
+
+    /// Runs a strategy until it finishes, or until at least `duration` of game time has passed.
+    ///
+    /// Returns the strategy's output, or `None` if it was stopped. The strategy is only stopped
+    /// once the clock passes the end of the duration, so it may overshoot by up to the length of
+    /// the strategy's last sleep.
+    pub async fn run_for<F: Future>(&self, duration: Duration, strategy: F) -> Option<F::Output> {
+        let end = self.now() + duration;
+
+        tokio::select! {
+            output = strategy => Some(output),
+            _ = self.clock.reached(end) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        conditional_types::Symbol,
+        mining::{MiningConfig, MiningEvent, MiningLoop},
+    };
+    use serde_json::Value;
+
+    fn start() -> DateTime<Utc> {
+        "2023-05-17T04:18:05.930Z".parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn sleepers_wake_in_order() {
+        let clock = VirtualClock::new(start());
+        let (early, late) = (
+            start() + Duration::seconds(30),
+            start() + Duration::seconds(70),
+        );
+
+        let woke_at = |deadline| {
+            let clock = &clock;
+            async move {
+                clock.sleep_until(deadline).await;
+                clock.now()
+            }
+        };
+        let (late_at, early_at) = tokio::join!(woke_at(late), woke_at(early));
+
+        assert_eq!(early_at, early);
+        assert_eq!(late_at, late);
+    }
+
+    #[tokio::test]
+    async fn mines_for_a_day_in_virtual_time() -> STResult<()> {
+        let simulator = Simulator::start(start()).await?;
+        let client = simulator.client();
+        client.register_callsign("BACKTEST", None).await?;
+
+        let headquarters = Symbol::new("X1-ZA40-15970B").unwrap();
+        let config = MiningConfig::new(Symbol::new("X1-ZA40-99095A").unwrap(), headquarters);
+        let mut mining = MiningLoop::new(Symbol::new("BACKTEST-1").unwrap(), config);
+        let mut events = mining.subscribe();
+
+        let output = simulator
+            .run_for(Duration::days(1), mining.run(&client))
+            .await;
+        assert!(output.is_none());
+        assert!(simulator.elapsed() >= Duration::days(1));
+        assert!(simulator.elapsed() < Duration::days(1) + Duration::hours(1));
+
+        // The ship made many trips to sell its cargo
+        let mut sold = 0;
+        while let Ok(event) = events.try_recv() {
+            if let crate::mining::MiningEvent::Sold { total_price, .. } = event {
+                sold += total_price;
+            }
+        }
+        assert!(sold > 10_000);
+        assert!(client.agent()?.credits > 100_000);
+
+        Ok(())
+    }
+
+    /// Mines for a few hours, returning the final agent and ships, and the events reported.
+    async fn mine_for_a_few_hours() -> STResult<(Value, Value, Vec<MiningEvent>)> {
+        let simulator = Simulator::start(start()).await?;
+        let client = simulator.client();
+        client.register_callsign("BACKTEST", None).await?;
+
+        let headquarters = Symbol::new("X1-ZA40-15970B").unwrap();
+        let config = MiningConfig::new(Symbol::new("X1-ZA40-99095A").unwrap(), headquarters);
+        let mut mining = MiningLoop::new(Symbol::new("BACKTEST-1").unwrap(), config);
+        let mut rx = mining.subscribe();
+
+        let output = simulator
+            .run_for(Duration::hours(6), mining.run(&client))
+            .await;
+        assert!(output.is_none());
+
+        let mut events = vec![];
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+
+        Ok((
+            serde_json::to_value(client.agent()?)?,
+            serde_json::to_value(client.ships()?)?,
+            events,
+        ))
+    }
+
+    #[tokio::test]
+    async fn replays_the_same_scenario_identically() -> STResult<()> {
+        let (agent, ships, events) = mine_for_a_few_hours().await?;
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, MiningEvent::Sold { .. })));
+
+        let (replayed_agent, replayed_ships, replayed_events) = mine_for_a_few_hours().await?;
+        assert_eq!(replayed_agent, agent);
+        assert_eq!(replayed_ships, ships);
+        assert_eq!(replayed_events, events);
+
+        Ok(())
+    }
+}
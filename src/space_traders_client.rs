@@ -4,12 +4,27 @@ use crate::{
     agent::Agent,
     conditional_types::strings::Symbol,
     contract::Contract,
+    events::ClientEvent,
     faction::{Faction, FactionSymbol},
     ship::Ship,
     ResponseData, STResult, SpaceTradersError,
 };
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, vec};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, RwLock},
+    vec,
+};
+
+/// The file [load_saved](SpaceTradersClient::load_saved) and
+/// [save_client](SpaceTradersClient::save_client) use.
+pub const SAVEFILE: &str = "./spacetraders.save";
+
+/// The URL all API calls are made to, unless changed with
+/// [set_base_url](SpaceTradersClient::set_base_url).
+pub const DEFAULT_BASE_URL: &str = "https://api.spacetraders.io/v2";
 
 /// Values cached from initial registration
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,22 +35,104 @@ pub(crate) struct CachedInfo {
     pub(crate) ships: Vec<Ship>,
 }
 
-/// The client used to interact with the `SpaceTraders API`.
+/// The [CachedInfo] shared by every clone of a client.
+///
+/// Each ship and contract has its own lock, so tasks controlling different ships don't block
+/// each other.
 #[derive(Debug)]
+pub(crate) struct SharedCache {
+    pub(crate) agent: RwLock<Agent>,
+    pub(crate) contracts: RwLock<Vec<Arc<RwLock<Contract>>>>,
+    pub(crate) faction: Faction,
+    pub(crate) ships: RwLock<Vec<Arc<RwLock<Ship>>>>,
+}
+
+impl From<CachedInfo> for SharedCache {
+    fn from(cache: CachedInfo) -> Self {
+        Self {
+            agent: RwLock::new(cache.agent),
+            contracts: RwLock::new(
+                cache
+                    .contracts
+                    .into_iter()
+                    .map(|contract| Arc::new(RwLock::new(contract)))
+                    .collect(),
+            ),
+            faction: cache.faction,
+            ships: RwLock::new(
+                cache
+                    .ships
+                    .into_iter()
+                    .map(|ship| Arc::new(RwLock::new(ship)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl SharedCache {
+    /// Copies the current values out of the cache.
+    pub(crate) fn snapshot(&self) -> CachedInfo {
+        CachedInfo {
+            agent: self.agent.read().unwrap().clone(),
+            contracts: self
+                .contracts
+                .read()
+                .unwrap()
+                .iter()
+                .map(|contract| contract.read().unwrap().clone())
+                .collect(),
+            faction: self.faction.clone(),
+            ships: self
+                .ships
+                .read()
+                .unwrap()
+                .iter()
+                .map(|ship| ship.read().unwrap().clone())
+                .collect(),
+        }
+    }
+}
+
+/// The client used to interact with the `SpaceTraders API`.
+///
+/// Cloning the client is cheap: clones share the same token, cache and rate limit, so ships can be
+/// controlled concurrently from multiple tasks.
+#[derive(Debug, Clone)]
 pub struct SpaceTradersClient {
-    pub(crate) client: reqwest::Client,
-    pub(crate) token: Option<String>,
-    pub(crate) token_set: bool,
-    pub(crate) cache: Option<CachedInfo>,
+    pub(crate) transport: Arc<dyn crate::transport::Transport>,
+    pub(crate) base_url: String,
+    pub(crate) token: Arc<RwLock<Option<String>>>,
+    pub(crate) cache: Arc<RwLock<Option<Arc<SharedCache>>>>,
+    pub(crate) market_store: Option<Arc<dyn crate::market_history::MarketStore>>,
+    pub(crate) ledger: Option<Arc<dyn crate::ledger::Ledger>>,
+    pub(crate) rate_limiter: Arc<crate::rate_limiter::RateLimiter>,
+    pub(crate) auto_prepare: bool,
+    pub(crate) scheduler: Arc<crate::scheduler::Scheduler>,
+    pub(crate) events: tokio::sync::broadcast::Sender<crate::events::ClientEvent>,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: Option<crate::metrics::Metrics>,
+    #[cfg(feature = "sqlite")]
+    pub(crate) galaxy_cache: Option<Arc<crate::galaxy_cache::GalaxyCache>>,
 }
 
 impl Default for SpaceTradersClient {
     fn default() -> Self {
         Self {
-            client: reqwest::Client::new(),
-            token: None,
-            token_set: false,
-            cache: None,
+            transport: Arc::new(crate::transport::ReqwestTransport::default()),
+            base_url: DEFAULT_BASE_URL.into(),
+            token: Default::default(),
+            cache: Default::default(),
+            market_store: None,
+            ledger: None,
+            rate_limiter: Default::default(),
+            auto_prepare: false,
+            scheduler: Default::default(),
+            events: tokio::sync::broadcast::channel(crate::events::EVENT_CAPACITY).0,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "sqlite")]
+            galaxy_cache: None,
         }
     }
 }
@@ -61,64 +158,114 @@ impl SpaceTradersClient {
     /// let client = SpaceTradersClient::load_saved();
     /// ```
     pub fn load_saved() -> STResult<Self> {
-        use std::fs::File;
-        use std::io::BufRead;
-        use std::io::BufReader;
+        Self::load_saved_from(SAVEFILE)
+    }
 
-        const SAVEFILE: &str = "./spacetraders.save";
+    /// Load a `SpaceTradersClient` from the save file at the given path.
+    ///
+    /// See [load_saved](Self::load_saved) for details.
+    pub fn load_saved_from(path: impl AsRef<Path>) -> STResult<Self> {
+        let path = path.as_ref();
+        Self::from_save(&std::fs::read_to_string(path)?, path)
+    }
+
+    /// Creates a client from the contents of a save file.
+    ///
+    /// Saves made without the token have an empty first line, so the client has no token.
+    pub(crate) fn from_save(contents: &str, path: &Path) -> STResult<Self> {
+        let invalid_save = || SpaceTradersError::InvalidSave(path.display().to_string());
 
-        let mut save_data = BufReader::new(File::open(SAVEFILE)?).lines();
+        let mut save_data = contents.lines();
 
         // Read first line and get token
-        let token = save_data
-            .next()
-            .ok_or_else(|| SpaceTradersError::InvalidSave(SAVEFILE.into()))??;
+        let token = save_data.next().ok_or_else(invalid_save)?;
+        if token == crate::secrets::ENCRYPTED_SAVE_HEADER {
+            return Err(SpaceTradersError::InvalidSave(format!(
+                "{} is encrypted, and can only be loaded with its key",
+                path.display()
+            )));
+        }
 
         // Read second line and get cache
-        let cache: CachedInfo = serde_json::from_str(
-            &save_data
-                .next()
-                .ok_or_else(|| SpaceTradersError::InvalidSave(SAVEFILE.into()))??,
-        )?;
-
-        Ok(Self {
-            client: reqwest::Client::new(),
-            token: Some(token),
-            token_set: true,
-            cache: Some(cache),
-        })
+        let cache: CachedInfo = serde_json::from_str(save_data.next().ok_or_else(invalid_save)?)?;
+
+        let client = Self {
+            transport: Arc::new(crate::transport::ReqwestTransport::default()),
+            base_url: DEFAULT_BASE_URL.into(),
+            token: Arc::new(RwLock::new(
+                Some(token.to_string()).filter(|token| !token.is_empty()),
+            )),
+            cache: Default::default(),
+            market_store: None,
+            ledger: None,
+            rate_limiter: Default::default(),
+            auto_prepare: false,
+            scheduler: Default::default(),
+            events: tokio::sync::broadcast::channel(crate::events::EVENT_CAPACITY).0,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "sqlite")]
+            galaxy_cache: None,
+        };
+        client.set_cache(cache);
+
+        Ok(client)
     }
 
     /// Saves the `SpaceTradersClient` to a file named `spacetraders.save`.
     ///
     /// This data can be retrieved using [load_saved](SpaceTradersClient::load_saved).
     pub fn save_client(&self) -> STResult<()> {
-        use std::fs::File;
-        use std::io::Write;
+        self.save_client_to(SAVEFILE)
+    }
 
-        const SAVEFILE: &str = "./spacetraders.save";
+    /// Saves the `SpaceTradersClient` to the file at the given path.
+    ///
+    /// The token is saved in plaintext, so on Unix the file is only readable by its owner. To keep
+    /// the token elsewhere, see [save_cache_to](Self::save_cache_to) and the
+    /// [secrets](crate::secrets) module.
+    ///
+    /// This data can be retrieved using [load_saved_from](SpaceTradersClient::load_saved_from).
+    pub fn save_client_to(&self, path: impl AsRef<Path>) -> STResult<()> {
+        let contents = self.save_contents(Some(&self.token()?))?;
+        crate::secrets::write_private(path.as_ref(), contents.as_bytes())?;
 
-        if !self.token_set || self.token.is_none() {
-            return Err(SpaceTradersError::TokenNotSet);
-        }
+        Ok(())
+    }
 
-        // Save cached data
-        if self.cache.is_some() {
-            let token = self.token.as_ref().unwrap();
-            let mut cached_data = File::create(SAVEFILE)?;
-            let file_contents = format!(
-                "{}\n{}",
-                token,
-                serde_json::to_string(self.cache.as_ref().unwrap())?
-            );
-            cached_data.write_all(file_contents.as_bytes())?;
-        } else {
-            return Err(SpaceTradersError::EmptyCache(None));
-        }
+    /// Saves the cached data of the `SpaceTradersClient` to the file at the given path, without
+    /// the token.
+    ///
+    /// A client loaded from this file has no token until one is set, e.g. with
+    /// [load_with_store](Self::load_with_store).
+    pub fn save_cache_to(&self, path: impl AsRef<Path>) -> STResult<()> {
+        std::fs::write(path, self.save_contents(None)?)?;
 
         Ok(())
     }
 
+    /// The contents of a save file: the token on the first line, and the cache on the second.
+    pub(crate) fn save_contents(&self, token: Option<&str>) -> STResult<String> {
+        let cache = self.shared_cache()?.snapshot();
+
+        Ok(format!(
+            "{}\n{}",
+            token.unwrap_or_default(),
+            serde_json::to_string(&cache)?
+        ))
+    }
+
+    /// Sets the URL API calls are made to, e.g. to use a mock server or a proxy.
+    ///
+    /// Defaults to [DEFAULT_BASE_URL]. The URL is copied to clones made after this call.
+    pub fn set_base_url(&mut self, base_url: impl Into<String>) {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
     /// Registers the given (unique) callsign with the Space Traders API.
     ///
     /// The response from the API is used to populate the fields of the [SpaceTradersClient].
@@ -128,7 +275,7 @@ impl SpaceTradersClient {
     /// # use space_traders::prelude::*;
     /// # tokio_test::block_on(async {
     /// // Creates SpaceTradersClient with the `client` field initalized.
-    /// let mut client = SpaceTradersClient::default();
+    /// let client = SpaceTradersClient::default();
     ///
     /// // Populates the `cache` field with values from the response.
     /// // Note: callsign must be unique or an error is returned!
@@ -136,7 +283,7 @@ impl SpaceTradersClient {
     /// # })
     /// ```
     pub async fn register_callsign(
-        &mut self,
+        &self,
         callsign: &str,
         faction: Option<FactionSymbol>,
     ) -> STResult<()> {
@@ -147,7 +294,7 @@ impl SpaceTradersClient {
             return Err(SpaceTradersError::InvalidCallsignLength);
         }
 
-        const URL: &str = "https://api.spacetraders.io/v2/register";
+        let url = format!("{}/register", self.base_url);
         const HEADER: (HeaderName, &str) = (CONTENT_TYPE, "application/json");
 
         let mut data = HashMap::with_capacity(1);
@@ -159,9 +306,9 @@ impl SpaceTradersClient {
         }
 
         // Send request
+        self.rate_limiter.acquire().await;
         let res = self
-            .client
-            .post(URL)
+            .post(url)
             .header(HEADER.0, HEADER.1)
             .json(&data)
             .send()
@@ -177,12 +324,11 @@ impl SpaceTradersClient {
             ship: Ship,
         }
 
-        match res.json::<ResponseData<RegistrationResponse>>().await? {
+        match res.json::<ResponseData<RegistrationResponse>>()? {
             ResponseData::Data { data } => {
-                self.token = Some(data.token);
-                self.token_set = true;
+                *self.token.write().unwrap() = Some(data.token);
 
-                self.cache = Some(CachedInfo {
+                self.set_cache(CachedInfo {
                     agent: data.agent,
                     contracts: vec![data.contract],
                     faction: data.faction,
@@ -197,47 +343,124 @@ impl SpaceTradersClient {
         }
     }
 
-    /// Get a reference to the [Agent] associated with the current client.
-    pub fn agent(&self) -> STResult<&Agent> {
-        if let Some(cache) = &self.cache {
-            return Ok(&cache.agent);
-        }
-
-        Err(SpaceTradersError::EmptyCache(None))
+    /// Get the [Agent] associated with the current client.
+    pub fn agent(&self) -> STResult<Agent> {
+        Ok(self.shared_cache()?.agent.read().unwrap().clone())
     }
 
-    /// Get a mutable reference to the [Agent] associated with the current client.
-    pub fn agent_mut(&mut self) -> STResult<&mut Agent> {
-        if let Some(cache) = &mut self.cache {
-            return Ok(&mut cache.agent);
+    /// Replaces the cached [Agent] with an updated one from the API.
+    pub(crate) fn set_agent(&self, agent: Agent) -> STResult<()> {
+        let before = std::mem::replace(&mut *self.shared_cache()?.agent.write().unwrap(), agent);
+
+        let after = self.agent()?.credits;
+        if before.credits != after {
+            self.emit(ClientEvent::CreditsChanged {
+                before: before.credits,
+                after,
+            });
         }
 
-        Err(SpaceTradersError::EmptyCache(None))
+        Ok(())
     }
 
     /// Get all contracts associated w/ the current agent.
-    pub fn contracts(&self) -> STResult<&Vec<Contract>> {
-        if let Some(cache) = &self.cache {
-            return Ok(&cache.contracts);
+    pub fn contracts(&self) -> STResult<Vec<Contract>> {
+        Ok(self
+            .shared_cache()?
+            .contracts
+            .read()
+            .unwrap()
+            .iter()
+            .map(|contract| contract.read().unwrap().clone())
+            .collect())
+    }
+
+    /// Get all ships owned by the current agent.
+    pub(crate) fn ships(&self) -> STResult<Vec<Ship>> {
+        Ok(self
+            .shared_cache()?
+            .ships
+            .read()
+            .unwrap()
+            .iter()
+            .map(|ship| ship.read().unwrap().clone())
+            .collect())
+    }
+
+    pub(crate) fn token(&self) -> STResult<String> {
+        self.token
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(SpaceTradersError::TokenNotSet)
+    }
+
+    pub(crate) fn token_set(&self) -> bool {
+        self.token.read().unwrap().is_some()
+    }
+
+    pub(crate) fn shared_cache(&self) -> STResult<Arc<SharedCache>> {
+        self.cache
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(SpaceTradersError::EmptyCache {
+                operation: None,
+                data: None,
+            })
+    }
+
+    /// Replaces the cached data of the client (and all of its clones).
+    pub(crate) fn set_cache(&self, cache: CachedInfo) {
+        for ship in &cache.ships {
+            self.scheduler.record_nav(&ship.symbol, &ship.nav);
         }
 
-        Err(SpaceTradersError::EmptyCache(None))
+        *self.cache.write().unwrap() = Some(Arc::new(cache.into()));
+
+        self.emit(ClientEvent::CacheReplaced);
+    }
+
+    /// Creates the headers required for authenticated API calls.
+    ///
+    /// **NOTE:** `Content-Length` must be added separately for `POST` requests without a body.
+    pub(crate) fn auth_headers(&self) -> STResult<HeaderMap> {
+        let mut headers = HeaderMap::with_capacity(3);
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", self.token()?))?,
+        );
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        Ok(headers)
     }
 
     pub fn starting_system(&self) -> STResult<Symbol> {
-        if let Some(cache) = &self.cache {
-            Ok(cache.ships[0].nav.system_symbol.clone())
-        } else {
-            Err(SpaceTradersError::EmptyCache(None))
-        }
+        let cache = self
+            .shared_cache()
+            .map_err(|err| err.in_operation("starting_system"))?;
+        let ships = cache.ships.read().unwrap();
+        let ship = ships
+            .first()
+            .ok_or(SpaceTradersError::EmptyCache {
+                operation: Some("starting_system"),
+                data: None,
+            })?
+            .read()
+            .unwrap();
+
+        Ok(ship.nav.system_symbol.clone())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "mock")]
     use crate::{
         contract::ContractType,
         faction::FactionTraitSymbol,
+        mock::MockServer,
         ship::{
             Deposit, EngineSymbol, FlightMode, FrameSymbol, ModuleSymbol, MountSymbol,
             ReactorSymbol, Role, Rotation, ShipStatus,
@@ -247,6 +470,7 @@ mod tests {
 
     use super::*;
 
+    #[cfg(feature = "mock")]
     fn gen_callsign() -> String {
         use uuid::Uuid;
 
@@ -255,6 +479,7 @@ mod tests {
         callsign.to_string().get(0..13).unwrap().to_uppercase()
     }
 
+    #[cfg(feature = "mock")]
     fn check_default_values(cache: CachedInfo, callsign: &str) {
         let agent = cache.agent;
         assert_eq!(agent.symbol, callsign);
@@ -475,28 +700,80 @@ mod tests {
         assert_eq!(cargo.inventory[0].units, 15);
     }
 
+    #[cfg(feature = "mock")]
     #[tokio::test]
-    async fn can_register_agent() {
+    async fn can_register_agent() -> STResult<()> {
         let callsign = gen_callsign();
+        let server = MockServer::start().await?;
 
-        let mut client = SpaceTradersClient::default();
-        client.register_callsign(&callsign, None).await.unwrap();
+        let client = server.client();
+        client.register_callsign(&callsign, None).await?;
 
-        assert!(client.token_set);
+        assert!(client.token_set());
+        check_default_values(client.shared_cache()?.snapshot(), &callsign);
+
+        Ok(())
     }
 
+    #[cfg(feature = "mock")]
     #[tokio::test]
     async fn can_save_and_load_client() -> STResult<()> {
         let callsign = "TST-RS-04";
+        let server = MockServer::start().await?;
+        let client = server.client();
+        client.register_callsign(callsign, None).await?;
 
-        let saved_client = SpaceTradersClient::load_saved()?;
+        let path = std::env::temp_dir().join(format!("{}.save", uuid::Uuid::new_v4()));
+        client.save_client_to(&path)?;
+        let saved_client = SpaceTradersClient::load_saved_from(&path);
+        std::fs::remove_file(&path)?;
+        let saved_client = saved_client?;
 
-        assert!(saved_client.token_set);
+        assert!(saved_client.token_set());
 
-        let saved_cache = saved_client.cache.unwrap();
+        let saved_cache = saved_client.shared_cache()?.snapshot();
         assert_eq!(saved_cache.ships[0].symbol, "TST-RS-04-1");
         check_default_values(saved_cache, callsign);
 
         Ok(())
     }
+
+    #[test]
+    fn clones_share_token_and_cache() -> STResult<()> {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<SpaceTradersClient>();
+
+        let client = SpaceTradersClient::new();
+        let clone = client.clone();
+        assert!(!clone.token_set());
+        assert!(clone.agent().is_err());
+
+        *client.token.write().unwrap() = Some("token".into());
+        client.set_cache(serde_json::from_value(serde_json::json!({
+            "agent": {
+                "accountId": "clhr6zx0q07s1s60djj5u61ml",
+                "symbol": "TST-RS-04",
+                "headquarters": "X1-ZA40-15970B",
+                "credits": 100000
+            },
+            "contracts": [],
+            "faction": {
+                "symbol": "COSMIC",
+                "name": "Cosmic Engineers",
+                "description": "Engineers.",
+                "headquarters": "X1-ZA40-15970B",
+                "traits": []
+            },
+            "ships": []
+        }))?);
+        assert!(clone.token_set());
+        assert_eq!(clone.agent()?.credits, 100_000);
+
+        let mut agent = clone.agent()?;
+        agent.credits = 50_000;
+        clone.set_agent(agent)?;
+        assert_eq!(client.agent()?.credits, 50_000);
+
+        Ok(())
+    }
 }
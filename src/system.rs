@@ -0,0 +1,87 @@
+use crate::{
+    conditional_types::Symbol, faction::FactionSymbol, space_traders_client::SpaceTradersClient,
+    waypoint::WaypointType, ResponseData, STResult, SpaceTradersError,
+};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION};
+use serde::{Deserialize, Serialize};
+
+/// Represents a `System` in the API.
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct System {
+    pub(crate) symbol: Symbol,
+    pub(crate) sector_symbol: Symbol,
+    #[serde(rename = "type")]
+    pub(crate) system_type: SystemType,
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) waypoints: Vec<SystemWaypoint>,
+    pub(crate) factions: Vec<SystemFaction>,
+}
+
+#[derive(Deserialize, Debug, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum SystemType {
+    NeutronStar,
+    RedStar,
+    OrangeStar,
+    BlueStar,
+    YoungStar,
+    WhiteDwarf,
+    BlackHole,
+    Hypergiant,
+    Nebula,
+    Unstable,
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SystemWaypoint {
+    pub(crate) symbol: Symbol,
+    #[serde(rename = "type")]
+    pub(crate) waypoint_type: WaypointType,
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SystemFaction {
+    pub(crate) symbol: FactionSymbol,
+}
+
+impl SpaceTradersClient {
+    /// Get info on a specific system.
+    pub async fn view_system(&self, system_symbol: &Symbol) -> STResult<System> {
+        #[cfg(feature = "sqlite")]
+        if let Some(system) = self.cached_system(system_symbol)? {
+            return Ok(system);
+        }
+
+        let url = format!("{}/systems/{}", self.base_url, system_symbol);
+
+        let mut headers = HeaderMap::with_capacity(2);
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", self.token()?))?,
+        );
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+
+        // Send request
+        self.rate_limiter.acquire().await;
+        let res = self.get(url).headers(headers).send().await?;
+
+        match res.json::<ResponseData<System>>()? {
+            ResponseData::Data { data } => {
+                #[cfg(feature = "sqlite")]
+                if let Some(cache) = &self.galaxy_cache {
+                    cache.store_system(&data)?;
+                }
+
+                Ok(data)
+            }
+            ResponseData::PaginatedData { .. } => unreachable!(),
+            ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Finds the most profitable trade routes in a system using recorded market prices.
+//!
+//! A trade route is a loop between two markets: buy a cargo hold of a good at one market, fly to
+//! another market and sell it there, then fly back. Routes are ranked by their profit per hour,
+//! which accounts for the cost of the fuel used and the time spent travelling.
+//!
+//! # Example
+//! ```
+//! # use space_traders::prelude::*;
+//! let store = InMemoryMarketStore::new();
+//! let planner = RoutePlanner::new(vec![], 30, 1200);
+//!
+//! // Nothing has been recorded yet, so there are no routes to take
+//! let routes = find_trade_routes(&store, &planner, "X1-ZA40", 60).unwrap();
+//! assert!(routes.is_empty());
+//! ```
+
+use crate::{
+    conditional_types::Symbol,
+    market_history::{MarketStore, PriceRecord},
+    route_planner::{RouteNode, RoutePlanner},
+    space_traders_client::SpaceTradersClient,
+    system::System,
+    STResult, SpaceTradersError,
+};
+
+/// The trade symbol of fuel in markets.
+pub(crate) const FUEL: &str = "FUEL";
+
+/// A loop between two markets for a single good.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeRoute {
+    pub trade_symbol: Symbol,
+    /// The market the good is bought at.
+    pub buy_at: Symbol,
+    /// The market the good is sold at.
+    pub sell_at: Symbol,
+    /// The number of units traded per loop, at most the trade volume of both markets.
+    pub units: i64,
+    pub purchase_price: i64,
+    pub sell_price: i64,
+    /// The credits spent on fuel per loop.
+    pub fuel_cost: i64,
+    /// The time (in seconds) it takes to complete one loop.
+    pub travel_time: i64,
+    /// The credits earned per loop, after paying for fuel.
+    pub profit: i64,
+    pub profit_per_hour: f64,
+}
+
+/// Finds all profitable trade routes in the system, sorted by profit per hour (highest first).
+///
+/// Only the latest recorded price of each good is used. Fuel is priced at the cheapest recorded
+/// `FUEL` purchase price in the system; if fuel prices haven't been recorded, fuel is considered
+/// free.
+///
+/// Each unit of fuel the ship burns is charged at the full market price of one unit of `FUEL`,
+/// which is how the mock server charges for refuelling. The live API fills 100
+/// units of the tank per market unit, so against it the fuel costs are an overestimate.
+pub fn find_trade_routes(
+    store: &dyn MarketStore,
+    planner: &RoutePlanner,
+    system_symbol: &str,
+    cargo_capacity: i64,
+) -> STResult<Vec<TradeRoute>> {
+    let prices = store.latest_prices(system_symbol)?;
+
+    let fuel_price = prices
+        .iter()
+        .filter(|record| record.trade_symbol == FUEL)
+        .map(|record| record.purchase_price)
+        .min()
+        .unwrap_or(0);
+
+    let mut routes = Vec::new();
+    for buy in &prices {
+        for sell in &prices {
+            if let Some(route) = evaluate(planner, buy, sell, cargo_capacity, fuel_price) {
+                routes.push(route);
+            }
+        }
+    }
+
+    routes.sort_by(|a, b| b.profit_per_hour.total_cmp(&a.profit_per_hour));
+
+    Ok(routes)
+}
+
+/// Evaluates a single buy/sell pair, returning `None` if it isn't a profitable loop.
+///
+/// The ship is assumed to leave the buy market with a full tank. It only refuels at the sell
+/// market if fuel is sold there, otherwise the return leg starts with the fuel left over.
+fn evaluate(
+    planner: &RoutePlanner,
+    buy: &PriceRecord,
+    sell: &PriceRecord,
+    cargo_capacity: i64,
+    fuel_price: i64,
+) -> Option<TradeRoute> {
+    if buy.trade_symbol != sell.trade_symbol
+        || buy.waypoint_symbol == sell.waypoint_symbol
+        || sell.sell_price <= buy.purchase_price
+    {
+        return None;
+    }
+
+    let fuel_capacity = planner.fuel_capacity();
+    let outbound = planner.plan(&buy.waypoint_symbol, &sell.waypoint_symbol, fuel_capacity)?;
+
+    // The last leg of the outbound route always starts with a full tank
+    let fuel_left = match planner.node(&sell.waypoint_symbol) {
+        Some(node) if node.refuel => fuel_capacity,
+        _ => fuel_capacity - outbound.legs.last().map_or(0, |leg| leg.fuel),
+    };
+    let inbound = planner.plan(&sell.waypoint_symbol, &buy.waypoint_symbol, fuel_left)?;
+
+    // Trading more than the trade volume at once moves the price
+    let units = cargo_capacity.min(buy.trade_volume).min(sell.trade_volume);
+
+    let travel_time = outbound.travel_time + inbound.travel_time;
+    let fuel_cost = (outbound.fuel + inbound.fuel) * fuel_price;
+    let profit = units * (sell.sell_price - buy.purchase_price) - fuel_cost;
+    if profit <= 0 {
+        return None;
+    }
+
+    Some(TradeRoute {
+        trade_symbol: buy.trade_symbol.clone(),
+        buy_at: buy.waypoint_symbol.clone(),
+        sell_at: sell.waypoint_symbol.clone(),
+        units,
+        purchase_price: buy.purchase_price,
+        sell_price: sell.sell_price,
+        fuel_cost,
+        travel_time,
+        profit,
+        profit_per_hour: profit as f64 * 3600. / travel_time.max(1) as f64,
+    })
+}
+
+impl SpaceTradersClient {
+    /// Creates a [RoutePlanner] for the given ship over the waypoints of the system.
+    ///
+    /// Waypoints are marked as refuelling stops if a `FUEL` price has been recorded there in the
+    /// attached [MarketStore].
+    pub fn route_planner(&self, ship_symbol: &Symbol, system: &System) -> STResult<RoutePlanner> {
+        let ship = self.get_ship(ship_symbol)?;
+
+        let fuel_stops: Vec<Symbol> = match self.market_store() {
+            Some(store) => store
+                .latest_prices(&system.symbol)?
+                .into_iter()
+                .filter(|record| record.trade_symbol == FUEL)
+                .map(|record| record.waypoint_symbol)
+                .collect(),
+            None => vec![],
+        };
+
+        let nodes = system
+            .waypoints
+            .iter()
+            .map(|waypoint| {
+                RouteNode::new(
+                    waypoint.symbol.clone(),
+                    waypoint.x,
+                    waypoint.y,
+                    fuel_stops.contains(&waypoint.symbol),
+                )
+            })
+            .collect();
+
+        Ok(RoutePlanner::new(
+            nodes,
+            *ship.engine.speed,
+            *ship.fuel.capacity,
+        ))
+    }
+
+    /// Finds the most profitable trade routes in the system for the given ship.
+    ///
+    /// See [find_trade_routes] for details.
+    pub fn find_trade_routes(
+        &self,
+        ship_symbol: &Symbol,
+        system: &System,
+    ) -> STResult<Vec<TradeRoute>> {
+        let store = self
+            .market_store()
+            .ok_or(SpaceTradersError::MarketStoreNotSet)?;
+        let planner = self.route_planner(ship_symbol, system)?;
+        let cargo_capacity = *self.get_ship(ship_symbol)?.cargo.capacity;
+
+        find_trade_routes(store, &planner, &system.symbol, cargo_capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_history::{InMemoryMarketStore, SupplyLevel};
+    use chrono::Utc;
+
+    fn symbol(symbol: &str) -> Symbol {
+        Symbol::new(symbol).unwrap()
+    }
+
+    fn record(waypoint: &str, good: &str, purchase_price: i64, sell_price: i64) -> PriceRecord {
+        PriceRecord {
+            waypoint_symbol: symbol(waypoint),
+            system_symbol: symbol("X1-A"),
+            trade_symbol: symbol(good),
+            purchase_price,
+            sell_price,
+            supply: SupplyLevel::Moderate,
+            trade_volume: 100,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn ranks_routes_by_profit_per_hour() -> STResult<()> {
+        let store = InMemoryMarketStore::new();
+        store.record(&[
+            record("X1-A-1", "FUEL", 1, 1),
+            record("X1-A-1", "IRON_ORE", 10, 8),
+            record("X1-A-1", "COPPER_ORE", 20, 18),
+            record("X1-A-2", "IRON_ORE", 25, 20),
+            record("X1-A-3", "COPPER_ORE", 50, 45),
+        ])?;
+
+        let planner = RoutePlanner::new(
+            vec![
+                RouteNode::new(symbol("X1-A-1"), 0, 0, true),
+                RouteNode::new(symbol("X1-A-2"), 30, 0, false),
+                RouteNode::new(symbol("X1-A-3"), 300, 0, false),
+            ],
+            30,
+            1000,
+        );
+
+        let routes = find_trade_routes(&store, &planner, "X1-A", 100)?;
+        assert_eq!(routes.len(), 2);
+
+        // Copper is more profitable per loop, but iron is much closer
+        let iron = &routes[0];
+        assert_eq!(iron.trade_symbol, "IRON_ORE");
+        assert_eq!(iron.buy_at, "X1-A-1");
+        assert_eq!(iron.sell_at, "X1-A-2");
+        assert_eq!(iron.fuel_cost, 60);
+        assert_eq!(iron.profit, 100 * (20 - 10) - 60);
+        assert_eq!(iron.travel_time, 2 * (15 + 15));
+
+        let copper = &routes[1];
+        assert_eq!(copper.trade_symbol, "COPPER_ORE");
+        assert_eq!(copper.profit, 100 * (45 - 20) - 600);
+        assert!(copper.profit > iron.profit);
+        assert!(copper.profit_per_hour < iron.profit_per_hour);
+
+        Ok(())
+    }
+
+    #[test]
+    fn trades_within_the_trade_volume() -> STResult<()> {
+        let store = InMemoryMarketStore::new();
+        let mut sell = record("X1-A-2", "IRON_ORE", 25, 20);
+        sell.trade_volume = 40;
+        store.record(&[record("X1-A-1", "IRON_ORE", 10, 8), sell])?;
+
+        let planner = RoutePlanner::new(
+            vec![
+                RouteNode::new(symbol("X1-A-1"), 0, 0, true),
+                RouteNode::new(symbol("X1-A-2"), 30, 0, false),
+            ],
+            30,
+            1000,
+        );
+
+        let routes = find_trade_routes(&store, &planner, "X1-A", 100)?;
+        assert_eq!(routes[0].units, 40);
+        assert_eq!(routes[0].profit, 40 * (20 - 10));
+
+        Ok(())
+    }
+
+    #[test]
+    fn returns_with_the_fuel_left_over() -> STResult<()> {
+        let store = InMemoryMarketStore::new();
+        store.record(&[
+            record("X1-A-1", "IRON_ORE", 10, 8),
+            record("X1-A-2", "IRON_ORE", 25, 20),
+        ])?;
+
+        let mut nodes = vec![
+            RouteNode::new(symbol("X1-A-1"), 0, 0, true),
+            RouteNode::new(symbol("X1-A-2"), 600, 0, false),
+            RouteNode::new(symbol("X1-A-3"), 300, 0, true),
+        ];
+        let planner = RoutePlanner::new(nodes.clone(), 30, 1000);
+
+        // Only 400 fuel is left after flying out, so the ship has to stop on the way back
+        let routes = find_trade_routes(&store, &planner, "X1-A", 100)?;
+        assert_eq!(routes[0].travel_time, 315 + 2 * 165);
+
+        // Unless it can refuel where it sells
+        nodes[1].refuel = true;
+        let planner = RoutePlanner::new(nodes, 30, 1000);
+        let routes = find_trade_routes(&store, &planner, "X1-A", 100)?;
+        assert_eq!(routes[0].travel_time, 2 * 315);
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_unprofitable_routes() -> STResult<()> {
+        let store = InMemoryMarketStore::new();
+        store.record(&[
+            record("X1-A-1", "FUEL", 100, 90),
+            record("X1-A-1", "IRON_ORE", 10, 8),
+            record("X1-A-2", "IRON_ORE", 25, 11),
+        ])?;
+
+        let planner = RoutePlanner::new(
+            vec![
+                RouteNode::new(symbol("X1-A-1"), 0, 0, true),
+                RouteNode::new(symbol("X1-A-2"), 30, 0, false),
+            ],
+            30,
+            1000,
+        );
+
+        // Margin is too small to cover the fuel
+        assert!(find_trade_routes(&store, &planner, "X1-A", 10)?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,624 @@
+//! Sends the client's HTTP requests.
+//!
+//! Every API call of the [SpaceTradersClient] goes through its [Transport]. By default this is a
+//! [ReqwestTransport], which sends the requests over the network, but any other backend can be
+//! injected with [set_transport](SpaceTradersClient::set_transport), e.g. an in-memory fake for
+//! unit tests, a caching proxy, or a transport that records requests.
+//!
+//! # Example
+//! ```
+//! # use space_traders::prelude::*;
+//! # tokio_test::block_on(async {
+//! /// Answers every request with the same error, without touching the network.
+//! #[derive(Debug)]
+//! struct Offline;
+//!
+//! impl Transport for Offline {
+//!     fn send(&self, _request: HttpRequest) -> TransportFuture<'_> {
+//!         Box::pin(async {
+//!             Ok(HttpResponse::new(
+//!                 503,
+//!                 r#"{"error":{"code":503,"message":"Offline"}}"#,
+//!             ))
+//!         })
+//!     }
+//! }
+//!
+//! let mut client = SpaceTradersClient::new();
+//! client.set_transport(Offline);
+//! client.register_callsign("OFFLINE", None).await.unwrap_err();
+//! # })
+//! ```
+//!
+//! # Tracing
+//! With the `tracing` feature, every request is sent inside an `api_request` span at the `INFO`
+//! level, with the `method`, the `endpoint` path, the `ship` or `contract` it's for, and, once the
+//! response arrives, its `status`, `latency_ms` and the `rate_limit_remaining` and
+//! `rate_limit_reset` headers. Failed requests are logged at the `WARN` level, and the request
+//! headers at the `TRACE` level, with the bearer token redacted.
+
+use crate::{space_traders_client::SpaceTradersClient, RequestInfo, ResponseError, STResult};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Method,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt::Debug, future::Future, pin::Pin};
+
+/// A future returned by [Transport::send].
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = STResult<HttpResponse>> + Send + 'a>>;
+
+/// A backend that sends HTTP requests and returns their responses.
+pub trait Transport: Debug + Send + Sync {
+    /// Sends the request.
+    ///
+    /// Error responses from the API should be returned as responses, not errors, so the client can
+    /// report the API's error.
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_>;
+}
+
+/// An HTTP request made by the client.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+}
+
+/// The response to an [HttpRequest].
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// A response without headers.
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+
+    /// The value of the header, if it's present and valid UTF-8.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    /// Deserializes the body of the response.
+    pub fn json<T: DeserializeOwned>(&self) -> STResult<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// The default [Transport], which sends requests over the network with [reqwest].
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let mut builder = self
+                .client
+                .request(request.method, request.url)
+                .headers(request.headers);
+            if let Some(body) = request.body {
+                builder = builder.body(body);
+            }
+
+            let response = builder.send().await?;
+            let status = response.status().as_u16();
+            let headers = response.headers().clone();
+            let body = response.bytes().await?.to_vec();
+
+            Ok(HttpResponse {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+/// Builds a request and sends it through the client's [Transport].
+#[derive(Debug)]
+pub(crate) struct RequestBuilder<'a> {
+    client: &'a SpaceTradersClient,
+    request: STResult<HttpRequest>,
+}
+
+impl RequestBuilder<'_> {
+    pub(crate) fn header(mut self, name: HeaderName, value: impl AsRef<str>) -> Self {
+        if let Ok(request) = &mut self.request {
+            match HeaderValue::from_str(value.as_ref()) {
+                Ok(value) => {
+                    request.headers.insert(name, value);
+                }
+                Err(err) => self.request = Err(err.into()),
+            }
+        }
+
+        self
+    }
+
+    pub(crate) fn headers(mut self, headers: HeaderMap) -> Self {
+        if let Ok(request) = &mut self.request {
+            request.headers.extend(headers);
+        }
+
+        self
+    }
+
+    /// Sets the body of the request to the value, serialized as JSON.
+    pub(crate) fn json<T: Serialize + ?Sized>(mut self, body: &T) -> Self {
+        if let Ok(request) = &mut self.request {
+            match serde_json::to_vec(body) {
+                Ok(body) => {
+                    request.headers.insert(
+                        reqwest::header::CONTENT_TYPE,
+                        HeaderValue::from_static("application/json"),
+                    );
+                    request.body = Some(body);
+                }
+                Err(err) => self.request = Err(err.into()),
+            }
+        }
+
+        self
+    }
+
+    /// Sends the request.
+    ///
+    /// Error responses of the API are returned as a [ResponseError], along with the request they
+    /// were returned for.
+    pub(crate) async fn send(self) -> STResult<HttpResponse> {
+        let request = self.request?;
+        let (method, url) = (request.method.clone(), request.url.clone());
+        #[cfg(feature = "metrics")]
+        let start = std::time::Instant::now();
+
+        #[cfg(feature = "tracing")]
+        let result = traced::send(self.client, request).await;
+        #[cfg(not(feature = "tracing"))]
+        let result = self.client.transport.send(request).await;
+
+        let path = relative_path(&url, &self.client.base_url);
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.client.metrics {
+            metrics.record_request(
+                method.as_str(),
+                endpoint_label(path),
+                result.as_ref().ok().map(|response| response.status),
+                start.elapsed(),
+            );
+        }
+
+        let response = result?;
+        if response.status >= 400 {
+            #[derive(Debug, serde::Deserialize)]
+            struct ErrorResponse {
+                error: ResponseError,
+            }
+
+            // Responses that aren't API errors are left to the caller
+            if let Ok(ErrorResponse { error }) = response.json() {
+                return Err(error
+                    .with_request(RequestInfo {
+                        method,
+                        path: path.into(),
+                        status: response.status,
+                    })
+                    .into());
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+/// The path of the URL relative to the base URL, without the query.
+pub(crate) fn relative_path<'a>(url: &'a str, base_url: &str) -> &'a str {
+    let url = url.split('?').next().unwrap_or_default();
+    url.strip_prefix(base_url).unwrap_or(url)
+}
+
+/// The path segments that are followed by a symbol, and the placeholders that replace them.
+const PLACEHOLDERS: [(&str, &str); 5] = [
+    ("ships", "{shipSymbol}"),
+    ("contracts", "{contractId}"),
+    ("systems", "{systemSymbol}"),
+    ("waypoints", "{waypointSymbol}"),
+    ("factions", "{factionSymbol}"),
+];
+
+/// The path with symbols replaced by placeholders, e.g. `/my/ships/{shipSymbol}/orbit`.
+pub(crate) fn endpoint_label(path: &str) -> String {
+    let mut previous = "";
+    path.split('/')
+        .map(|segment| {
+            let placeholder = PLACEHOLDERS
+                .iter()
+                .find(|(collection, _)| *collection == previous)
+                .filter(|_| !segment.is_empty());
+            previous = segment;
+
+            placeholder.map_or(segment, |(_, placeholder)| placeholder)
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The path segment following `collection`, e.g. the ship symbol of `/my/ships/{ship}/dock`.
+pub(crate) fn segment_after<'a>(path: &'a str, collection: &str) -> Option<&'a str> {
+    let mut segments = path.split('/');
+    segments.find(|segment| *segment == collection)?;
+    segments.next().filter(|segment| !segment.is_empty())
+}
+
+#[cfg(feature = "tracing")]
+mod traced {
+    use super::{relative_path, segment_after, HttpRequest, HttpResponse};
+    use crate::{space_traders_client::SpaceTradersClient, STResult};
+    use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+    use std::time::Instant;
+    use tracing::{field::Empty, Instrument};
+
+    /// Sends the request inside an `api_request` span.
+    pub(super) async fn send(
+        client: &SpaceTradersClient,
+        request: HttpRequest,
+    ) -> STResult<HttpResponse> {
+        let endpoint = relative_path(&request.url, client.base_url());
+        let span = tracing::info_span!(
+            "api_request",
+            method = %request.method,
+            endpoint,
+            ship = Empty,
+            contract = Empty,
+            status = Empty,
+            latency_ms = Empty,
+            rate_limit_remaining = Empty,
+            rate_limit_reset = Empty,
+        );
+        if let Some(ship) = segment_after(endpoint, "ships") {
+            span.record("ship", ship);
+        }
+        if let Some(contract) = segment_after(endpoint, "contracts") {
+            span.record("contract", contract);
+        }
+        tracing::trace!(parent: &span, headers = ?redacted(&request.headers), "sending request");
+
+        let start = Instant::now();
+        let result = client
+            .transport
+            .send(request)
+            .instrument(span.clone())
+            .await;
+        span.record("latency_ms", start.elapsed().as_millis() as u64);
+
+        match &result {
+            Ok(response) => {
+                span.record("status", response.status);
+                if let Some(remaining) = response.header("x-ratelimit-remaining") {
+                    span.record("rate_limit_remaining", remaining);
+                }
+                if let Some(reset) = response.header("x-ratelimit-reset") {
+                    span.record("rate_limit_reset", reset);
+                }
+
+                if response.status >= 400 {
+                    let body = String::from_utf8_lossy(&response.body);
+                    tracing::warn!(parent: &span, %body, "request failed");
+                } else {
+                    tracing::debug!(parent: &span, "request succeeded");
+                }
+            }
+            Err(err) => tracing::warn!(parent: &span, error = %err, "request failed"),
+        }
+
+        result
+    }
+
+    /// The headers with the bearer token replaced, so they can be logged.
+    fn redacted(headers: &HeaderMap) -> HeaderMap {
+        let mut headers = headers.clone();
+        if headers.contains_key(AUTHORIZATION) {
+            headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer <redacted>"));
+        }
+
+        headers
+    }
+}
+
+impl SpaceTradersClient {
+    /// Sends all further requests of the client through the given [Transport].
+    ///
+    /// The transport is shared with clones made after this call.
+    pub fn set_transport(&mut self, transport: impl Transport + 'static) {
+        self.transport = std::sync::Arc::new(transport);
+    }
+
+    pub(crate) fn request(&self, method: Method, url: String) -> RequestBuilder<'_> {
+        RequestBuilder {
+            client: self,
+            request: Ok(HttpRequest {
+                method,
+                url,
+                headers: HeaderMap::new(),
+                body: None,
+            }),
+        }
+    }
+
+    pub(crate) fn get(&self, url: String) -> RequestBuilder<'_> {
+        self.request(Method::GET, url)
+    }
+
+    pub(crate) fn post(&self, url: String) -> RequestBuilder<'_> {
+        self.request(Method::POST, url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{conditional_types::Symbol, SpaceTradersError};
+    use std::sync::{Arc, Mutex};
+
+    /// Answers requests from a list of canned responses, and remembers the requests.
+    #[derive(Debug, Default)]
+    struct InMemory {
+        responses: Mutex<Vec<(String, &'static str)>>,
+        requests: Mutex<Vec<HttpRequest>>,
+    }
+
+    impl Transport for Arc<InMemory> {
+        fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+            Box::pin(async move {
+                let mut responses = self.responses.lock().unwrap();
+                let response = responses
+                    .iter()
+                    .position(|(url, _)| request.url.ends_with(url.as_str()))
+                    .map(|i| responses.remove(i).1)
+                    .unwrap_or(r#"{"error":{"code":404,"message":"Not found"}}"#);
+                self.requests.lock().unwrap().push(request);
+
+                Ok(HttpResponse::new(200, response))
+            })
+        }
+    }
+
+    const SYSTEM: &str = r#"{
+        "data": {
+            "symbol": "X1-A",
+            "sectorSymbol": "X1",
+            "type": "RED_STAR",
+            "x": 0,
+            "y": 0,
+            "waypoints": [],
+            "factions": []
+        }
+    }"#;
+
+    #[tokio::test]
+    async fn sends_requests_through_the_transport() -> STResult<()> {
+        let transport = Arc::new(InMemory::default());
+        transport
+            .responses
+            .lock()
+            .unwrap()
+            .push(("/systems/X1-A".into(), SYSTEM));
+
+        let mut client = SpaceTradersClient::new();
+        client.set_transport(transport.clone());
+        *client.token.write().unwrap() = Some("secret".into());
+
+        let system = client.view_system(&Symbol::new("X1-A").unwrap()).await?;
+        assert_eq!(system.symbol, "X1-A");
+
+        match client.view_system(&Symbol::new("X1-B").unwrap()).await {
+            Err(SpaceTradersError::ResponseError(error)) => assert_eq!(error.code, 404),
+            result => panic!("expected a not found error: {:?}", result),
+        }
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, Method::GET);
+        assert_eq!(
+            requests[0].url,
+            format!("{}/systems/X1-A", client.base_url())
+        );
+        assert_eq!(
+            requests[0].headers[reqwest::header::AUTHORIZATION],
+            "Bearer secret"
+        );
+
+        Ok(())
+    }
+
+    /// Answers every request with the same status and body.
+    #[derive(Debug)]
+    struct Fixed(u16, &'static str);
+
+    impl Transport for Fixed {
+        fn send(&self, _request: HttpRequest) -> TransportFuture<'_> {
+            Box::pin(async move { Ok(HttpResponse::new(self.0, self.1)) })
+        }
+    }
+
+    async fn post(response: Fixed, path: &str) -> STResult<HttpResponse> {
+        let mut client = SpaceTradersClient::new();
+        client.set_transport(response);
+        client
+            .post(format!("{}{}?page=1", client.base_url(), path))
+            .send()
+            .await
+    }
+
+    #[tokio::test]
+    async fn adds_the_request_to_api_errors() {
+        let cooldown = Fixed(
+            409,
+            r#"{"error":{"code":4000,"message":"Ship is on cooldown","data":{"remainingSeconds":30}}}"#,
+        );
+        let err = post(cooldown, "/my/ships/TST-RS-04-1/extract")
+            .await
+            .unwrap_err();
+        let SpaceTradersError::ResponseError(error) = &err else {
+            panic!("expected an API error: {:?}", err);
+        };
+        assert_eq!(error.code(), 4000);
+        assert_eq!(error.status(), Some(409));
+        assert_eq!(error.data().unwrap()["remainingSeconds"], 30);
+        assert_eq!(error.path(), Some("/my/ships/TST-RS-04-1/extract"));
+        assert_eq!(
+            error.operation().as_deref(),
+            Some("POST /my/ships/{shipSymbol}/extract")
+        );
+        assert_eq!(error.ship_symbol(), Some("TST-RS-04-1"));
+        assert_eq!(error.contract_id(), None);
+        assert!(err
+            .to_string()
+            .ends_with("for POST /my/ships/TST-RS-04-1/extract (409)"));
+        assert!(!err.is_retryable() && !err.is_rate_limited() && !err.is_not_found());
+
+        let missing = Fixed(404, r#"{"error":{"code":404,"message":"Not found"}}"#);
+        let err = post(missing, "/my/contracts/abc/accept").await.unwrap_err();
+        match &err {
+            SpaceTradersError::ResponseError(error) => assert_eq!(error.contract_id(), Some("abc")),
+            err => panic!("expected an API error: {:?}", err),
+        }
+        assert!(err.is_not_found() && !err.is_retryable());
+
+        let limited = Fixed(
+            429,
+            r#"{"error":{"code":429,"message":"Too many requests"}}"#,
+        );
+        let err = post(limited, "/my/ships/TST-RS-04-1/orbit")
+            .await
+            .unwrap_err();
+        assert!(err.is_rate_limited() && err.is_retryable());
+
+        // Responses without an API error are left to the caller
+        let response = post(Fixed(502, "Bad Gateway"), "/my/agent").await.unwrap();
+        assert_eq!(response.status, 502);
+    }
+
+    #[test]
+    fn describes_local_errors() {
+        let err = SpaceTradersError::EmptyCache {
+            operation: None,
+            data: Some("agent".into()),
+        };
+        assert!(err.to_string().ends_with("properly\ndata: agent"));
+
+        let err = SpaceTradersError::InvalidShipSymbol {
+            ship_symbol: "TST-RS-04-9".into(),
+            operation: None,
+        }
+        .in_operation("orbit_ship");
+        assert!(err.is_not_found() && !err.is_retryable());
+        assert!(matches!(
+            err,
+            SpaceTradersError::InvalidShipSymbol {
+                operation: Some("orbit_ship"),
+                ..
+            }
+        ));
+        assert!(err
+            .to_string()
+            .ends_with("`TST-RS-04-9` does not exist in the current client (in `orbit_ship`)"));
+    }
+
+    /// Collects the formatted output of a `tracing` subscriber.
+    #[cfg(feature = "tracing")]
+    #[derive(Debug, Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    #[cfg(feature = "tracing")]
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn traces_requests() -> STResult<()> {
+        /// Answers every request like a rate-limited ship endpoint.
+        #[derive(Debug)]
+        struct RateLimited;
+
+        impl Transport for RateLimited {
+            fn send(&self, _request: HttpRequest) -> TransportFuture<'_> {
+                Box::pin(async {
+                    let mut response = HttpResponse::new(
+                        429,
+                        r#"{"error":{"code":429,"message":"Too many requests"}}"#,
+                    );
+                    response
+                        .headers
+                        .insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+                    response.headers.insert(
+                        "x-ratelimit-reset",
+                        HeaderValue::from_static("2023-05-17T04:18:06.930Z"),
+                    );
+
+                    Ok(response)
+                })
+            }
+        }
+
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut client = SpaceTradersClient::new();
+        client.set_transport(RateLimited);
+        let err = client
+            .post(format!("{}/my/ships/TRACED-1/orbit", client.base_url()))
+            .header(reqwest::header::AUTHORIZATION, "Bearer secret")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_rate_limited());
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("method=POST"), "{}", logs);
+        assert!(
+            logs.contains("endpoint=\"/my/ships/TRACED-1/orbit\""),
+            "{}",
+            logs
+        );
+        assert!(logs.contains("ship=\"TRACED-1\""), "{}", logs);
+        assert!(logs.contains("status=429"), "{}", logs);
+        assert!(logs.contains("rate_limit_remaining=\"0\""), "{}", logs);
+        assert!(logs.contains("Too many requests"), "{}", logs);
+        assert!(logs.contains("Bearer <redacted>"), "{}", logs);
+        assert!(!logs.contains("secret"), "{}", logs);
+
+        Ok(())
+    }
+}
@@ -8,7 +8,7 @@ use crate::{
 };
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Waypoint {
     pub(crate) symbol: Symbol,
@@ -37,19 +37,19 @@ pub(crate) enum WaypointType {
     GravityWell,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Debug, Serialize, Clone, Copy)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct InnerFactionSymbol {
     pub(crate) symbol: FactionSymbol,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct OrbitalSymbol {
     pub(crate) symbol: Symbol,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Deserialize, Debug, Serialize, PartialEq, Eq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Trait {
     pub(crate) symbol: WaypointTraitSymbols,
@@ -57,7 +57,7 @@ pub(crate) struct Trait {
     pub(crate) description: Description,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Deserialize, Debug, Serialize, PartialEq, Eq, Clone, Copy)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub(crate) enum WaypointTraitSymbols {
     Uncharted,
@@ -121,7 +121,7 @@ pub(crate) enum WaypointTraitSymbols {
     Stripped,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Chart {
     pub waypoint_symbol: Option<Symbol>,
@@ -138,38 +138,47 @@ impl SpaceTradersClient {
     ) -> STResult<Waypoint> {
         use reqwest::header::AUTHORIZATION;
 
-        if !self.token_set || self.token.is_none() {
+        #[cfg(feature = "sqlite")]
+        if let Some(waypoint) = self.cached_waypoint(&waypoint_symbol)? {
+            return Ok(waypoint);
+        }
+
+        if !self.token_set() {
             return Err(SpaceTradersError::TokenNotSet);
         }
 
         let url = format!(
-            "https://api.spacetraders.io/v2/systems/{}/waypoints/{}",
-            system_symbol, waypoint_symbol
+            "{}/systems/{}/waypoints/{}",
+            self.base_url, system_symbol, waypoint_symbol
         );
 
-        let header = (AUTHORIZATION, self.token.as_ref().unwrap());
+        let header = (AUTHORIZATION, format!("Bearer {}", self.token()?));
 
         // Send request
-        let res = self
-            .client
-            .get(url)
-            .header(header.0, header.1)
-            .send()
-            .await?;
-
-        match res.json::<ResponseData<Waypoint>>().await? {
-            ResponseData::Data { data } => Ok(data),
+        self.rate_limiter.acquire().await;
+        let res = self.get(url).header(header.0, header.1).send().await?;
+
+        match res.json::<ResponseData<Waypoint>>()? {
+            ResponseData::Data { data } => {
+                #[cfg(feature = "sqlite")]
+                if let Some(cache) = &self.galaxy_cache {
+                    cache.store_waypoint(&data)?;
+                }
+
+                Ok(data)
+            }
             ResponseData::PaginatedData { .. } => unreachable!(),
             ResponseData::Error { error } => Err(SpaceTradersError::ResponseError(error)),
         }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "mock"))]
 mod tests {
     use std::str::FromStr;
 
     use super::*;
+    use crate::mock::MockServer;
 
     fn check_waypoint_default_valies(waypoint: Waypoint) {
         assert_eq!(waypoint.system_symbol, "X1-ZA40");
@@ -213,7 +222,9 @@ mod tests {
 
     #[tokio::test]
     async fn can_query_waypoint() {
-        let client = SpaceTradersClient::load_saved().unwrap();
+        let server = MockServer::start().await.unwrap();
+        let client = server.client();
+        client.register_callsign("TST-RS-04", None).await.unwrap();
 
         let waypoint = client
             .view_waypoint(